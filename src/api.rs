@@ -2,19 +2,99 @@ use std::net::TcpListener;
 
 use actix_files as fs;
 use actix_web::{
-    dev::Server, get, rt, web::{self}, App, Error, HttpRequest, HttpResponse, HttpServer
+    cookie::Cookie, delete, dev::Server, get, post, rt, web::{self}, App, Error, HttpRequest, HttpResponse, HttpServer
 };
 
-use serde::Deserialize;
+use rand::{thread_rng, Rng};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use tokio::spawn;
 
 
-use crate::{consts::{PlayerName, RoomId}, server::ServerCommander, ws_client::WsClient};
+use crate::{
+    consts::{
+        BotToken, GameId, HostToken, Identity, ParticipantToken, PlayerName, ReconnectToken,
+        RoomCode, RoomId, TournamentId,
+    },
+    history::{GameRepository, InMemoryRepository, SqliteRepository},
+    invite::InviteToken,
+    matchmaking::MatchRequest,
+    room::{config::RoomConfig, events::RoomEvent},
+    room_code,
+    server::{RoomAccess, RoomKey, ServerCommander, ServerError},
+    stats::StatsReport,
+    tournament::TournamentConfig,
+    ws_client::{WsClient, WsQueuedPlayer, WsSpectator, WsTournamentWatcher},
+};
 use crate::server::Server as CrabulServer;
 
+/// Cookie holding a guest's secret. Its hash is the identity other players see.
+const GUEST_COOKIE: &str = "crabul_guest";
+
+/// Identity of the guest behind the request, with the cookie to set when the
+/// guest is new.
+fn guest_identity(req: &HttpRequest) -> (Identity, Option<Cookie<'static>>) {
+    if let Some(cookie) = req.cookie(GUEST_COOKIE) {
+        return (identity_of(cookie.value()), None);
+    }
+    let secret = format!("{:032x}", thread_rng().gen::<u128>());
+    let identity = identity_of(&secret);
+    let cookie = Cookie::build(GUEST_COOKIE, secret)
+        .path("/")
+        .http_only(true)
+        .permanent()
+        .finish();
+    (identity, Some(cookie))
+}
+
+fn identity_of(secret: &str) -> Identity {
+    let digest = Sha256::digest(secret.as_bytes());
+    format!("guest-{:016x}", u64::from_be_bytes(digest[..8].try_into().unwrap()))
+}
+
 #[derive(Deserialize)]
 struct NameInfo {
     name: PlayerName,
+    #[serde(flatten)]
+    key: KeyInfo,
+}
+
+/// Credentials for private rooms, an invite wins over a password.
+#[derive(Deserialize)]
+struct KeyInfo {
+    password: Option<String>,
+    invite: Option<String>,
+}
+
+impl KeyInfo {
+    fn into_key(self) -> RoomKey {
+        match (self.invite, self.password) {
+            (Some(invite), _) => RoomKey::Invite(invite),
+            (None, Some(password)) => RoomKey::Password(password),
+            (None, None) => RoomKey::None,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct BotInfo {
+    token: BotToken,
+    #[serde(flatten)]
+    key: KeyInfo,
+}
+
+#[derive(Deserialize)]
+struct ResumeInfo {
+    token: ReconnectToken,
+}
+
+#[derive(Deserialize)]
+struct NewRoomInfo {
+    name: PlayerName,
+    config: Option<String>,
+    password: Option<String>,
+    #[serde(default)]
+    public: bool,
 }
 
 #[get("/connect")]
@@ -22,42 +102,184 @@ async fn new_room(
     req: HttpRequest,
     stream: web::Payload,
     server_commander: web::Data<ServerCommander>,
-    name_info: web::Query<NameInfo>,
+    room_info: web::Query<NewRoomInfo>,
 ) -> Result<HttpResponse, Error> {
-    let (res, session, stream) = actix_ws::handle(&req, stream)?;
+    let (mut res, mut session, stream) = actix_ws::handle(&req, stream)?;
+    let (identity, guest_cookie) = guest_identity(&req);
+    if let Some(cookie) = guest_cookie {
+        res.add_cookie(&cookie)?;
+    }
     let stream = stream
         .aggregate_continuations()
         .max_continuation_size(2_usize.pow(20));
 
-    let room_commander = server_commander.new_room().await;
-    let (player_id, player_channel) = room_commander
-        .new_player(name_info.name.clone())
+    let config = match &room_info.config {
+        Some(config) => serde_json::from_str(config).map_err(|_| ServerError::InvalidConfig),
+        None => Ok(RoomConfig::default()),
+    };
+    let room_info = room_info.into_inner();
+    let is_private = room_info.password.is_some();
+    let room_commander = match config {
+        Ok(config) => {
+            let access = RoomAccess {
+                public: room_info.public,
+                password: room_info.password,
+            };
+            server_commander.new_room(config, access).await
+        }
+        Err(err) => Err(err),
+    };
+    let room = match room_commander {
+        Ok(room) => room,
+        Err(err) => {
+            let _ = session.text(serde_json::to_string(&err).unwrap()).await;
+            let _ = session.close(None).await;
+            return Ok(res);
+        }
+    };
+    let profile = server_commander.get_profile(identity).await.ok();
+    let (player_id, player_channel) = room
+        .commander
+        .new_identified_player(room_info.name, profile)
         .await
         .unwrap();
 
-    let client = WsClient::new(player_id, room_commander, player_channel, stream, session);
+    if is_private {
+        if let Ok(invite) = server_commander.create_invite(room.room_id).await {
+            let event = RoomEvent::InviteCreated(invite.token);
+            let _ = session.text(serde_json::to_string(&event).unwrap()).await;
+        }
+    }
+
+    let client = WsClient::new(player_id, room.commander, player_channel, stream, session);
 
     rt::spawn(client.run());
 
     Ok(res)
 }
 
-#[get("/connect/{room_id}")]
+/// Takes the room code, e.g. `/connect/CRAB-TIDE`, or the numeric room id.
+#[get("/connect/{room_code}")]
 async fn join_room(
     req: HttpRequest,
     stream: web::Payload,
     server_commander: web::Data<ServerCommander>,
     name_info: web::Query<NameInfo>,
-    path: web::Path<RoomId>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, Error> {
+    let (mut res, mut session, stream) = actix_ws::handle(&req, stream)?;
+    let (identity, guest_cookie) = guest_identity(&req);
+    if let Some(cookie) = guest_cookie {
+        res.add_cookie(&cookie)?;
+    }
+    let stream = stream
+        .aggregate_continuations()
+        .max_continuation_size(2_usize.pow(20));
+
+    let room_id = room_code::from_code(&path).ok_or(ServerError::RoomNotFound);
+    let name_info = name_info.into_inner();
+    let room_commander = match room_id {
+        Ok(room_id) => {
+            server_commander
+                .join_room(
+                    room_id,
+                    name_info.key.into_key(),
+                    Some(name_info.name.clone()),
+                )
+                .await
+        }
+        Err(err) => Err(err),
+    };
+    let profile = server_commander.get_profile(identity).await.ok();
+    match room_commander {
+        Ok(room_commander) => match room_commander
+            .new_identified_player(name_info.name, profile)
+            .await
+        {
+            Ok((player_id, player_channel)) => {
+                let client =
+                    WsClient::new(player_id, room_commander, player_channel, stream, session);
+
+                rt::spawn(client.run());
+            }
+            Err(err) => {
+                let _ = session.text(serde_json::to_string(&err).unwrap()).await;
+                let _ = session.close(None).await;
+            }
+        },
+        Err(err) => {
+            let _ = session.text(serde_json::to_string(&err).unwrap()).await;
+            let _ = session.close(None).await;
+        }
+    }
+
+    Ok(res)
+}
+
+/// Registers a bot under a unique name, e.g. `{"name": "Clawbert"}`. The
+/// response carries the token it connects with, it is not shown again.
+#[post("/bots")]
+async fn register_bot(
+    server_commander: web::Data<ServerCommander>,
+    bot: web::Json<ParticipantInfo>,
+) -> HttpResponse {
+    match server_commander.register_bot(bot.into_inner().name).await {
+        Ok(registered) => HttpResponse::Created().json(registered),
+        Err(err) => error_response(err),
+    }
+}
+
+/// Seats a bot registered with `POST /bots`, e.g.
+/// `/bots/connect/CRAB-TIDE?token=...`, with `password` or `invite` for
+/// private rooms. The socket speaks the same protocol as `/connect`: room
+/// events come as JSON and commands go as `ClientRequest`s, e.g.
+/// `{"type":"DrawCard","request_id":1}`, each answered by a
+/// `CommandResponse`.
+///
+/// Other players see the seat as a bot in `PlayerJoined`. Whenever the game
+/// waits for the bot it gets a `YourMove` event listing the commands it may
+/// send, along with the time left. Each decision of a turn has the room's
+/// `bot_turn_countdown`, much shorter than a person's, after which the turn
+/// is played for it as on a person's timeout.
+#[get("/bots/connect/{room_code}")]
+async fn connect_bot(
+    req: HttpRequest,
+    stream: web::Payload,
+    server_commander: web::Data<ServerCommander>,
+    bot_info: web::Query<BotInfo>,
+    path: web::Path<String>,
 ) -> Result<HttpResponse, Error> {
     let (res, mut session, stream) = actix_ws::handle(&req, stream)?;
     let stream = stream
         .aggregate_continuations()
         .max_continuation_size(2_usize.pow(20));
 
-    let room_id = path.into_inner();
-    match server_commander.join_room(room_id).await {
-        Ok(room_commander) => match room_commander.new_player(name_info.name.clone()).await {
+    let bot_info = bot_info.into_inner();
+    let account = match server_commander.authenticate_bot(bot_info.token).await {
+        Ok(account) => account,
+        Err(err) => {
+            let _ = session.text(serde_json::to_string(&err).unwrap()).await;
+            let _ = session.close(None).await;
+            return Ok(res);
+        }
+    };
+    let room_commander = match room_code::from_code(&path) {
+        Some(room_id) => {
+            server_commander
+                .join_room(
+                    room_id,
+                    bot_info.key.into_key(),
+                    Some(account.name.clone()),
+                )
+                .await
+        }
+        None => Err(ServerError::RoomNotFound),
+    };
+    match room_commander {
+        Ok(room_commander) => match room_commander
+            .new_bot(account.name, Some(account.profile))
+            .await
+        {
             Ok((player_id, player_channel)) => {
                 let client =
                     WsClient::new(player_id, room_commander, player_channel, stream, session);
@@ -78,8 +300,458 @@ async fn join_room(
     Ok(res)
 }
 
+#[get("/connect/{room_code}/resume")]
+async fn resume_room(
+    req: HttpRequest,
+    stream: web::Payload,
+    server_commander: web::Data<ServerCommander>,
+    resume_info: web::Query<ResumeInfo>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, Error> {
+    let (res, mut session, stream) = actix_ws::handle(&req, stream)?;
+    let stream = stream
+        .aggregate_continuations()
+        .max_continuation_size(2_usize.pow(20));
+
+    let room_id = room_code::from_code(&path).ok_or(ServerError::RoomNotFound);
+    let room_commander = match room_id {
+        Ok(room_id) => {
+            server_commander
+                .join_room(room_id, RoomKey::Reconnect, None)
+                .await
+        }
+        Err(err) => Err(err),
+    };
+    match room_commander {
+        Ok(room_commander) => match room_commander
+            .resume_player(resume_info.token.clone())
+            .await
+        {
+            Ok((player_id, player_channel)) => {
+                let client =
+                    WsClient::new(player_id, room_commander, player_channel, stream, session);
+
+                rt::spawn(client.run());
+            }
+            Err(err) => {
+                let _ = session.text(serde_json::to_string(&err).unwrap()).await;
+                let _ = session.close(None).await;
+            }
+        },
+        Err(err) => {
+            let _ = session.text(serde_json::to_string(&err).unwrap()).await;
+            let _ = session.close(None).await;
+        }
+    }
+
+    Ok(res)
+}
+
+#[get("/spectate/{room_code}")]
+async fn spectate_room(
+    req: HttpRequest,
+    stream: web::Payload,
+    server_commander: web::Data<ServerCommander>,
+    key_info: web::Query<KeyInfo>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, Error> {
+    let (res, mut session, stream) = actix_ws::handle(&req, stream)?;
+    let stream = stream
+        .aggregate_continuations()
+        .max_continuation_size(2_usize.pow(20));
+
+    let room_id = room_code::from_code(&path).ok_or(ServerError::RoomNotFound);
+    let room_commander = match room_id {
+        Ok(room_id) => {
+            server_commander
+                .join_room(room_id, key_info.into_inner().into_key(), None)
+                .await
+        }
+        Err(err) => Err(err),
+    };
+    match room_commander {
+        Ok(room_commander) => {
+            let (spectator_id, spectator_channel) = room_commander.new_spectator().await;
+            let spectator = WsSpectator::new(
+                spectator_id,
+                room_commander,
+                spectator_channel,
+                stream,
+                session,
+            );
+
+            rt::spawn(spectator.run());
+        }
+        Err(err) => {
+            let _ = session.text(serde_json::to_string(&err).unwrap()).await;
+            let _ = session.close(None).await;
+        }
+    }
+
+    Ok(res)
+}
+
+/// Quick play: queues the player until enough others want a table of the
+/// same size, e.g. `/matchmake?name=ann&players=4&rating_band=200`.
+#[get("/matchmake")]
+async fn matchmake(
+    req: HttpRequest,
+    stream: web::Payload,
+    server_commander: web::Data<ServerCommander>,
+    request: web::Query<MatchRequest>,
+) -> Result<HttpResponse, Error> {
+    let (mut res, mut session, stream) = actix_ws::handle(&req, stream)?;
+    let (identity, guest_cookie) = guest_identity(&req);
+    if let Some(cookie) = guest_cookie {
+        res.add_cookie(&cookie)?;
+    }
+    let stream = stream
+        .aggregate_continuations()
+        .max_continuation_size(2_usize.pow(20));
+
+    let request = request.into_inner();
+    let players = request.players;
+    match server_commander.matchmake(request, identity).await {
+        Ok((ticket_id, queue_channel)) => {
+            let queued = WsQueuedPlayer::new(
+                ticket_id,
+                players,
+                server_commander.get_ref().clone(),
+                queue_channel,
+                stream,
+                session,
+            );
+            rt::spawn(queued.run());
+        }
+        Err(err) => {
+            let _ = session.text(serde_json::to_string(&err).unwrap()).await;
+            let _ = session.close(None).await;
+        }
+    }
+
+    Ok(res)
+}
+
+/// Public rooms, for players looking for a game to join.
+#[get("/rooms")]
+async fn list_rooms(server_commander: web::Data<ServerCommander>) -> HttpResponse {
+    HttpResponse::Ok().json(server_commander.list_rooms().await)
+}
+
+/// Body of `POST /rooms`, every field is optional.
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct CreateRoomRequest {
+    config: RoomConfig,
+    public: bool,
+    password: Option<String>,
+}
+
+#[derive(Serialize)]
+struct CreateRoomResponse {
+    room_id: RoomId,
+    room_code: RoomCode,
+    /// Needed to delete the room.
+    host_token: HostToken,
+    /// Only for rooms with a password.
+    invite: Option<InviteToken>,
+}
+
+#[derive(Deserialize)]
+struct HostTokenInfo {
+    host_token: HostToken,
+}
+
+fn error_response(err: ServerError) -> HttpResponse {
+    let mut response = match err {
+        ServerError::RoomNotFound
+        | ServerError::ReplayNotFound
+        | ServerError::PlayerNotFound
+        | ServerError::TournamentNotFound
+        | ServerError::NotSeated => HttpResponse::NotFound(),
+        ServerError::InvalidConfig | ServerError::InvalidBotName => HttpResponse::BadRequest(),
+        ServerError::Tournament(_) => HttpResponse::Conflict(),
+        ServerError::TooManyRooms => HttpResponse::ServiceUnavailable(),
+        ServerError::WrongPassword
+        | ServerError::InvalidInvite
+        | ServerError::InviteExpired
+        | ServerError::NotRoomHost
+        | ServerError::NotTournamentHost => HttpResponse::Forbidden(),
+        ServerError::InvalidBotToken | ServerError::InvalidParticipantToken => {
+            HttpResponse::Unauthorized()
+        }
+        ServerError::Storage(_) => HttpResponse::InternalServerError(),
+    };
+    response.json(err)
+}
+
+/// Sets up a table without taking a seat, for tools that share the link
+/// before anyone connects.
+#[post("/rooms")]
+async fn create_room(
+    server_commander: web::Data<ServerCommander>,
+    request: web::Json<CreateRoomRequest>,
+) -> HttpResponse {
+    let request = request.into_inner();
+    let is_private = request.password.is_some();
+    let access = RoomAccess {
+        public: request.public,
+        password: request.password,
+    };
+    let room = match server_commander.new_room(request.config, access).await {
+        Ok(room) => room,
+        Err(err) => return error_response(err),
+    };
+    let invite = match is_private {
+        true => server_commander
+            .create_invite(room.room_id)
+            .await
+            .ok()
+            .map(|invite| invite.token),
+        false => None,
+    };
+    HttpResponse::Created().json(CreateRoomResponse {
+        room_id: room.room_id,
+        room_code: room_code::to_code(room.room_id),
+        host_token: room.host_token,
+        invite,
+    })
+}
+
+#[get("/rooms/{room_code}")]
+async fn room_status(
+    server_commander: web::Data<ServerCommander>,
+    path: web::Path<String>,
+) -> HttpResponse {
+    let Some(room_id) = room_code::from_code(&path) else {
+        return error_response(ServerError::RoomNotFound);
+    };
+    match server_commander.room_summary(room_id).await {
+        Ok(summary) => HttpResponse::Ok().json(summary),
+        Err(err) => error_response(err),
+    }
+}
+
+/// Closes the room for everybody in it, with the token given at creation.
+#[delete("/rooms/{room_code}")]
+async fn delete_room(
+    server_commander: web::Data<ServerCommander>,
+    path: web::Path<String>,
+    token_info: web::Query<HostTokenInfo>,
+) -> HttpResponse {
+    let Some(room_id) = room_code::from_code(&path) else {
+        return error_response(ServerError::RoomNotFound);
+    };
+    let host_token = token_info.into_inner().host_token;
+    match server_commander.delete_room(room_id, host_token).await {
+        Ok(()) => HttpResponse::NoContent().finish(),
+        Err(err) => error_response(err),
+    }
+}
+
+/// Opens the registration, the response carries the token needed to start.
+#[post("/tournaments")]
+async fn create_tournament(
+    server_commander: web::Data<ServerCommander>,
+    config: web::Json<TournamentConfig>,
+) -> HttpResponse {
+    match server_commander.new_tournament(config.into_inner()).await {
+        Ok(created) => HttpResponse::Created().json(created),
+        Err(err) => error_response(err),
+    }
+}
+
+#[derive(Deserialize)]
+struct ParticipantInfo {
+    name: PlayerName,
+}
+
+/// Participants later join their tables under the name they registered with,
+/// the response carries the token their seats are fetched with.
+#[post("/tournaments/{tournament_id}/participants")]
+async fn register_participant(
+    server_commander: web::Data<ServerCommander>,
+    path: web::Path<TournamentId>,
+    participant: web::Json<ParticipantInfo>,
+) -> HttpResponse {
+    match server_commander
+        .register_participant(path.into_inner(), participant.into_inner().name)
+        .await
+    {
+        Ok(registered) => HttpResponse::Created().json(registered),
+        Err(err) => error_response(err),
+    }
+}
+
+#[derive(Deserialize)]
+struct ParticipantTokenInfo {
+    token: ParticipantToken,
+}
+
+/// The table the participant plays next, with an invite for their name only.
+#[get("/tournaments/{tournament_id}/seat")]
+async fn tournament_seat(
+    server_commander: web::Data<ServerCommander>,
+    path: web::Path<TournamentId>,
+    token_info: web::Query<ParticipantTokenInfo>,
+) -> HttpResponse {
+    let token = token_info.into_inner().token;
+    match server_commander.get_seat(path.into_inner(), token).await {
+        Ok(seat) => HttpResponse::Ok().json(seat),
+        Err(err) => error_response(err),
+    }
+}
+
+/// Closes the registration and seats the first round.
+#[post("/tournaments/{tournament_id}/start")]
+async fn start_tournament(
+    server_commander: web::Data<ServerCommander>,
+    path: web::Path<TournamentId>,
+    token_info: web::Query<HostTokenInfo>,
+) -> HttpResponse {
+    let host_token = token_info.into_inner().host_token;
+    match server_commander
+        .start_tournament(path.into_inner(), host_token)
+        .await
+    {
+        Ok(()) => HttpResponse::NoContent().finish(),
+        Err(err) => error_response(err),
+    }
+}
+
+#[get("/tournaments/{tournament_id}")]
+async fn tournament_standings(
+    server_commander: web::Data<ServerCommander>,
+    path: web::Path<TournamentId>,
+) -> HttpResponse {
+    match server_commander.get_tournament(path.into_inner()).await {
+        Ok(standings) => HttpResponse::Ok().json(standings),
+        Err(err) => error_response(err),
+    }
+}
+
+/// Streams the tables seated and their results as the tournament goes on.
+#[get("/tournaments/{tournament_id}/feed")]
+async fn tournament_feed(
+    req: HttpRequest,
+    stream: web::Payload,
+    server_commander: web::Data<ServerCommander>,
+    path: web::Path<TournamentId>,
+) -> Result<HttpResponse, Error> {
+    let (res, mut session, stream) = actix_ws::handle(&req, stream)?;
+    let stream = stream
+        .aggregate_continuations()
+        .max_continuation_size(2_usize.pow(20));
+
+    match server_commander.watch_tournament(path.into_inner()).await {
+        Ok(feed) => {
+            let watcher = WsTournamentWatcher::new(feed, stream, session);
+            rt::spawn(watcher.run());
+        }
+        Err(err) => {
+            let _ = session.text(serde_json::to_string(&err).unwrap()).await;
+            let _ = session.close(None).await;
+        }
+    }
+
+    Ok(res)
+}
+
+/// How many games the history endpoints return.
+const HISTORY_LIMIT: usize = 50;
+
+#[get("/history")]
+async fn game_history(server_commander: web::Data<ServerCommander>) -> HttpResponse {
+    match server_commander.get_history(None, HISTORY_LIMIT).await {
+        Ok(games) => HttpResponse::Ok().json(games),
+        Err(err) => error_response(err),
+    }
+}
+
+#[get("/players/{name}/history")]
+async fn player_history(
+    server_commander: web::Data<ServerCommander>,
+    path: web::Path<PlayerName>,
+) -> HttpResponse {
+    match server_commander
+        .get_history(Some(path.into_inner()), HISTORY_LIMIT)
+        .await
+    {
+        Ok(games) => HttpResponse::Ok().json(games),
+        Err(err) => error_response(err),
+    }
+}
+
+/// Stats follow the identity shown on the leaderboard, not the display name.
+#[get("/players/{identity}/stats")]
+async fn player_stats(
+    server_commander: web::Data<ServerCommander>,
+    path: web::Path<Identity>,
+) -> HttpResponse {
+    match server_commander.get_stats(path.into_inner()).await {
+        Ok(stats) => HttpResponse::Ok().json(StatsReport::from(stats)),
+        Err(err) => error_response(err),
+    }
+}
+
+/// How many players the leaderboard shows.
+const LEADERBOARD_LIMIT: usize = 100;
+
+#[get("/leaderboard")]
+async fn leaderboard(server_commander: web::Data<ServerCommander>) -> HttpResponse {
+    match server_commander.get_leaderboard(LEADERBOARD_LIMIT).await {
+        Ok(ratings) => HttpResponse::Ok().json(ratings),
+        Err(err) => error_response(err),
+    }
+}
+
+/// Streams the record of a finished game, one entry per message, hidden cards
+/// included, then closes.
+#[get("/replay/{game_id}")]
+async fn replay_game(
+    req: HttpRequest,
+    stream: web::Payload,
+    server_commander: web::Data<ServerCommander>,
+    path: web::Path<GameId>,
+) -> Result<HttpResponse, Error> {
+    let (res, mut session, _) = actix_ws::handle(&req, stream)?;
+
+    let game_id = path.into_inner();
+    let record = server_commander.get_replay(game_id).await;
+    rt::spawn(async move {
+        match record {
+            Ok(record) => {
+                for entry in record.entries {
+                    if session
+                        .text(serde_json::to_string(&entry).unwrap())
+                        .await
+                        .is_err()
+                    {
+                        return;
+                    }
+                }
+            }
+            Err(err) => {
+                let _ = session.text(serde_json::to_string(&err).unwrap()).await;
+            }
+        }
+        let _ = session.close(None).await;
+    });
+
+    Ok(res)
+}
+
+/// Games are kept in the SQLite database at `CRABUL_DB` when set, in memory
+/// otherwise.
 pub fn run(listener: TcpListener) -> Result<Server, std::io::Error> {
-    let (game_server, server_commander) = CrabulServer::new();
+    let history: Box<dyn GameRepository> = match std::env::var("CRABUL_DB") {
+        Ok(path) => Box::new(
+            SqliteRepository::open(path)
+                .map_err(|err| std::io::Error::other(format!("{err:?}")))?,
+        ),
+        Err(_) => Box::new(InMemoryRepository::default()),
+    };
+    let (game_server, server_commander) = CrabulServer::with_history(history);
 
     spawn(game_server.run());
 
@@ -88,6 +760,26 @@ pub fn run(listener: TcpListener) -> Result<Server, std::io::Error> {
         .app_data(web::Data::new(server_commander.clone()))
         .service(new_room)
         .service(join_room)
+        .service(resume_room)
+        .service(spectate_room)
+        .service(matchmake)
+        .service(register_bot)
+        .service(connect_bot)
+        .service(replay_game)
+        .service(list_rooms)
+        .service(create_room)
+        .service(room_status)
+        .service(delete_room)
+        .service(game_history)
+        .service(player_history)
+        .service(player_stats)
+        .service(leaderboard)
+        .service(create_tournament)
+        .service(register_participant)
+        .service(tournament_seat)
+        .service(start_tournament)
+        .service(tournament_standings)
+        .service(tournament_feed)
         .service(fs::Files::new("/", "./static").index_file("index.html"))
     })
     .listen(listener)?
@@ -0,0 +1,431 @@
+use std::{str::FromStr, time::Duration};
+
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use tokio::{spawn, sync::mpsc::UnboundedReceiver, time::sleep};
+
+use crate::{
+    consts::PlayerId,
+    deck::Card,
+    room::{
+        commander::RoomCommander,
+        errors::GameError,
+        events::RoomEvent,
+        server::Power,
+        snapshot::{GameSnapshot, Phase, PlayerSnapshot},
+    },
+};
+
+/// Pause before each move, so humans can follow what the bot does.
+const THINKING_TIME: Duration = Duration::from_millis(800);
+/// Score the memory bot assumes for a card it has never seen.
+const UNKNOWN_CARD_SCORE: i8 = 6;
+/// Estimated hand score under which the memory bot calls crabul.
+const CRABUL_THRESHOLD: i8 = 5;
+/// One in how many turns the random bot calls crabul.
+const RANDOM_CRABUL_ODDS: u32 = 20;
+
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Debug)]
+pub enum BotLevel {
+    /// Picks any legal move at random.
+    Random,
+    /// Remembers the cards it has seen and plays to lower its score.
+    Memory,
+}
+
+impl FromStr for BotLevel {
+    type Err = GameError;
+
+    fn from_str(level: &str) -> Result<Self, Self::Err> {
+        match level {
+            "random" => Ok(BotLevel::Random),
+            "memory" => Ok(BotLevel::Memory),
+            _ => Err(GameError::UnableToParseCommand),
+        }
+    }
+}
+
+/// A player driven by the server. It plays through the same `RoomCommander`
+/// methods as a socket client and reads the room through `get_state`, so it
+/// only knows what a human in its seat would.
+pub struct Bot {
+    player_id: PlayerId,
+    level: BotLevel,
+    room_commander: RoomCommander,
+    player_channel: UnboundedReceiver<RoomEvent>,
+    rng: StdRng,
+}
+
+impl Bot {
+    pub fn new(
+        player_id: PlayerId,
+        level: BotLevel,
+        room_commander: RoomCommander,
+        player_channel: UnboundedReceiver<RoomEvent>,
+    ) -> Self {
+        Self {
+            player_id,
+            level,
+            room_commander,
+            player_channel,
+            rng: StdRng::from_entropy(),
+        }
+    }
+
+    /// Makes the bot play the same way every time.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = StdRng::seed_from_u64(seed);
+        self
+    }
+
+    /// Seats a new bot in the room, named after the first free "Bot n", and
+    /// lets it play in the background.
+    pub async fn join(
+        room_commander: &RoomCommander,
+        level: BotLevel,
+    ) -> Result<PlayerId, GameError> {
+        Self::seat(room_commander, None, level).await
+    }
+
+    /// Same as `join`, on behalf of a player who must be the host.
+    pub async fn add(
+        room_commander: &RoomCommander,
+        host_id: PlayerId,
+        level: BotLevel,
+    ) -> Result<PlayerId, GameError> {
+        Self::seat(room_commander, Some(host_id), level).await
+    }
+
+    async fn seat(
+        room_commander: &RoomCommander,
+        host_id: Option<PlayerId>,
+        level: BotLevel,
+    ) -> Result<PlayerId, GameError> {
+        let mut idx = 1;
+        loop {
+            let name = format!("Bot {idx}");
+            let seated = match host_id {
+                Some(host_id) => room_commander.add_bot(host_id, name).await,
+                None => room_commander.new_bot(name, None).await,
+            };
+            match seated {
+                Ok((player_id, player_channel)) => {
+                    let bot = Bot::new(player_id, level, room_commander.clone(), player_channel);
+                    spawn(bot.run());
+                    return Ok(player_id);
+                }
+                Err(GameError::NameAlreadyExists) => idx += 1,
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    pub async fn run(mut self) {
+        while let Some(event) = self.player_channel.recv().await {
+            match event {
+                RoomEvent::PlayerLeft(player_id)
+                | RoomEvent::PlayerForfeited(player_id)
+                | RoomEvent::PlayerKicked(player_id)
+                | RoomEvent::PlayerEliminated(player_id)
+                    if player_id == self.player_id =>
+                {
+                    return
+                }
+                RoomEvent::RoomClosed => return,
+                RoomEvent::CardDiscarded(_, card) => self.throw_duplicate(card).await,
+                _ => {}
+            }
+            self.play().await;
+        }
+    }
+
+    /// Makes a move if the room is waiting for one from this bot.
+    async fn play(&mut self) {
+        let Ok(snapshot) = self.room_commander.get_state(self.player_id).await else {
+            return;
+        };
+        if !self.has_to_move(&snapshot) {
+            return;
+        }
+        sleep(THINKING_TIME).await;
+        // The room may have moved on while the bot was thinking.
+        let Ok(snapshot) = self.room_commander.get_state(self.player_id).await else {
+            return;
+        };
+        if !self.has_to_move(&snapshot) {
+            return;
+        }
+
+        let player_id = self.player_id;
+        let room_commander = self.room_commander.clone();
+        let _ = match snapshot.phase {
+            Phase::NotStarted => room_commander.set_lobby_ready(player_id, true).await,
+            Phase::PeekingPhase => room_commander.set_player_ready(player_id).await,
+            Phase::StartTurn(_) => {
+                if self.wants_crabul(&snapshot) {
+                    room_commander.go_crabul(player_id).await
+                } else {
+                    room_commander.draw_card(player_id).await
+                }
+            }
+            Phase::MiddleTurn(_) => match self.swap_for(&snapshot) {
+                Some(card_idx) => room_commander.swap_card(player_id, card_idx).await,
+                None => room_commander.discard_card(player_id).await,
+            },
+            Phase::PowerStage(_, power) => self.use_power(&snapshot, power).await,
+            _ => Ok(()),
+        };
+    }
+
+    fn has_to_move(&self, snapshot: &GameSnapshot) -> bool {
+        match snapshot.phase {
+            Phase::NotStarted | Phase::PeekingPhase => !self.me(snapshot).ready,
+            Phase::StartTurn(player_id)
+            | Phase::MiddleTurn(player_id)
+            | Phase::PowerStage(player_id, _) => player_id == self.player_id,
+            _ => false,
+        }
+    }
+
+    fn wants_crabul(&mut self, snapshot: &GameSnapshot) -> bool {
+        if snapshot.crabul_player.is_some() {
+            return false;
+        }
+        match self.level {
+            BotLevel::Random => self.rng.gen_ratio(1, RANDOM_CRABUL_ODDS),
+            BotLevel::Memory => estimated_score(self.me(snapshot)) <= CRABUL_THRESHOLD,
+        }
+    }
+
+    /// Where to put the drawn card, `None` to discard it.
+    fn swap_for(&mut self, snapshot: &GameSnapshot) -> Option<usize> {
+        let me = self.me(snapshot);
+        if me.hand_size == 0 {
+            return None;
+        }
+        match self.level {
+            BotLevel::Random => self
+                .rng
+                .gen_bool(0.5)
+                .then(|| self.rng.gen_range(0..me.hand_size)),
+            BotLevel::Memory => {
+                let drawn_card = snapshot.drawn_card?;
+                let card_idx = worst_card(me);
+                (drawn_card.get_score() < estimate(me.known_cards[card_idx])).then_some(card_idx)
+            }
+        }
+    }
+
+    async fn use_power(&mut self, snapshot: &GameSnapshot, power: Power) -> Result<(), GameError> {
+        let player_id = self.player_id;
+        let room_commander = self.room_commander.clone();
+        let me = self.me(snapshot);
+        match power {
+            Power::PeekOwnCard => {
+                let card_idx = match self.level {
+                    BotLevel::Random => self.random_card(me),
+                    BotLevel::Memory => unknown_card(me).or_else(|| self.random_card(me)),
+                };
+                let card_idx = card_idx.ok_or(GameError::InvalidCardIndex)?;
+                room_commander.peek_own_card(player_id, card_idx).await
+            }
+            Power::PeekOtherCard => {
+                let (other_player_id, other_card_idx) = self
+                    .pick_other_card(snapshot, |card| card.is_none().then_some(0))
+                    .ok_or(GameError::PlayerNotFound)?;
+                room_commander
+                    .peek_other_card(player_id, other_player_id, other_card_idx)
+                    .await
+            }
+            Power::BlindSwap => {
+                let card_idx = match self.level {
+                    BotLevel::Random => self.random_card(me),
+                    BotLevel::Memory => (me.hand_size > 0).then(|| worst_card(me)),
+                }
+                .ok_or(GameError::InvalidCardIndex)?;
+                let (other_player_id, other_card_idx) = self
+                    .pick_other_card(snapshot, |card| Some(estimate(card)))
+                    .ok_or(GameError::PlayerNotFound)?;
+                room_commander
+                    .blind_swap(player_id, card_idx, other_player_id, other_card_idx)
+                    .await
+            }
+            Power::CheckAndSwapStage1 => {
+                let (other_player_id, other_card_idx) = self
+                    .pick_other_card(snapshot, |card| Some(estimate(card)))
+                    .ok_or(GameError::PlayerNotFound)?;
+                room_commander
+                    .check_and_swap_stage1(player_id, other_player_id, other_card_idx)
+                    .await
+            }
+            Power::CheckAndSwapStage2(other_player_id, other_card_idx) => {
+                let card_idx = match self.level {
+                    BotLevel::Random => {
+                        let card_idx = self.random_card(me);
+                        card_idx.filter(|_| self.rng.gen_bool(0.5))
+                    }
+                    BotLevel::Memory => {
+                        let peeked_card = snapshot
+                            .players
+                            .iter()
+                            .find(|other| other.player_id == other_player_id)
+                            .and_then(|other| other.known_cards.get(other_card_idx).copied())
+                            .flatten();
+                        (me.hand_size > 0)
+                            .then(|| worst_card(me))
+                            .filter(|card_idx| {
+                                peeked_card.is_some_and(|peeked_card| {
+                                    peeked_card.get_score() < estimate(me.known_cards[*card_idx])
+                                })
+                            })
+                    }
+                };
+                room_commander
+                    .check_and_swap_stage2(player_id, card_idx)
+                    .await
+            }
+        }
+    }
+
+    /// The memory bot throws any card of its own it knows to match the discard.
+    async fn throw_duplicate(&mut self, discarded_card: Card) {
+        if self.level != BotLevel::Memory {
+            return;
+        }
+        let Ok(snapshot) = self.room_commander.get_state(self.player_id).await else {
+            return;
+        };
+        if snapshot.discard_pile_top != Some(discarded_card) {
+            return;
+        }
+        let matching_card = self.me(&snapshot).known_cards.iter().position(|card| {
+            card.is_some_and(|card| card.get_value() == discarded_card.get_value())
+        });
+        if let Some(card_idx) = matching_card {
+            let _ = self
+                .room_commander
+                .throw_same_card(self.player_id, self.player_id, card_idx)
+                .await;
+        }
+    }
+
+    /// A card of another player: the lowest ranked one for the memory bot,
+    /// `None` ranks excluding a card, at random otherwise.
+    fn pick_other_card(
+        &mut self,
+        snapshot: &GameSnapshot,
+        rank: impl Fn(Option<Card>) -> Option<i8>,
+    ) -> Option<(PlayerId, usize)> {
+        let others: Vec<&PlayerSnapshot> = snapshot
+            .players
+            .iter()
+            .filter(|other| {
+                other.player_id != self.player_id
+                    && other.hand_size > 0
+                    && Some(other.player_id) != snapshot.crabul_player
+            })
+            .collect();
+        if self.level == BotLevel::Memory {
+            let best = others
+                .iter()
+                .flat_map(|other| {
+                    other
+                        .known_cards
+                        .iter()
+                        .enumerate()
+                        .filter_map(|(idx, card)| {
+                            rank(*card).map(|rank| (rank, other.player_id, idx))
+                        })
+                })
+                .min_by_key(|(rank, ..)| *rank);
+            if let Some((_, other_player_id, other_card_idx)) = best {
+                return Some((other_player_id, other_card_idx));
+            }
+        }
+        let other = others.choose(&mut self.rng)?;
+        Some((other.player_id, self.rng.gen_range(0..other.hand_size)))
+    }
+
+    fn random_card(&mut self, player: &PlayerSnapshot) -> Option<usize> {
+        (player.hand_size > 0).then(|| self.rng.gen_range(0..player.hand_size))
+    }
+
+    fn me<'a>(&self, snapshot: &'a GameSnapshot) -> &'a PlayerSnapshot {
+        snapshot
+            .players
+            .iter()
+            .find(|player| player.player_id == self.player_id)
+            .unwrap()
+    }
+}
+
+fn estimate(card: Option<Card>) -> i8 {
+    card.map_or(UNKNOWN_CARD_SCORE, |card| card.get_score())
+}
+
+fn estimated_score(player: &PlayerSnapshot) -> i8 {
+    player.known_cards.iter().map(|card| estimate(*card)).sum()
+}
+
+/// Index of the card believed to score the most. The hand must not be empty.
+fn worst_card(player: &PlayerSnapshot) -> usize {
+    (0..player.known_cards.len())
+        .max_by_key(|idx| estimate(player.known_cards[*idx]))
+        .unwrap()
+}
+
+fn unknown_card(player: &PlayerSnapshot) -> Option<usize> {
+    player.known_cards.iter().position(Option::is_none)
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::time::timeout;
+
+    use crate::room::{config::RoomConfig, server::RoomServer};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn bots_play_a_full_game() {
+        tokio::time::pause();
+        let config = RoomConfig {
+            seed: Some(3),
+            ..Default::default()
+        };
+        let (room_server, room_commander) = RoomServer::new(config);
+        spawn(room_server.run());
+        let (_, mut spectator) = room_commander.new_spectator().await;
+
+        let host = Bot::join(&room_commander, BotLevel::Memory).await.unwrap();
+        Bot::join(&room_commander, BotLevel::Random).await.unwrap();
+        room_commander.start_game(host).await.unwrap();
+
+        let game_over = timeout(Duration::from_secs(24 * 3600), async {
+            while let Some(event) = spectator.recv().await {
+                if let RoomEvent::GameTerminated(final_score) = event {
+                    return Some(final_score);
+                }
+            }
+            None
+        })
+        .await;
+        assert!(matches!(game_over, Ok(Some(final_score)) if final_score.scores.len() == 2));
+    }
+
+    #[tokio::test]
+    async fn bots_get_free_names() {
+        let (room_server, room_commander) = RoomServer::new(RoomConfig::default());
+        spawn(room_server.run());
+        let (_, mut player) = room_commander.new_player("Bot 1".into()).await.unwrap();
+        let bot_id = Bot::join(&room_commander, BotLevel::Random).await.unwrap();
+
+        player.try_recv().unwrap();
+        let received_event = player.try_recv().unwrap();
+        assert!(matches!(
+            received_event,
+            RoomEvent::PlayerJoined { player_id, player_name, .. }
+                if player_id == bot_id && player_name == "Bot 2"
+        ));
+    }
+}
@@ -1,3 +1,17 @@
 pub type RoomId = u16;
+/// Human friendly form of a `RoomId`, see `room_code`.
+pub type RoomCode = String;
+pub type GameId = u64;
 pub type PlayerId = u16;
+pub type SpectatorId = u16;
 pub type PlayerName = String;
+/// Stable identity of a person across rooms and games, e.g. a guest id.
+pub type Identity = String;
+pub type ReconnectToken = String;
+/// Given to whoever creates a room over REST, to manage it.
+pub type HostToken = String;
+/// Secret a bot program connects with, handed out once when registering it.
+pub type BotToken = String;
+/// Secret a tournament participant fetches their seat invites with.
+pub type ParticipantToken = String;
+pub type TournamentId = u32;
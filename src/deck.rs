@@ -1,8 +1,14 @@
 use std::mem;
 
-use rand::{seq::SliceRandom, thread_rng};
+use rand::{seq::SliceRandom, thread_rng, Rng};
 use serde::{Deserialize, Serialize};
 
+/// Ranks of each suit, from ace to king.
+const RANKS: u8 = 13;
+const JOKERS: usize = 2;
+/// Cards in a full deck, jokers included.
+pub const DECK_SIZE: usize = 4 * RANKS as usize + JOKERS;
+
 #[derive(Deserialize, Serialize, Copy, Clone, PartialEq)]
 pub enum Card {
     Clubs(u8),
@@ -35,24 +41,24 @@ pub struct Deck {
 
 impl Default for Deck {
     fn default() -> Self {
-        Self::new()
+        Self::new(&mut thread_rng())
     }
 }
 
 impl Deck {
-    pub fn new() -> Self {
-        let mut cards = vec![];
-        for i in 1..=13 {
+    /// Builds a full deck shuffled with the given rng, so a seeded rng always
+    /// gives the same deal.
+    pub fn new(rng: &mut impl Rng) -> Self {
+        let mut cards = Vec::with_capacity(DECK_SIZE);
+        for i in 1..=RANKS {
             cards.push(Card::Clubs(i));
             cards.push(Card::Diamonds(i));
             cards.push(Card::Hearts(i));
             cards.push(Card::Spade(i));
         }
+        cards.extend([Card::Joker; JOKERS]);
 
-        cards.push(Card::Joker);
-        cards.push(Card::Joker);
-
-        cards.shuffle(&mut thread_rng());
+        cards.shuffle(rng);
         Deck {
             cards,
             discard_pile: vec![],
@@ -75,11 +81,8 @@ impl Deck {
     pub fn get_last_discarded(&self) -> Option<&Card> {
         self.discard_pile.last()
     }
-}
 
-pub fn testing_deck(cards: Vec<Card>) -> Deck {
-    Deck {
-        cards,
-        discard_pile: vec![],
+    pub fn remaining(&self) -> usize {
+        self.cards.len()
     }
 }
@@ -0,0 +1,706 @@
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    path::Path,
+    sync::mpsc,
+    thread,
+};
+
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    consts::{GameId, Identity, PlayerId, PlayerName, RoomId},
+    rating::{PlayerRating, Rating},
+    room::{
+        config::RoomConfig,
+        events::RoomEvent,
+        record::{GameRecord, RecordKind, RecordedCommand},
+        server::FinalScore,
+    },
+    stats::PlayerStats,
+};
+
+/// Records hold every hand, the in-memory history only keeps the latest ones.
+pub const RECORDS_IN_MEMORY: usize = 100;
+
+#[derive(Serialize, Debug)]
+pub enum StorageError {
+    Database(String),
+    Serialization(String),
+}
+
+impl From<rusqlite::Error> for StorageError {
+    fn from(err: rusqlite::Error) -> Self {
+        StorageError::Database(err.to_string())
+    }
+}
+
+impl From<serde_json::Error> for StorageError {
+    fn from(err: serde_json::Error) -> Self {
+        StorageError::Serialization(err.to_string())
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+pub struct GamePlayer {
+    pub player_id: PlayerId,
+    pub name: PlayerName,
+    pub identity: Option<Identity>,
+}
+
+/// A registered bot. Only a hash of its token is kept.
+#[derive(Clone, PartialEq, Debug)]
+pub struct BotRecord {
+    pub name: PlayerName,
+    pub token_hash: Vec<u8>,
+}
+
+/// What is kept of a finished game once its room is gone.
+#[derive(Deserialize, Serialize, Clone)]
+pub struct GameSummary {
+    pub game_id: GameId,
+    pub room_id: RoomId,
+    /// Unix time in milliseconds at which the room was created.
+    pub started_at: u64,
+    pub duration_ms: u64,
+    pub config: RoomConfig,
+    /// Players who were dealt cards, in seat order.
+    pub players: Vec<GamePlayer>,
+    /// Winner of the game, or of the whole match when playing to a score limit.
+    pub winner: PlayerId,
+    /// Result of the last hand played.
+    pub final_score: FinalScore,
+    /// Score of each player, one entry per hand played.
+    pub rounds: Vec<BTreeMap<PlayerId, i32>>,
+    /// How much the rating of each rated player moved over the whole game.
+    pub rating_changes: BTreeMap<PlayerId, Rating>,
+}
+
+impl GameSummary {
+    /// `None` when the record does not reach the end of a game.
+    pub fn from_record(record: &GameRecord) -> Option<Self> {
+        let mut identities = BTreeMap::new();
+        let mut names = BTreeMap::new();
+        let mut rounds = vec![];
+        let mut rating_changes = BTreeMap::new();
+        let mut final_score = None;
+        let mut match_winner = None;
+        for entry in record.entries.iter() {
+            match &entry.kind {
+                RecordKind::Command(RecordedCommand::AddPlayer(name, profile, _)) => {
+                    let identity = profile.as_ref().map(|profile| profile.identity.clone());
+                    identities.insert(name.clone(), identity);
+                }
+                RecordKind::Event { to: None, event } => match event {
+                    RoomEvent::PlayerJoined {
+                        player_id,
+                        player_name,
+                        ..
+                    } => {
+                        names.insert(*player_id, player_name.clone());
+                    }
+                    RoomEvent::GameTerminated(score) => {
+                        rounds.push(
+                            score
+                                .scores
+                                .iter()
+                                .map(|score| (score.player_id, score.total_score))
+                                .collect(),
+                        );
+                        for change in score.rating_changes.iter() {
+                            *rating_changes.entry(change.player_id).or_insert(0.0) += change.delta;
+                        }
+                        final_score = Some(score.clone());
+                    }
+                    RoomEvent::MatchEnded { winner, .. } => match_winner = Some(*winner),
+                    _ => {}
+                },
+                _ => {}
+            }
+        }
+        let final_score = final_score?;
+
+        let mut seated: Vec<PlayerId> = rounds
+            .iter()
+            .flat_map(|round: &BTreeMap<PlayerId, i32>| round.keys().copied())
+            .collect();
+        seated.sort();
+        seated.dedup();
+        let players = seated
+            .into_iter()
+            .filter_map(|player_id| {
+                let name = names.get(&player_id)?.clone();
+                let identity = identities.get(&name).cloned().flatten();
+                Some(GamePlayer {
+                    player_id,
+                    name,
+                    identity,
+                })
+            })
+            .collect();
+
+        Some(GameSummary {
+            game_id: record.game_id,
+            room_id: record.room_id,
+            started_at: record.started_at,
+            duration_ms: record.entries.last().map_or(0, |entry| entry.elapsed_ms),
+            config: record.config.clone(),
+            players,
+            winner: match_winner.unwrap_or(final_score.winner),
+            final_score,
+            rounds,
+            rating_changes,
+        })
+    }
+
+    pub fn has_player(&self, name: &str) -> bool {
+        self.players.iter().any(|player| player.name == name)
+    }
+}
+
+/// A game won by the first of `names`, the others finishing in seat order.
+#[cfg(test)]
+pub fn testing_summary(game_id: GameId, names: &[&str]) -> GameSummary {
+    use crate::room::server::Score;
+
+    GameSummary {
+        game_id,
+        room_id: 0,
+        started_at: 0,
+        duration_ms: 0,
+        config: RoomConfig::default(),
+        players: names
+            .iter()
+            .enumerate()
+            .map(|(idx, name)| GamePlayer {
+                player_id: idx as PlayerId,
+                name: name.to_string(),
+                identity: None,
+            })
+            .collect(),
+        winner: 0,
+        final_score: FinalScore {
+            game_id,
+            winner: 0,
+            scores: (0..names.len())
+                .map(|idx| Score {
+                    player_id: idx as PlayerId,
+                    cards: vec![],
+                    total_score: idx as i32,
+                    forfeited: false,
+                })
+                .collect(),
+            seed: 0,
+            rating_changes: vec![],
+        },
+        rounds: vec![],
+        rating_changes: BTreeMap::new(),
+    }
+}
+
+/// Where finished games, player ratings and stats are kept. Listings come most
+/// recent first, the leaderboard best rating first.
+pub trait GameRepository: Send {
+    fn save_game(&mut self, game: GameSummary) -> Result<(), StorageError>;
+    fn recent_games(&self, limit: usize) -> Result<Vec<GameSummary>, StorageError>;
+    fn player_games(&self, name: &str, limit: usize) -> Result<Vec<GameSummary>, StorageError>;
+    fn save_rating(&mut self, rating: PlayerRating) -> Result<(), StorageError>;
+    fn player_rating(&self, identity: &str) -> Result<Option<PlayerRating>, StorageError>;
+    fn leaderboard(&self, limit: usize) -> Result<Vec<PlayerRating>, StorageError>;
+    fn save_stats(&mut self, stats: PlayerStats) -> Result<(), StorageError>;
+    fn player_stats(&self, identity: &str) -> Result<Option<PlayerStats>, StorageError>;
+    fn save_record(&mut self, record: &GameRecord) -> Result<(), StorageError>;
+    fn game_record(&self, game_id: GameId) -> Result<Option<GameRecord>, StorageError>;
+    fn save_bot(&mut self, bot: BotRecord) -> Result<(), StorageError>;
+    fn bot_by_name(&self, name: &str) -> Result<Option<BotRecord>, StorageError>;
+    fn bot_by_token(&self, token_hash: &[u8]) -> Result<Option<BotRecord>, StorageError>;
+}
+
+type StorageJob = Box<dyn FnOnce(&mut dyn GameRepository) + Send>;
+
+/// Owns a repository on a thread of its own, so that a slow disk never holds
+/// up the server loop. Jobs run one at a time, in the order they are queued,
+/// and send their results back themselves.
+pub struct Storage {
+    tx: mpsc::Sender<StorageJob>,
+}
+
+impl Storage {
+    pub fn new(mut repository: Box<dyn GameRepository>) -> Self {
+        let (tx, rx) = mpsc::channel::<StorageJob>();
+        thread::spawn(move || {
+            for job in rx {
+                job(repository.as_mut());
+            }
+        });
+        Self { tx }
+    }
+
+    pub fn run(&self, job: impl FnOnce(&mut dyn GameRepository) + Send + 'static) {
+        let _ = self.tx.send(Box::new(job));
+    }
+}
+
+/// Keeps games for as long as the server runs, and the records of the last
+/// `RECORDS_IN_MEMORY` of them.
+#[derive(Default)]
+pub struct InMemoryRepository {
+    games: Vec<GameSummary>,
+    ratings: HashMap<Identity, PlayerRating>,
+    stats: HashMap<Identity, PlayerStats>,
+    records: VecDeque<GameRecord>,
+    bots: HashMap<PlayerName, BotRecord>,
+}
+
+impl GameRepository for InMemoryRepository {
+    fn save_game(&mut self, game: GameSummary) -> Result<(), StorageError> {
+        self.games.retain(|kept| kept.game_id != game.game_id);
+        let idx = self
+            .games
+            .partition_point(|kept| kept.started_at <= game.started_at);
+        self.games.insert(idx, game);
+        Ok(())
+    }
+
+    fn recent_games(&self, limit: usize) -> Result<Vec<GameSummary>, StorageError> {
+        Ok(self.games.iter().rev().take(limit).cloned().collect())
+    }
+
+    fn player_games(&self, name: &str, limit: usize) -> Result<Vec<GameSummary>, StorageError> {
+        Ok(self
+            .games
+            .iter()
+            .rev()
+            .filter(|game| game.has_player(name))
+            .take(limit)
+            .cloned()
+            .collect())
+    }
+
+    fn save_rating(&mut self, rating: PlayerRating) -> Result<(), StorageError> {
+        self.ratings.insert(rating.identity.clone(), rating);
+        Ok(())
+    }
+
+    fn player_rating(&self, identity: &str) -> Result<Option<PlayerRating>, StorageError> {
+        Ok(self.ratings.get(identity).cloned())
+    }
+
+    fn leaderboard(&self, limit: usize) -> Result<Vec<PlayerRating>, StorageError> {
+        let mut ratings: Vec<PlayerRating> = self.ratings.values().cloned().collect();
+        ratings.sort_by(|a, b| b.rating.total_cmp(&a.rating));
+        ratings.truncate(limit);
+        Ok(ratings)
+    }
+
+    fn save_stats(&mut self, stats: PlayerStats) -> Result<(), StorageError> {
+        self.stats.insert(stats.identity.clone(), stats);
+        Ok(())
+    }
+
+    fn player_stats(&self, identity: &str) -> Result<Option<PlayerStats>, StorageError> {
+        Ok(self.stats.get(identity).cloned())
+    }
+
+    fn save_record(&mut self, record: &GameRecord) -> Result<(), StorageError> {
+        self.records.retain(|kept| kept.game_id != record.game_id);
+        if self.records.len() == RECORDS_IN_MEMORY {
+            self.records.pop_front();
+        }
+        self.records.push_back(record.clone());
+        Ok(())
+    }
+
+    fn game_record(&self, game_id: GameId) -> Result<Option<GameRecord>, StorageError> {
+        Ok(self
+            .records
+            .iter()
+            .find(|record| record.game_id == game_id)
+            .cloned())
+    }
+
+    fn save_bot(&mut self, bot: BotRecord) -> Result<(), StorageError> {
+        self.bots.insert(bot.name.clone(), bot);
+        Ok(())
+    }
+
+    fn bot_by_name(&self, name: &str) -> Result<Option<BotRecord>, StorageError> {
+        Ok(self.bots.get(name).cloned())
+    }
+
+    fn bot_by_token(&self, token_hash: &[u8]) -> Result<Option<BotRecord>, StorageError> {
+        Ok(self
+            .bots
+            .values()
+            .find(|bot| bot.token_hash == token_hash)
+            .cloned())
+    }
+}
+
+/// Keeps games in a SQLite database. The summary is stored as JSON, players
+/// get their own table so they can be looked up.
+pub struct SqliteRepository {
+    connection: Connection,
+}
+
+impl SqliteRepository {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, StorageError> {
+        Self::init(Connection::open(path)?)
+    }
+
+    pub fn open_in_memory() -> Result<Self, StorageError> {
+        Self::init(Connection::open_in_memory()?)
+    }
+
+    fn init(connection: Connection) -> Result<Self, StorageError> {
+        connection.execute_batch(
+            "CREATE TABLE IF NOT EXISTS games (
+                game_id INTEGER PRIMARY KEY,
+                started_at INTEGER NOT NULL,
+                duration_ms INTEGER NOT NULL,
+                summary TEXT NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS game_players (
+                game_id INTEGER NOT NULL REFERENCES games(game_id) ON DELETE CASCADE,
+                player_id INTEGER NOT NULL,
+                name TEXT NOT NULL,
+                identity TEXT,
+                PRIMARY KEY (game_id, player_id)
+            );
+            CREATE INDEX IF NOT EXISTS game_players_name ON game_players(name);
+            CREATE TABLE IF NOT EXISTS ratings (
+                identity TEXT PRIMARY KEY,
+                name TEXT NOT NULL,
+                rating REAL NOT NULL,
+                games INTEGER NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS ratings_rating ON ratings(rating);
+            CREATE TABLE IF NOT EXISTS player_stats (
+                identity TEXT PRIMARY KEY,
+                stats TEXT NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS records (
+                game_id INTEGER PRIMARY KEY,
+                record TEXT NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS bots (
+                name TEXT PRIMARY KEY,
+                token_hash BLOB NOT NULL UNIQUE
+            );",
+        )?;
+        Ok(Self { connection })
+    }
+
+    fn query_games(
+        &self,
+        sql: &str,
+        params: impl rusqlite::Params,
+    ) -> Result<Vec<GameSummary>, StorageError> {
+        let mut statement = self.connection.prepare(sql)?;
+        let summaries = statement
+            .query_map(params, |row| row.get::<_, String>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        summaries
+            .iter()
+            .map(|summary| serde_json::from_str(summary).map_err(StorageError::from))
+            .collect()
+    }
+}
+
+impl GameRepository for SqliteRepository {
+    fn save_game(&mut self, game: GameSummary) -> Result<(), StorageError> {
+        // Game ids are random u64, SQLite integers are signed.
+        let game_id = game.game_id as i64;
+        let transaction = self.connection.transaction()?;
+        transaction.execute(
+            "DELETE FROM game_players WHERE game_id = ?1",
+            params![game_id],
+        )?;
+        transaction.execute(
+            "INSERT OR REPLACE INTO games (game_id, started_at, duration_ms, summary)
+            VALUES (?1, ?2, ?3, ?4)",
+            params![
+                game_id,
+                game.started_at as i64,
+                game.duration_ms as i64,
+                serde_json::to_string(&game)?
+            ],
+        )?;
+        for player in game.players.iter() {
+            transaction.execute(
+                "INSERT INTO game_players (game_id, player_id, name, identity)
+                VALUES (?1, ?2, ?3, ?4)",
+                params![game_id, player.player_id, player.name, player.identity],
+            )?;
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+
+    fn recent_games(&self, limit: usize) -> Result<Vec<GameSummary>, StorageError> {
+        self.query_games(
+            "SELECT summary FROM games ORDER BY started_at DESC LIMIT ?1",
+            params![limit as i64],
+        )
+    }
+
+    fn player_games(&self, name: &str, limit: usize) -> Result<Vec<GameSummary>, StorageError> {
+        self.query_games(
+            "SELECT games.summary FROM games
+            JOIN game_players ON game_players.game_id = games.game_id
+            WHERE game_players.name = ?1
+            ORDER BY games.started_at DESC LIMIT ?2",
+            params![name, limit as i64],
+        )
+    }
+
+    fn save_rating(&mut self, rating: PlayerRating) -> Result<(), StorageError> {
+        self.connection.execute(
+            "INSERT OR REPLACE INTO ratings (identity, name, rating, games)
+            VALUES (?1, ?2, ?3, ?4)",
+            params![rating.identity, rating.name, rating.rating, rating.games],
+        )?;
+        Ok(())
+    }
+
+    fn player_rating(&self, identity: &str) -> Result<Option<PlayerRating>, StorageError> {
+        let rating = self
+            .connection
+            .query_row(
+                "SELECT identity, name, rating, games FROM ratings WHERE identity = ?1",
+                params![identity],
+                rating_from_row,
+            )
+            .optional()?;
+        Ok(rating)
+    }
+
+    fn leaderboard(&self, limit: usize) -> Result<Vec<PlayerRating>, StorageError> {
+        let mut statement = self.connection.prepare(
+            "SELECT identity, name, rating, games FROM ratings
+            ORDER BY rating DESC LIMIT ?1",
+        )?;
+        let ratings = statement
+            .query_map(params![limit as i64], rating_from_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(ratings)
+    }
+
+    fn save_stats(&mut self, stats: PlayerStats) -> Result<(), StorageError> {
+        self.connection.execute(
+            "INSERT OR REPLACE INTO player_stats (identity, stats) VALUES (?1, ?2)",
+            params![stats.identity, serde_json::to_string(&stats)?],
+        )?;
+        Ok(())
+    }
+
+    fn player_stats(&self, identity: &str) -> Result<Option<PlayerStats>, StorageError> {
+        let stats = self
+            .connection
+            .query_row(
+                "SELECT stats FROM player_stats WHERE identity = ?1",
+                params![identity],
+                |row| row.get::<_, String>(0),
+            )
+            .optional()?;
+        Ok(stats
+            .map(|stats| serde_json::from_str(&stats))
+            .transpose()?)
+    }
+
+    fn save_record(&mut self, record: &GameRecord) -> Result<(), StorageError> {
+        self.connection.execute(
+            "INSERT OR REPLACE INTO records (game_id, record) VALUES (?1, ?2)",
+            params![record.game_id as i64, serde_json::to_string(record)?],
+        )?;
+        Ok(())
+    }
+
+    fn game_record(&self, game_id: GameId) -> Result<Option<GameRecord>, StorageError> {
+        let record = self
+            .connection
+            .query_row(
+                "SELECT record FROM records WHERE game_id = ?1",
+                params![game_id as i64],
+                |row| row.get::<_, String>(0),
+            )
+            .optional()?;
+        Ok(record
+            .map(|record| serde_json::from_str(&record))
+            .transpose()?)
+    }
+
+    fn save_bot(&mut self, bot: BotRecord) -> Result<(), StorageError> {
+        self.connection.execute(
+            "INSERT OR REPLACE INTO bots (name, token_hash) VALUES (?1, ?2)",
+            params![bot.name, bot.token_hash],
+        )?;
+        Ok(())
+    }
+
+    fn bot_by_name(&self, name: &str) -> Result<Option<BotRecord>, StorageError> {
+        let bot = self
+            .connection
+            .query_row(
+                "SELECT name, token_hash FROM bots WHERE name = ?1",
+                params![name],
+                bot_from_row,
+            )
+            .optional()?;
+        Ok(bot)
+    }
+
+    fn bot_by_token(&self, token_hash: &[u8]) -> Result<Option<BotRecord>, StorageError> {
+        let bot = self
+            .connection
+            .query_row(
+                "SELECT name, token_hash FROM bots WHERE token_hash = ?1",
+                params![token_hash],
+                bot_from_row,
+            )
+            .optional()?;
+        Ok(bot)
+    }
+}
+
+fn rating_from_row(row: &rusqlite::Row) -> rusqlite::Result<PlayerRating> {
+    Ok(PlayerRating {
+        identity: row.get(0)?,
+        name: row.get(1)?,
+        rating: row.get(2)?,
+        games: row.get(3)?,
+    })
+}
+
+fn bot_from_row(row: &rusqlite::Row) -> rusqlite::Result<BotRecord> {
+    Ok(BotRecord {
+        name: row.get(0)?,
+        token_hash: row.get(1)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary(game_id: GameId, started_at: u64, names: &[&str]) -> GameSummary {
+        GameSummary {
+            started_at,
+            ..testing_summary(game_id, names)
+        }
+    }
+
+    fn check_repository(repository: &mut impl GameRepository) {
+        repository
+            .save_game(summary(1, 10, &["ann", "bob"]))
+            .unwrap();
+        repository
+            .save_game(summary(2, 30, &["bob", "cid"]))
+            .unwrap();
+        repository
+            .save_game(summary(3, 20, &["ann", "cid"]))
+            .unwrap();
+        repository
+            .save_game(summary(u64::MAX, 5, &["ann"]))
+            .unwrap();
+
+        let recent = repository.recent_games(10).unwrap();
+        let ids: Vec<GameId> = recent.iter().map(|game| game.game_id).collect();
+        assert!(ids == vec![2, 3, 1, u64::MAX]);
+        assert!(repository.recent_games(1).unwrap().len() == 1);
+
+        let ann = repository.player_games("ann", 2).unwrap();
+        let ids: Vec<GameId> = ann.iter().map(|game| game.game_id).collect();
+        assert!(ids == vec![3, 1]);
+        assert!(repository.player_games("dan", 10).unwrap().is_empty());
+
+        for (identity, rating) in [
+            ("guest-a", 1490.5),
+            ("guest-b", 1530.0),
+            ("guest-c", 1400.0),
+        ] {
+            let mut player = PlayerRating::new(identity.into(), identity.into());
+            player.rating = rating;
+            repository.save_rating(player).unwrap();
+        }
+        let mut renamed = PlayerRating::new("guest-c".into(), "cid".into());
+        renamed.rating = 1600.0;
+        renamed.games = 3;
+        repository.save_rating(renamed).unwrap();
+
+        let cid = repository.player_rating("guest-c").unwrap().unwrap();
+        assert!(cid.name == "cid" && cid.games == 3);
+        assert!(repository.player_rating("guest-d").unwrap().is_none());
+        let leaderboard = repository.leaderboard(2).unwrap();
+        let best: Vec<&str> = leaderboard.iter().map(|p| p.identity.as_str()).collect();
+        assert!(best == vec!["guest-c", "guest-b"]);
+
+        let mut stats = PlayerStats::new("guest-a".into(), "ann".into());
+        stats.timeouts = 2;
+        repository.save_stats(stats.clone()).unwrap();
+        assert!(repository.player_stats("guest-a").unwrap() == Some(stats));
+        assert!(repository.player_stats("ann").unwrap().is_none());
+
+        let bot = BotRecord {
+            name: "clawbert".into(),
+            token_hash: vec![1, 2, 3],
+        };
+        repository.save_bot(bot.clone()).unwrap();
+        assert!(repository.bot_by_name("clawbert").unwrap() == Some(bot.clone()));
+        assert!(repository.bot_by_token(&[1, 2, 3]).unwrap() == Some(bot));
+        assert!(repository.bot_by_name("ann").unwrap().is_none());
+        assert!(repository.bot_by_token(&[3, 2, 1]).unwrap().is_none());
+
+        let record = GameRecord {
+            game_id: u64::MAX,
+            room_id: 3,
+            config: RoomConfig::default(),
+            seed: 7,
+            started_at: 5,
+            entries: vec![],
+        };
+        repository.save_record(&record).unwrap();
+        let stored = repository.game_record(u64::MAX).unwrap().unwrap();
+        assert!(stored.room_id == 3 && stored.seed == 7);
+        assert!(repository.game_record(1).unwrap().is_none());
+    }
+
+    #[test]
+    fn in_memory_records_are_capped() {
+        let mut repository = InMemoryRepository::default();
+        for game_id in 0..=RECORDS_IN_MEMORY as GameId {
+            let record = GameRecord {
+                game_id,
+                room_id: 0,
+                config: RoomConfig::default(),
+                seed: 0,
+                started_at: 0,
+                entries: vec![],
+            };
+            repository.save_record(&record).unwrap();
+        }
+        assert!(repository.game_record(0).unwrap().is_none());
+        assert!(repository.game_record(1).unwrap().is_some());
+    }
+
+    #[test]
+    fn storage_runs_jobs_in_order() {
+        let storage = Storage::new(Box::new(InMemoryRepository::default()));
+        storage.run(|history| history.save_game(summary(1, 10, &["ann"])).unwrap());
+        let (tx, rx) = mpsc::channel();
+        storage.run(move |history| {
+            let _ = tx.send(history.recent_games(10).unwrap().len());
+        });
+        assert!(rx.recv().unwrap() == 1);
+    }
+
+    #[test]
+    fn in_memory_repository() {
+        check_repository(&mut InMemoryRepository::default());
+    }
+
+    #[test]
+    fn sqlite_repository() {
+        check_repository(&mut SqliteRepository::open_in_memory().unwrap());
+    }
+}
@@ -0,0 +1,172 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use hmac::{Hmac, Mac};
+use rand::{thread_rng, Rng};
+use sha2::{Digest, Sha256};
+
+use crate::consts::RoomId;
+
+type HmacSha256 = Hmac<Sha256>;
+
+pub type InviteToken = String;
+
+#[derive(PartialEq, Debug)]
+pub enum InviteError {
+    Invalid,
+    Expired,
+}
+
+/// Signs invite tokens of the form `{room_id}.{expires_at}.{signature}`, so
+/// the server does not have to remember the invites it gave away. Seat
+/// invites also sign the name they were given to, without showing it.
+pub struct InviteSigner {
+    secret: [u8; 32],
+}
+
+impl Default for InviteSigner {
+    fn default() -> Self {
+        Self {
+            secret: thread_rng().gen(),
+        }
+    }
+}
+
+impl InviteSigner {
+    pub fn sign(&self, room_id: RoomId, valid_for: Duration) -> InviteToken {
+        self.sign_for(room_id, None, valid_for)
+    }
+
+    pub fn verify(&self, room_id: RoomId, token: &str) -> Result<(), InviteError> {
+        self.verify_for(room_id, None, token)
+    }
+
+    /// An invite only good for joining under `name`.
+    pub fn sign_seat(&self, room_id: RoomId, name: &str, valid_for: Duration) -> InviteToken {
+        self.sign_for(room_id, Some(name), valid_for)
+    }
+
+    pub fn verify_seat(&self, room_id: RoomId, name: &str, token: &str) -> Result<(), InviteError> {
+        self.verify_for(room_id, Some(name), token)
+    }
+
+    fn sign_for(&self, room_id: RoomId, name: Option<&str>, valid_for: Duration) -> InviteToken {
+        let expires_at = unix_secs() + valid_for.as_secs();
+        let payload = format!("{room_id}.{expires_at}");
+        let signature = to_hex(&self.mac(&payload, name).finalize().into_bytes());
+        format!("{payload}.{signature}")
+    }
+
+    fn verify_for(
+        &self,
+        room_id: RoomId,
+        name: Option<&str>,
+        token: &str,
+    ) -> Result<(), InviteError> {
+        let (payload, signature) = token.rsplit_once('.').ok_or(InviteError::Invalid)?;
+        let signature = from_hex(signature).ok_or(InviteError::Invalid)?;
+        self.mac(payload, name)
+            .verify_slice(&signature)
+            .map_err(|_| InviteError::Invalid)?;
+
+        let (invited_room, expires_at) = payload.split_once('.').ok_or(InviteError::Invalid)?;
+        if invited_room.parse::<RoomId>() != Ok(room_id) {
+            return Err(InviteError::Invalid);
+        }
+        let expires_at: u64 = expires_at.parse().map_err(|_| InviteError::Invalid)?;
+        if unix_secs() >= expires_at {
+            return Err(InviteError::Expired);
+        }
+        Ok(())
+    }
+
+    fn mac(&self, payload: &str, name: Option<&str>) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(&self.secret).unwrap();
+        mac.update(payload.as_bytes());
+        if let Some(name) = name {
+            mac.update(b"\0");
+            mac.update(name.as_bytes());
+        }
+        mac
+    }
+}
+
+/// Room passwords and bot tokens are only kept hashed.
+pub fn hash_password(password: &str) -> Vec<u8> {
+    Sha256::digest(password.as_bytes()).to_vec()
+}
+
+/// Compares a secret in constant time. Both sides are hashed first, so their
+/// length does not leak either.
+pub fn secrets_match(expected: &str, given: &str) -> bool {
+    hash_password(expected)
+        .iter()
+        .zip(hash_password(given))
+        .fold(0, |diff, (expected, given)| diff | (expected ^ given))
+        == 0
+}
+
+fn unix_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn from_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) || !text.is_ascii() {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&text[i..i + 2], 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signed_invite_opens_its_room_only() {
+        let signer = InviteSigner::default();
+        let token = signer.sign(7, Duration::from_secs(60));
+        assert!(signer.verify(7, &token).is_ok());
+        assert!(signer.verify(8, &token) == Err(InviteError::Invalid));
+        assert!(InviteSigner::default().verify(7, &token) == Err(InviteError::Invalid));
+
+        let forged = token.replacen("7.", "8.", 1);
+        assert!(signer.verify(8, &forged) == Err(InviteError::Invalid));
+        assert!(signer.verify(7, "garbage") == Err(InviteError::Invalid));
+    }
+
+    #[test]
+    fn secrets_match_only_themselves() {
+        assert!(secrets_match("0123abcd", "0123abcd"));
+        assert!(!secrets_match("0123abcd", "0123abce"));
+        assert!(!secrets_match("0123abcd", "0123abc"));
+        assert!(!secrets_match("0123abcd", ""));
+    }
+
+    #[test]
+    fn seat_invite_is_bound_to_its_name() {
+        let signer = InviteSigner::default();
+        let token = signer.sign_seat(7, "ann", Duration::from_secs(60));
+        assert!(signer.verify_seat(7, "ann", &token).is_ok());
+        assert!(signer.verify_seat(7, "bob", &token) == Err(InviteError::Invalid));
+        assert!(signer.verify(7, &token) == Err(InviteError::Invalid));
+
+        let room_invite = signer.sign(7, Duration::from_secs(60));
+        assert!(signer.verify_seat(7, "ann", &room_invite) == Err(InviteError::Invalid));
+    }
+
+    #[test]
+    fn invite_expires() {
+        let signer = InviteSigner::default();
+        let token = signer.sign(7, Duration::ZERO);
+        assert!(signer.verify(7, &token) == Err(InviteError::Expired));
+    }
+}
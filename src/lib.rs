@@ -1,6 +1,16 @@
+pub mod bot;
 pub mod consts;
 pub mod deck;
+pub mod history;
+pub mod invite;
+pub mod matchmaking;
+pub mod protocol;
+pub mod rating;
 pub mod room;
+pub mod room_code;
 pub mod server;
+pub mod sim;
+pub mod stats;
+pub mod tournament;
 pub mod ws_client;
 pub mod api;
\ No newline at end of file
@@ -4,6 +4,8 @@ use crabul::api::run;
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
+    // Errors are shown unless `RUST_LOG` asks for something else.
+    env_logger::init();
     let listener = TcpListener::bind("0.0.0.0:8000").expect("Failed to bind random port");
     run(listener)?.await
 }
@@ -0,0 +1,232 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+
+use crate::{
+    consts::{PlayerId, PlayerName, RoomCode, RoomId},
+    rating::{PlayerProfile, Rating},
+    room::{commander::RoomCommander, config::RoomConfig, events::RoomEvent},
+};
+
+pub type TicketId = u64;
+
+/// Matched players are ready at once, the game starts shortly after.
+pub const MATCH_START_COUNTDOWN: Duration = Duration::from_secs(3);
+
+/// Sent to a queued player before the events of the room they end up in.
+#[derive(Deserialize, Serialize, Clone)]
+pub enum MatchmakingEvent {
+    /// 1 for the next player to be matched, among those waiting for a table
+    /// of the same size.
+    QueuePosition { position: usize, players: usize },
+    MatchFound {
+        room_id: RoomId,
+        room_code: RoomCode,
+        player_id: PlayerId,
+    },
+}
+
+/// What a player asks for when entering the queue.
+#[derive(Deserialize, Clone)]
+pub struct MatchRequest {
+    pub name: PlayerName,
+    /// Size of the table, everyone included.
+    pub players: usize,
+    /// Largest rating gap accepted with the other players, any when unset.
+    pub rating_band: Option<Rating>,
+}
+
+impl MatchRequest {
+    pub fn room_config(&self) -> RoomConfig {
+        RoomConfig {
+            min_players: self.players,
+            max_players: self.players,
+            auto_start_countdown: MATCH_START_COUNTDOWN,
+            ..Default::default()
+        }
+    }
+}
+
+pub enum QueueUpdate {
+    Position(usize),
+    Found(Box<SeatedPlayer>),
+}
+
+/// A queued player once seated in the room made for their match.
+pub struct SeatedPlayer {
+    pub room_id: RoomId,
+    pub player_id: PlayerId,
+    pub commander: RoomCommander,
+    pub player_channel: UnboundedReceiver<RoomEvent>,
+}
+
+pub struct Ticket {
+    pub id: TicketId,
+    pub request: MatchRequest,
+    pub profile: PlayerProfile,
+    pub tx: UnboundedSender<QueueUpdate>,
+}
+
+impl Ticket {
+    /// Both bands have to accept the gap, and nobody plays against themselves.
+    fn accepts(&self, other: &Ticket) -> bool {
+        let gap = (self.profile.rating - other.profile.rating).abs();
+        let within = |band: Option<Rating>| band.is_none_or(|band| gap <= band);
+        self.request.players == other.request.players
+            && self.request.name != other.request.name
+            && self.profile.identity != other.profile.identity
+            && within(self.request.rating_band)
+            && within(other.request.rating_band)
+    }
+}
+
+/// Players waiting for a match, in arrival order.
+#[derive(Default)]
+pub struct MatchmakingQueue {
+    tickets: Vec<Ticket>,
+}
+
+impl MatchmakingQueue {
+    /// Queues the ticket and returns the players of a match when one can be
+    /// made. Those who wait the longest are matched first.
+    pub fn join(&mut self, ticket: Ticket) -> Option<Vec<Ticket>> {
+        self.tickets.push(ticket);
+        let group = self.find_group().map(|indices| {
+            let mut group: Vec<Ticket> = indices
+                .into_iter()
+                .rev()
+                .map(|idx| self.tickets.remove(idx))
+                .collect();
+            group.reverse();
+            group
+        });
+        self.send_positions();
+        group
+    }
+
+    pub fn leave(&mut self, ticket_id: TicketId) {
+        self.tickets.retain(|ticket| ticket.id != ticket_id);
+        self.send_positions();
+    }
+
+    pub fn len(&self) -> usize {
+        self.tickets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tickets.is_empty()
+    }
+
+    fn find_group(&self) -> Option<Vec<usize>> {
+        self.tickets.iter().enumerate().find_map(|(idx, first)| {
+            let mut group = vec![idx];
+            for (other_idx, other) in self.tickets.iter().enumerate().skip(idx + 1) {
+                if group.len() == first.request.players {
+                    break;
+                }
+                if group.iter().all(|&idx| self.tickets[idx].accepts(other)) {
+                    group.push(other_idx);
+                }
+            }
+            (group.len() == first.request.players).then_some(group)
+        })
+    }
+
+    fn send_positions(&self) {
+        for (idx, ticket) in self.tickets.iter().enumerate() {
+            let position = self.tickets[..=idx]
+                .iter()
+                .filter(|other| other.request.players == ticket.request.players)
+                .count();
+            let _ = ticket.tx.send(QueueUpdate::Position(position));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::mpsc;
+
+    use super::*;
+
+    fn ticket(
+        id: TicketId,
+        players: usize,
+        rating: Rating,
+        rating_band: Option<Rating>,
+    ) -> (Ticket, UnboundedReceiver<QueueUpdate>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let ticket = Ticket {
+            id,
+            request: MatchRequest {
+                name: format!("player{id}"),
+                players,
+                rating_band,
+            },
+            profile: PlayerProfile {
+                identity: format!("guest-{id}"),
+                rating,
+            },
+            tx,
+        };
+        (ticket, rx)
+    }
+
+    fn last_position(rx: &mut UnboundedReceiver<QueueUpdate>) -> Option<usize> {
+        let mut position = None;
+        while let Ok(update) = rx.try_recv() {
+            if let QueueUpdate::Position(current) = update {
+                position = Some(current);
+            }
+        }
+        position
+    }
+
+    #[test]
+    fn players_are_grouped_by_table_size() {
+        let mut queue = MatchmakingQueue::default();
+        let (first, mut first_rx) = ticket(1, 3, 1500.0, None);
+        let (second, mut second_rx) = ticket(2, 2, 1500.0, None);
+        let (third, _) = ticket(3, 3, 1500.0, None);
+        assert!(queue.join(first).is_none());
+        assert!(queue.join(second).is_none());
+        assert!(queue.join(third).is_none());
+        assert!(last_position(&mut first_rx) == Some(1));
+        assert!(last_position(&mut second_rx) == Some(1));
+
+        let (fourth, _) = ticket(4, 3, 1500.0, None);
+        let group = queue.join(fourth).unwrap();
+        let ids: Vec<TicketId> = group.iter().map(|ticket| ticket.id).collect();
+        assert!(ids == vec![1, 3, 4]);
+        assert!(queue.len() == 1);
+
+        queue.leave(2);
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn rating_bands_keep_players_apart() {
+        let mut queue = MatchmakingQueue::default();
+        let (strong, _) = ticket(1, 2, 1900.0, Some(100.0));
+        let (weak, mut weak_rx) = ticket(2, 2, 1400.0, None);
+        assert!(queue.join(strong).is_none());
+        assert!(queue.join(weak).is_none());
+        assert!(last_position(&mut weak_rx) == Some(2));
+
+        let (close, _) = ticket(3, 2, 1450.0, Some(100.0));
+        let group = queue.join(close).unwrap();
+        let ids: Vec<TicketId> = group.iter().map(|ticket| ticket.id).collect();
+        assert!(ids == vec![2, 3]);
+    }
+
+    #[test]
+    fn nobody_is_matched_with_themselves() {
+        let mut queue = MatchmakingQueue::default();
+        let (first, _) = ticket(1, 2, 1500.0, None);
+        let (mut again, _) = ticket(2, 2, 1500.0, None);
+        again.profile.identity = first.profile.identity.clone();
+        assert!(queue.join(first).is_none());
+        assert!(queue.join(again).is_none());
+    }
+}
@@ -0,0 +1,342 @@
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bot::{Bot, BotLevel},
+    consts::PlayerId,
+    room::{commander::RoomCommander, errors::GameError, events::RoomEvent},
+};
+
+pub type RequestId = u64;
+
+/// Commands a player can send over the socket, mirroring `RoomCommand`.
+#[derive(Deserialize, Serialize, Clone, PartialEq, Debug)]
+#[serde(tag = "type")]
+pub enum ClientMessage {
+    StartGame,
+    /// Readiness in the lobby, the game starts by itself once everybody is ready.
+    SetLobbyReady {
+        ready: bool,
+    },
+    SetPlayerReady,
+    GoCrabul,
+    DrawCard,
+    SwapCard {
+        card_idx: usize,
+    },
+    DiscardCard,
+    PeekOwnCard {
+        card_idx: usize,
+    },
+    PeekOtherCard {
+        other_player_id: PlayerId,
+        other_card_idx: usize,
+    },
+    BlindSwap {
+        card_idx: usize,
+        other_player_id: PlayerId,
+        other_card_idx: usize,
+    },
+    CheckAndSwapStage1 {
+        other_player_id: PlayerId,
+        other_card_idx: usize,
+    },
+    CheckAndSwapStage2 {
+        card_idx: Option<usize>,
+    },
+    ThrowSameCard {
+        picked_player_id: PlayerId,
+        picked_card_idx: usize,
+    },
+    SelectCardToGiveAway {
+        card_idx: usize,
+    },
+    KickPlayer {
+        kicked_player_id: PlayerId,
+    },
+    TransferHost {
+        new_host_id: PlayerId,
+    },
+    GetState,
+    /// Answered with a `LegalActions` event.
+    GetLegalActions,
+    /// Seats a bot, only while the room is waiting for players.
+    AddBot {
+        level: BotLevel,
+    },
+    Leave,
+}
+
+/// A JSON message, e.g. `{"type":"SwapCard","card_idx":1,"request_id":7}`.
+#[derive(Deserialize, Serialize, Clone, PartialEq, Debug)]
+pub struct ClientRequest {
+    pub request_id: Option<RequestId>,
+    #[serde(flatten)]
+    pub message: ClientMessage,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct CommandResponse {
+    pub request_id: Option<RequestId>,
+    pub result: Result<(), GameError>,
+}
+
+impl ClientRequest {
+    pub fn parse(text: &str) -> Result<Self, GameError> {
+        if text.starts_with('/') {
+            return ClientMessage::parse_slash(text).map(|message| Self {
+                request_id: None,
+                message,
+            });
+        }
+        serde_json::from_str(text).map_err(|err| GameError::InvalidMessage(err.to_string()))
+    }
+
+    /// Reads the id alone, so that a request failing to parse still gets its
+    /// error back under the right id.
+    pub fn request_id_of(text: &str) -> Option<RequestId> {
+        #[derive(Deserialize)]
+        struct RequestIdOnly {
+            request_id: Option<RequestId>,
+        }
+        serde_json::from_str::<RequestIdOnly>(text).ok()?.request_id
+    }
+}
+
+impl ClientMessage {
+    /// Parses the slash syntax kept around for manual debugging, e.g. `/pow3 1 42 0`.
+    pub fn parse_slash(command: &str) -> Result<Self, GameError> {
+        let mut split = command.split(' ').filter(|split| !split.is_empty());
+        let name = split.next().ok_or(GameError::UnknownCommand)?;
+        let params: Vec<&str> = split.collect();
+
+        let message = match name {
+            "/start" => ClientMessage::StartGame,
+            "/ready" => ClientMessage::SetPlayerReady,
+            "/lobby_ready" => ClientMessage::SetLobbyReady {
+                ready: param(&params, 0)?,
+            },
+            "/draw" => ClientMessage::DrawCard,
+            "/discard" => ClientMessage::DiscardCard,
+            "/crabul" => ClientMessage::GoCrabul,
+            "/kick" => ClientMessage::KickPlayer {
+                kicked_player_id: param(&params, 0)?,
+            },
+            "/host" => ClientMessage::TransferHost {
+                new_host_id: param(&params, 0)?,
+            },
+            "/state" => ClientMessage::GetState,
+            "/actions" => ClientMessage::GetLegalActions,
+            "/bot" => ClientMessage::AddBot {
+                level: param(&params, 0)?,
+            },
+            "/leave" => ClientMessage::Leave,
+            "/swap" => ClientMessage::SwapCard {
+                card_idx: param(&params, 0)?,
+            },
+            "/pow1" => ClientMessage::PeekOwnCard {
+                card_idx: param(&params, 0)?,
+            },
+            "/pow2" => ClientMessage::PeekOtherCard {
+                other_player_id: param(&params, 0)?,
+                other_card_idx: param(&params, 1)?,
+            },
+            "/pow3" => ClientMessage::BlindSwap {
+                card_idx: param(&params, 0)?,
+                other_player_id: param(&params, 1)?,
+                other_card_idx: param(&params, 2)?,
+            },
+            "/pow4_1" => ClientMessage::CheckAndSwapStage1 {
+                other_player_id: param(&params, 0)?,
+                other_card_idx: param(&params, 1)?,
+            },
+            "/pow4_2" => ClientMessage::CheckAndSwapStage2 {
+                card_idx: params.first().map(|_| param(&params, 0)).transpose()?,
+            },
+            "/throw" => ClientMessage::ThrowSameCard {
+                picked_player_id: param(&params, 0)?,
+                picked_card_idx: param(&params, 1)?,
+            },
+            "/throw_2" => ClientMessage::SelectCardToGiveAway {
+                card_idx: param(&params, 0)?,
+            },
+            _ => return Err(GameError::UnknownCommand),
+        };
+        Ok(message)
+    }
+
+    /// Runs the message against the room. Queries answer with an event meant for
+    /// the sender only.
+    pub async fn execute(
+        self,
+        player_id: PlayerId,
+        room_commander: &RoomCommander,
+    ) -> Result<Option<RoomEvent>, GameError> {
+        if let ClientMessage::GetState = self {
+            let snapshot = room_commander.get_state(player_id).await?;
+            return Ok(Some(RoomEvent::StateSnapshot(snapshot)));
+        }
+        if let ClientMessage::GetLegalActions = self {
+            let actions = room_commander.get_legal_actions(player_id).await?;
+            return Ok(Some(RoomEvent::LegalActions(actions)));
+        }
+        let result = match self {
+            ClientMessage::StartGame => room_commander.start_game(player_id).await,
+            ClientMessage::KickPlayer { kicked_player_id } => {
+                room_commander
+                    .kick_player(player_id, kicked_player_id)
+                    .await
+            }
+            ClientMessage::TransferHost { new_host_id } => {
+                room_commander.transfer_host(player_id, new_host_id).await
+            }
+            ClientMessage::SetLobbyReady { ready } => {
+                room_commander.set_lobby_ready(player_id, ready).await
+            }
+            ClientMessage::SetPlayerReady => room_commander.set_player_ready(player_id).await,
+            ClientMessage::GoCrabul => room_commander.go_crabul(player_id).await,
+            ClientMessage::DrawCard => room_commander.draw_card(player_id).await,
+            ClientMessage::SwapCard { card_idx } => {
+                room_commander.swap_card(player_id, card_idx).await
+            }
+            ClientMessage::DiscardCard => room_commander.discard_card(player_id).await,
+            ClientMessage::PeekOwnCard { card_idx } => {
+                room_commander.peek_own_card(player_id, card_idx).await
+            }
+            ClientMessage::PeekOtherCard {
+                other_player_id,
+                other_card_idx,
+            } => {
+                room_commander
+                    .peek_other_card(player_id, other_player_id, other_card_idx)
+                    .await
+            }
+            ClientMessage::BlindSwap {
+                card_idx,
+                other_player_id,
+                other_card_idx,
+            } => {
+                room_commander
+                    .blind_swap(player_id, card_idx, other_player_id, other_card_idx)
+                    .await
+            }
+            ClientMessage::CheckAndSwapStage1 {
+                other_player_id,
+                other_card_idx,
+            } => {
+                room_commander
+                    .check_and_swap_stage1(player_id, other_player_id, other_card_idx)
+                    .await
+            }
+            ClientMessage::CheckAndSwapStage2 { card_idx } => {
+                room_commander
+                    .check_and_swap_stage2(player_id, card_idx)
+                    .await
+            }
+            ClientMessage::ThrowSameCard {
+                picked_player_id,
+                picked_card_idx,
+            } => {
+                room_commander
+                    .throw_same_card(player_id, picked_player_id, picked_card_idx)
+                    .await
+            }
+            ClientMessage::SelectCardToGiveAway { card_idx } => {
+                room_commander
+                    .select_card_to_give_away(player_id, card_idx)
+                    .await
+            }
+            ClientMessage::GetState | ClientMessage::GetLegalActions => {
+                unreachable!("queries are answered above")
+            }
+            ClientMessage::AddBot { level } => {
+                Bot::add(room_commander, player_id, level).await.map(|_| ())
+            }
+            ClientMessage::Leave => {
+                room_commander.remove_player(player_id).await;
+                Ok(())
+            }
+        };
+        result.map(|_| None)
+    }
+}
+
+fn param<T: FromStr>(params: &[&str], idx: usize) -> Result<T, GameError> {
+    params
+        .get(idx)
+        .and_then(|param| param.parse().ok())
+        .ok_or(GameError::UnableToParseCommand)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_json_request() {
+        let request = ClientRequest::parse(
+            r#"{"type":"BlindSwap","card_idx":1,"other_player_id":42,"other_card_idx":0,"request_id":3}"#,
+        )
+        .unwrap();
+        assert!(request.request_id == Some(3));
+        assert!(
+            request.message
+                == ClientMessage::BlindSwap {
+                    card_idx: 1,
+                    other_player_id: 42,
+                    other_card_idx: 0
+                }
+        );
+
+        let request = ClientRequest::parse(r#"{"type":"DrawCard"}"#).unwrap();
+        assert!(request.request_id.is_none());
+        assert!(request.message == ClientMessage::DrawCard);
+    }
+
+    #[test]
+    fn parse_invalid_json_request() {
+        assert!(matches!(
+            ClientRequest::parse(r#"{"type":"SwapCard"}"#),
+            Err(GameError::InvalidMessage(_))
+        ));
+        assert!(ClientRequest::request_id_of(r#"{"type":"SwapCard","request_id":4}"#) == Some(4));
+        assert!(ClientRequest::request_id_of("not json").is_none());
+    }
+
+    #[test]
+    fn parse_slash_commands() {
+        assert!(ClientMessage::parse_slash("/draw").unwrap() == ClientMessage::DrawCard);
+        assert!(
+            ClientMessage::parse_slash("/pow4_1 60000 2").unwrap()
+                == ClientMessage::CheckAndSwapStage1 {
+                    other_player_id: 60000,
+                    other_card_idx: 2
+                }
+        );
+        assert!(
+            ClientMessage::parse_slash("/pow4_2 ").unwrap()
+                == ClientMessage::CheckAndSwapStage2 { card_idx: None }
+        );
+        assert!(matches!(
+            ClientMessage::parse_slash("/pow2 70000 1"),
+            Err(GameError::UnableToParseCommand)
+        ));
+        assert!(ClientMessage::parse_slash("/state").unwrap() == ClientMessage::GetState);
+        assert!(ClientMessage::parse_slash("/actions").unwrap() == ClientMessage::GetLegalActions);
+        assert!(
+            ClientMessage::parse_slash("/lobby_ready false").unwrap()
+                == ClientMessage::SetLobbyReady { ready: false }
+        );
+        assert!(
+            ClientMessage::parse_slash("/bot memory").unwrap()
+                == ClientMessage::AddBot {
+                    level: BotLevel::Memory
+                }
+        );
+        assert!(matches!(
+            ClientMessage::parse_slash("/unknown"),
+            Err(GameError::UnknownCommand)
+        ));
+    }
+}
@@ -0,0 +1,113 @@
+use serde::{Deserialize, Serialize};
+
+use crate::consts::{Identity, PlayerId, PlayerName};
+
+pub type Rating = f64;
+
+pub const INITIAL_RATING: Rating = 1500.0;
+/// Most a rating can move in a single game.
+const K_FACTOR: f64 = 32.0;
+
+/// Who sits behind a player and how strong they are, as known when joining.
+#[derive(Deserialize, Serialize, Clone, PartialEq)]
+pub struct PlayerProfile {
+    pub identity: Identity,
+    pub rating: Rating,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+pub struct RatingChange {
+    pub player_id: PlayerId,
+    /// Rating after the game.
+    pub rating: Rating,
+    pub delta: Rating,
+}
+
+/// Rating kept for a person across games.
+#[derive(Deserialize, Serialize, Clone)]
+pub struct PlayerRating {
+    pub identity: Identity,
+    /// Name last played under.
+    pub name: PlayerName,
+    pub rating: Rating,
+    pub games: u32,
+}
+
+impl PlayerRating {
+    pub fn new(identity: Identity, name: PlayerName) -> Self {
+        Self {
+            identity,
+            name,
+            rating: INITIAL_RATING,
+            games: 0,
+        }
+    }
+}
+
+/// Multiplayer Elo: a game counts as a duel between every pair of players,
+/// won by the better place and drawn on equal places. Places are compared
+/// with `place`, lower being better. The sum of the duels is scaled down so
+/// a whole game moves a rating at most by `K_FACTOR`.
+pub fn rating_changes(players: &[(PlayerId, Rating, i32)]) -> Vec<RatingChange> {
+    if players.len() < 2 {
+        return vec![];
+    }
+    let weight = K_FACTOR / (players.len() - 1) as f64;
+    players
+        .iter()
+        .map(|&(player_id, rating, place)| {
+            let delta: f64 = players
+                .iter()
+                .filter(|(other_id, ..)| *other_id != player_id)
+                .map(|&(_, other_rating, other_place)| {
+                    let expected = 1.0 / (1.0 + 10f64.powf((other_rating - rating) / 400.0));
+                    let actual = match place.cmp(&other_place) {
+                        std::cmp::Ordering::Less => 1.0,
+                        std::cmp::Ordering::Equal => 0.5,
+                        std::cmp::Ordering::Greater => 0.0,
+                    };
+                    weight * (actual - expected)
+                })
+                .sum();
+            let delta = (delta * 10.0).round() / 10.0;
+            RatingChange {
+                player_id,
+                rating: rating + delta,
+                delta,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_place_counts() {
+        let changes = rating_changes(&[
+            (1, INITIAL_RATING, 0),
+            (2, INITIAL_RATING, 5),
+            (3, INITIAL_RATING, 9),
+        ]);
+        assert!(changes[0].delta == 16.0);
+        assert!(changes[1].delta == 0.0);
+        assert!(changes[2].delta == -16.0);
+        assert!(changes[0].rating == INITIAL_RATING + 16.0);
+    }
+
+    #[test]
+    fn upsets_move_ratings_more() {
+        let expected_win = rating_changes(&[(1, 1700.0, 0), (2, 1300.0, 1)]);
+        let upset = rating_changes(&[(1, 1700.0, 1), (2, 1300.0, 0)]);
+        assert!(expected_win[0].delta < -upset[0].delta);
+        assert!(upset[1].delta > 16.0);
+        assert!(rating_changes(&[(1, 1500.0, 0)]).is_empty());
+    }
+
+    #[test]
+    fn equal_places_are_draws() {
+        let changes = rating_changes(&[(1, 1500.0, 3), (2, 1500.0, 3)]);
+        assert!(changes.iter().all(|change| change.delta == 0.0));
+    }
+}
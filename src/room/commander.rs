@@ -3,11 +3,16 @@ use tokio::sync::{
     oneshot,
 };
 
-use crate::consts::{PlayerId, PlayerName};
+use crate::consts::{PlayerId, PlayerName, ReconnectToken, SpectatorId};
+use crate::protocol::ClientMessage;
+use crate::rating::PlayerProfile;
 use crate::room::commands::RoomCommand;
 use crate::room::events::RoomEvent;
 
-use super::errors::GameError;
+use super::{
+    errors::GameError,
+    snapshot::{GameSnapshot, RoomSummary},
+};
 
 #[derive(Clone)]
 pub struct RoomCommander {
@@ -21,10 +26,56 @@ impl RoomCommander {
     pub async fn new_player(
         &self,
         name: PlayerName,
+    ) -> Result<(PlayerId, UnboundedReceiver<RoomEvent>), GameError> {
+        self.new_identified_player(name, None).await
+    }
+    /// Seats a player carrying an identity and a rating that outlive the room.
+    pub async fn new_identified_player(
+        &self,
+        name: PlayerName,
+        profile: Option<PlayerProfile>,
+    ) -> Result<(PlayerId, UnboundedReceiver<RoomEvent>), GameError> {
+        self.add_player(name, profile, false).await
+    }
+    /// Seats a program. It is told its legal actions whenever it owes a
+    /// decision and has `bot_turn_countdown` to play its turns.
+    pub async fn new_bot(
+        &self,
+        name: PlayerName,
+        profile: Option<PlayerProfile>,
+    ) -> Result<(PlayerId, UnboundedReceiver<RoomEvent>), GameError> {
+        self.add_player(name, profile, true).await
+    }
+    /// Seats a bot on behalf of the host, anybody else is turned down.
+    pub async fn add_bot(
+        &self,
+        id: PlayerId,
+        name: PlayerName,
     ) -> Result<(PlayerId, UnboundedReceiver<RoomEvent>), GameError> {
         let (cmd_tx, cmd_rx) = oneshot::channel();
         self.tx_channel
-            .send(RoomCommand::AddPlayer { name, cmd_tx })
+            .send(RoomCommand::AddBot {
+                player_id: id,
+                name,
+                cmd_tx,
+            })
+            .unwrap();
+        cmd_rx.await.unwrap()
+    }
+    async fn add_player(
+        &self,
+        name: PlayerName,
+        profile: Option<PlayerProfile>,
+        bot: bool,
+    ) -> Result<(PlayerId, UnboundedReceiver<RoomEvent>), GameError> {
+        let (cmd_tx, cmd_rx) = oneshot::channel();
+        self.tx_channel
+            .send(RoomCommand::AddPlayer {
+                name,
+                profile,
+                bot,
+                cmd_tx,
+            })
             .unwrap();
         cmd_rx.await.unwrap()
     }
@@ -38,10 +89,123 @@ impl RoomCommander {
             .unwrap();
         cmd_rx.await.unwrap();
     }
-    pub async fn start_game(&self) -> Result<(), GameError> {
+    /// Parks the player's channel in the room so that it can be resumed later.
+    /// The room may already be gone, in which case there is nothing to do.
+    pub async fn disconnect_player(
+        &self,
+        id: PlayerId,
+        player_channel: UnboundedReceiver<RoomEvent>,
+    ) {
+        let (cmd_tx, cmd_rx) = oneshot::channel();
+        let _ = self.tx_channel.send(RoomCommand::DisconnectPlayer {
+            player_id: id,
+            player_channel,
+            cmd_tx,
+        });
+        let _ = cmd_rx.await;
+    }
+    pub async fn resume_player(
+        &self,
+        reconnect_token: ReconnectToken,
+    ) -> Result<(PlayerId, UnboundedReceiver<RoomEvent>), GameError> {
+        let (cmd_tx, cmd_rx) = oneshot::channel();
+        self.tx_channel
+            .send(RoomCommand::ResumePlayer {
+                reconnect_token,
+                cmd_tx,
+            })
+            .unwrap();
+        cmd_rx.await.unwrap()
+    }
+    /// Fails with `RoomClosed` once the room is gone, as bots keep asking
+    /// until their channel runs dry.
+    pub async fn get_state(&self, id: PlayerId) -> Result<GameSnapshot, GameError> {
         let (cmd_tx, cmd_rx) = oneshot::channel();
         self.tx_channel
-            .send(RoomCommand::StartGame { cmd_tx })
+            .send(RoomCommand::GetState {
+                player_id: id,
+                cmd_tx,
+            })
+            .map_err(|_| GameError::RoomClosed)?;
+        cmd_rx.await.unwrap_or(Err(GameError::RoomClosed))
+    }
+    pub async fn get_legal_actions(&self, id: PlayerId) -> Result<Vec<ClientMessage>, GameError> {
+        let (cmd_tx, cmd_rx) = oneshot::channel();
+        self.tx_channel
+            .send(RoomCommand::GetLegalActions {
+                player_id: id,
+                cmd_tx,
+            })
+            .map_err(|_| GameError::RoomClosed)?;
+        cmd_rx.await.unwrap_or(Err(GameError::RoomClosed))
+    }
+    /// `None` once the room is gone.
+    pub async fn get_summary(&self) -> Option<RoomSummary> {
+        let (cmd_tx, cmd_rx) = oneshot::channel();
+        self.tx_channel
+            .send(RoomCommand::GetSummary { cmd_tx })
+            .ok()?;
+        cmd_rx.await.ok()
+    }
+    pub async fn new_spectator(&self) -> (SpectatorId, UnboundedReceiver<RoomEvent>) {
+        let (cmd_tx, cmd_rx) = oneshot::channel();
+        self.tx_channel
+            .send(RoomCommand::AddSpectator { cmd_tx })
+            .unwrap();
+        cmd_rx.await.unwrap()
+    }
+    pub fn close(&self) {
+        let _ = self.tx_channel.send(RoomCommand::CloseRoom);
+    }
+    pub fn remove_spectator(&self, spectator_id: SpectatorId) {
+        let _ = self
+            .tx_channel
+            .send(RoomCommand::RemoveSpectator { spectator_id });
+    }
+    pub async fn start_game(&self, id: PlayerId) -> Result<(), GameError> {
+        let (cmd_tx, cmd_rx) = oneshot::channel();
+        self.tx_channel
+            .send(RoomCommand::StartGame {
+                player_id: id,
+                cmd_tx,
+            })
+            .unwrap();
+        cmd_rx.await.unwrap()
+    }
+    pub async fn kick_player(&self, id: PlayerId, kicked_id: PlayerId) -> Result<(), GameError> {
+        let (cmd_tx, cmd_rx) = oneshot::channel();
+        self.tx_channel
+            .send(RoomCommand::KickPlayer {
+                player_id: id,
+                kicked_player_id: kicked_id,
+                cmd_tx,
+            })
+            .unwrap();
+        cmd_rx.await.unwrap()
+    }
+    pub async fn transfer_host(
+        &self,
+        id: PlayerId,
+        new_host_id: PlayerId,
+    ) -> Result<(), GameError> {
+        let (cmd_tx, cmd_rx) = oneshot::channel();
+        self.tx_channel
+            .send(RoomCommand::TransferHost {
+                player_id: id,
+                new_host_id,
+                cmd_tx,
+            })
+            .unwrap();
+        cmd_rx.await.unwrap()
+    }
+    pub async fn set_lobby_ready(&self, id: PlayerId, ready: bool) -> Result<(), GameError> {
+        let (cmd_tx, cmd_rx) = oneshot::channel();
+        self.tx_channel
+            .send(RoomCommand::SetLobbyReady {
+                player_id: id,
+                ready,
+                cmd_tx,
+            })
             .unwrap();
         cmd_rx.await.unwrap()
     }
@@ -1,12 +1,26 @@
 use tokio::sync::{mpsc::UnboundedReceiver, oneshot};
 
-use crate::consts::{PlayerId, PlayerName};
+use crate::consts::{PlayerId, PlayerName, ReconnectToken, SpectatorId};
+use crate::protocol::ClientMessage;
+use crate::rating::PlayerProfile;
 use crate::room::errors::GameError;
 
-use super::events::RoomEvent;
+use super::{
+    events::RoomEvent,
+    snapshot::{GameSnapshot, RoomSummary},
+};
 
 pub enum RoomCommand {
     AddPlayer {
+        name: PlayerName,
+        profile: Option<PlayerProfile>,
+        /// Seats driven by a program, prompted for each decision.
+        bot: bool,
+        cmd_tx: oneshot::Sender<Result<(PlayerId, UnboundedReceiver<RoomEvent>), GameError>>,
+    },
+    /// A bot seated on the host's request.
+    AddBot {
+        player_id: PlayerId,
         name: PlayerName,
         cmd_tx: oneshot::Sender<Result<(PlayerId, UnboundedReceiver<RoomEvent>), GameError>>,
     },
@@ -14,7 +28,51 @@ pub enum RoomCommand {
         player_id: PlayerId,
         cmd_tx: oneshot::Sender<()>,
     },
+    DisconnectPlayer {
+        player_id: PlayerId,
+        player_channel: UnboundedReceiver<RoomEvent>,
+        cmd_tx: oneshot::Sender<()>,
+    },
+    ResumePlayer {
+        reconnect_token: ReconnectToken,
+        cmd_tx: oneshot::Sender<Result<(PlayerId, UnboundedReceiver<RoomEvent>), GameError>>,
+    },
+    ReconnectTimeout(PlayerId),
+    GetState {
+        player_id: PlayerId,
+        cmd_tx: oneshot::Sender<Result<GameSnapshot, GameError>>,
+    },
+    GetLegalActions {
+        player_id: PlayerId,
+        cmd_tx: oneshot::Sender<Result<Vec<ClientMessage>, GameError>>,
+    },
+    GetSummary {
+        cmd_tx: oneshot::Sender<RoomSummary>,
+    },
+    AddSpectator {
+        cmd_tx: oneshot::Sender<(SpectatorId, UnboundedReceiver<RoomEvent>)>,
+    },
+    RemoveSpectator {
+        spectator_id: SpectatorId,
+    },
     StartGame {
+        player_id: PlayerId,
+        cmd_tx: oneshot::Sender<Result<(), GameError>>,
+    },
+    SetLobbyReady {
+        player_id: PlayerId,
+        ready: bool,
+        cmd_tx: oneshot::Sender<Result<(), GameError>>,
+    },
+    AutoStartGame,
+    KickPlayer {
+        player_id: PlayerId,
+        kicked_player_id: PlayerId,
+        cmd_tx: oneshot::Sender<Result<(), GameError>>,
+    },
+    TransferHost {
+        player_id: PlayerId,
+        new_host_id: PlayerId,
         cmd_tx: oneshot::Sender<Result<(), GameError>>,
     },
     SetPlayerReady {
@@ -80,6 +138,11 @@ pub enum RoomCommand {
         cmd_tx: oneshot::Sender<Result<(), GameError>>,
     },
     StopRoomServer,
+    /// Stops the room if nobody has joined it in the meantime.
+    EmptyRoomTimeout,
+    /// Tells everybody the room is closing, then stops it.
+    CloseRoom,
     ForceEndTurn(PlayerId),
     FinalizeGame,
+    StartNextRound,
 }
@@ -0,0 +1,211 @@
+use std::{collections::BTreeMap, time::Duration};
+
+use serde::{Deserialize, Serialize};
+
+use crate::deck::{Card, DECK_SIZE};
+
+use super::{
+    consts::{
+        AUTO_START_COUNTDOWN, BOT_TURN_COUNTDOWN, EMPTY_ROOM_TIMEOUT, FINALIZE_GAME_COUNTDOWN,
+        HAND_SIZE, MAX_CARD_SCORE, MAX_PLAYERS, MIN_PLAYERS, NEXT_ROUND_COUNTDOWN, PEEKED_CARDS,
+        PEEKING_PHASE_COUNTDOWN, RECONNECT_GRACE_PERIOD, TURN_COUNTDOWN,
+    },
+    server::Power,
+};
+
+/// Rule set of a single room. Every field falls back to the classic rules when
+/// missing, so clients only need to send what they want to change.
+#[derive(Deserialize, Serialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct RoomConfig {
+    pub min_players: usize,
+    pub max_players: usize,
+    pub hand_size: usize,
+    pub peeked_cards: usize,
+    /// Delay before the game starts once enough players are ready in the lobby.
+    #[serde(with = "duration_secs")]
+    pub auto_start_countdown: Duration,
+    #[serde(with = "duration_secs")]
+    pub peeking_phase_countdown: Duration,
+    #[serde(with = "duration_secs")]
+    pub turn_countdown: Duration,
+    /// Time a bot seat has for each decision of its turn.
+    #[serde(with = "duration_secs")]
+    pub bot_turn_countdown: Duration,
+    #[serde(with = "duration_secs")]
+    pub finalize_game_countdown: Duration,
+    /// How long a disconnected player keeps their seat in a running game.
+    #[serde(with = "duration_secs")]
+    pub reconnect_grace_period: Duration,
+    /// How long a room nobody has joined stays open.
+    #[serde(with = "duration_secs")]
+    pub empty_room_timeout: Duration,
+    /// Power activated when a card of the given rank is discarded.
+    pub powers: BTreeMap<u8, Power>,
+    /// Cards scoring differently from `Card::get_score`, by at most
+    /// `MAX_CARD_SCORE` either way.
+    pub score_overrides: Vec<(Card, i8)>,
+    /// Plays rounds until players go over this cumulative score, eliminating
+    /// them as they do. A single hand is played when missing.
+    pub score_limit: Option<i32>,
+    /// Pause between two rounds of a match.
+    #[serde(with = "duration_secs")]
+    pub next_round_countdown: Duration,
+    /// Seed of the room rng. Rooms with the same seed and the same sequence of
+    /// commands play out identically; a random one is picked when missing.
+    pub seed: Option<u64>,
+}
+
+impl Default for RoomConfig {
+    fn default() -> Self {
+        Self {
+            min_players: MIN_PLAYERS,
+            max_players: MAX_PLAYERS,
+            hand_size: HAND_SIZE,
+            peeked_cards: PEEKED_CARDS,
+            auto_start_countdown: AUTO_START_COUNTDOWN,
+            peeking_phase_countdown: PEEKING_PHASE_COUNTDOWN,
+            turn_countdown: TURN_COUNTDOWN,
+            bot_turn_countdown: BOT_TURN_COUNTDOWN,
+            finalize_game_countdown: FINALIZE_GAME_COUNTDOWN,
+            reconnect_grace_period: RECONNECT_GRACE_PERIOD,
+            empty_room_timeout: EMPTY_ROOM_TIMEOUT,
+            powers: BTreeMap::from([
+                (7, Power::PeekOwnCard),
+                (8, Power::PeekOwnCard),
+                (9, Power::PeekOtherCard),
+                (10, Power::PeekOtherCard),
+                (11, Power::BlindSwap),
+                (12, Power::BlindSwap),
+                (13, Power::CheckAndSwapStage1),
+            ]),
+            score_overrides: vec![],
+            score_limit: None,
+            next_round_countdown: NEXT_ROUND_COUNTDOWN,
+            seed: None,
+        }
+    }
+}
+
+impl RoomConfig {
+    /// Comes straight from clients, so nothing here may overflow.
+    pub fn is_valid(&self) -> bool {
+        self.min_players >= 2
+            && (self.min_players..=MAX_PLAYERS).contains(&self.max_players)
+            && self.hand_size > 0
+            && self.peeked_cards <= self.hand_size
+            && self
+                .max_players
+                .checked_mul(self.hand_size)
+                .is_some_and(|cards| cards < DECK_SIZE)
+            && self
+                .score_overrides
+                .iter()
+                .all(|(_, score)| (-MAX_CARD_SCORE..=MAX_CARD_SCORE).contains(score))
+            && self.score_limit.is_none_or(|score_limit| score_limit > 0)
+            && self.powers.iter().all(|(rank, power)| {
+                (1..=13).contains(rank) && !matches!(power, Power::CheckAndSwapStage2(..))
+            })
+    }
+
+    pub fn match_power(&self, card: Card) -> Option<Power> {
+        card.get_value()
+            .and_then(|value| self.powers.get(&value))
+            .copied()
+    }
+
+    pub fn card_score(&self, card: Card) -> i8 {
+        self.score_overrides
+            .iter()
+            .find(|(overridden, _)| *overridden == card)
+            .map_or_else(|| card.get_score(), |(_, score)| *score)
+    }
+}
+
+pub(crate) mod duration_secs {
+    use std::time::Duration;
+
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(duration.as_secs())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        u64::deserialize(deserializer).map(Duration::from_secs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn partial_config_falls_back_to_defaults() {
+        let config: RoomConfig =
+            serde_json::from_str(r#"{"hand_size": 6, "turn_countdown": 30}"#).unwrap();
+        assert!(config.hand_size == 6);
+        assert!(config.turn_countdown == Duration::from_secs(30));
+        assert!(config.max_players == MAX_PLAYERS);
+        assert!(config.powers == RoomConfig::default().powers);
+    }
+
+    #[test]
+    fn invalid_configs() {
+        let too_many_peeks = RoomConfig {
+            peeked_cards: 5,
+            ..Default::default()
+        };
+        assert!(!too_many_peeks.is_valid());
+
+        let not_enough_cards = RoomConfig {
+            max_players: MAX_PLAYERS,
+            hand_size: 9,
+            ..Default::default()
+        };
+        assert!(!not_enough_cards.is_valid());
+
+        let too_many_players = RoomConfig {
+            max_players: MAX_PLAYERS + 1,
+            hand_size: 1,
+            ..Default::default()
+        };
+        assert!(!too_many_players.is_valid());
+
+        let overflowing_cards = RoomConfig {
+            max_players: 2,
+            hand_size: usize::MAX / 2 + 1,
+            ..Default::default()
+        };
+        assert!(!overflowing_cards.is_valid());
+
+        let overflowing_score = RoomConfig {
+            score_overrides: vec![(Card::Joker, i8::MAX)],
+            ..Default::default()
+        };
+        assert!(!overflowing_score.is_valid());
+
+        let second_stage_power = RoomConfig {
+            powers: BTreeMap::from([(5, Power::CheckAndSwapStage2(0, 0))]),
+            ..Default::default()
+        };
+        assert!(!second_stage_power.is_valid());
+
+        let no_score_limit = RoomConfig {
+            score_limit: Some(0),
+            ..Default::default()
+        };
+        assert!(!no_score_limit.is_valid());
+    }
+
+    #[test]
+    fn score_overrides() {
+        let config = RoomConfig {
+            score_overrides: vec![(Card::Joker, -2)],
+            ..Default::default()
+        };
+        assert!(config.card_score(Card::Joker) == -2);
+        assert!(config.card_score(Card::Hearts(13)) == -1);
+        assert!(config.card_score(Card::Spade(9)) == 9);
+    }
+}
@@ -2,6 +2,15 @@ use std::time::Duration;
 
 pub const MIN_PLAYERS: usize = 2;
 pub const MAX_PLAYERS: usize = 6;
+pub const AUTO_START_COUNTDOWN: Duration = Duration::from_secs(10);
 pub const PEEKING_PHASE_COUNTDOWN: Duration = Duration::from_secs(10);
 pub const TURN_COUNTDOWN: Duration = Duration::from_secs(600);
+pub const BOT_TURN_COUNTDOWN: Duration = Duration::from_secs(15);
 pub const FINALIZE_GAME_COUNTDOWN: Duration = Duration::from_secs(5);
+pub const NEXT_ROUND_COUNTDOWN: Duration = Duration::from_secs(10);
+pub const RECONNECT_GRACE_PERIOD: Duration = Duration::from_secs(60);
+pub const EMPTY_ROOM_TIMEOUT: Duration = Duration::from_secs(300);
+pub const HAND_SIZE: usize = 4;
+pub const PEEKED_CARDS: usize = 2;
+/// No card may score further from zero than a king.
+pub const MAX_CARD_SCORE: i8 = 13;
@@ -1,6 +1,6 @@
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Debug)]
+#[derive(Deserialize, Serialize, Debug)]
 pub enum GameError {
     NameAlreadyExists,
     EmptyName,
@@ -8,5 +8,12 @@ pub enum GameError {
     TooManyPlayers,
     OperationNotAllowedAtCurrentState,
     InvalidCardIndex,
+    PlayerNotFound,
     UnableToParseCommand,
+    UnknownCommand,
+    InvalidMessage(String),
+    InvalidReconnectToken,
+    PlayerAlreadyConnected,
+    NotHost,
+    RoomClosed,
 }
@@ -1,26 +1,57 @@
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    consts::{PlayerId, PlayerName, RoomId},
+    consts::{PlayerId, PlayerName, ReconnectToken, RoomCode, RoomId},
     deck::Card,
+    invite::InviteToken,
+    protocol::ClientMessage,
 };
 
-use super::server::{DuplicateCardResult, FinalScore, Power};
+use super::{
+    record::GameRecord,
+    server::{DuplicateCardResult, FinalScore, Power, Standing},
+    snapshot::GameSnapshot,
+};
+
+/// What a room reports back to the server that owns it.
+pub enum RoomNotification {
+    GameFinished(Box<GameRecord>),
+}
 
 #[derive(Deserialize, Serialize, Clone)]
 pub enum RoomEvent {
     PlayerJoined {
         room_id: RoomId,
+        room_code: RoomCode,
         player_id: PlayerId,
         player_name: PlayerName,
-        player_list: HashMap<PlayerId, PlayerName>,
+        player_list: BTreeMap<PlayerId, PlayerName>,
+        host: PlayerId,
+        /// The seat is played by a program.
+        bot: bool,
+        /// Only filled in the copy sent to the joining player.
+        reconnect_token: Option<ReconnectToken>,
     },
     PlayerLeft(PlayerId),
+    PlayerKicked(PlayerId),
+    LobbyReadyChanged(PlayerId, bool),
+    /// The game starts by itself in the given number of seconds.
+    AutoStartScheduled(u64),
+    AutoStartCancelled,
+    HostChanged(PlayerId),
+    PlayerForfeited(PlayerId),
+    PlayerDisconnected(PlayerId),
+    PlayerReconnected(PlayerId),
+    StateSnapshot(GameSnapshot),
+    RoomClosed,
+    /// Only sent to the creator of a private room, to be shared with the
+    /// other players.
+    InviteCreated(InviteToken),
     GameStarted,
     PlayerTurn(PlayerId),
-    PeekingPhaseStarted((Card, Card)),
+    PeekingPhaseStarted(Vec<Card>),
     PlayerIsReady(PlayerId),
     CardWasDrawn(PlayerId),
     DrawnCard(Card),
@@ -39,7 +70,27 @@ pub enum RoomEvent {
     CardReplaced(PlayerId, usize, PlayerId, usize),
     PlayerWentCrabul(PlayerId),
     GameTerminated(FinalScore),
+    /// Standings of a match after each round, best first.
+    RoundEnded {
+        round: u32,
+        standings: Vec<Standing>,
+    },
+    RoundStarted(u32),
+    PlayerEliminated(PlayerId),
+    MatchEnded {
+        winner: PlayerId,
+        standings: Vec<Standing>,
+    },
     TurnEndedByTimeout(PlayerId),
     PowerDiscarded(PlayerId, Power),
     ForcedBlindSwap(PlayerId, usize, PlayerId, usize),
+    /// What the player may send from now on, see `RoomServer::legal_actions`.
+    /// Sent to every player each time the game moves to another state.
+    LegalActions(Vec<ClientMessage>),
+    /// Only sent to bot seats, when the game waits for them. Any of the
+    /// actions goes through, anything else is refused.
+    YourMove {
+        actions: Vec<ClientMessage>,
+        remaining_time_ms: Option<u64>,
+    },
 }
@@ -1,6 +1,9 @@
 pub mod commander;
 pub mod commands;
+pub mod config;
 pub mod consts;
 pub mod errors;
 pub mod events;
+pub mod record;
 pub mod server;
+pub mod snapshot;
@@ -0,0 +1,301 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::oneshot;
+
+use crate::{
+    consts::{GameId, PlayerId, PlayerName, RoomId},
+    deck::Card,
+    rating::PlayerProfile,
+};
+
+use super::{commands::RoomCommand, config::RoomConfig, events::RoomEvent};
+
+/// Everything that happened in a room, in order, from its creation to the end
+/// of the game. Re-running `commands` on a room built from `config` and `seed`
+/// reproduces the same events.
+#[derive(Deserialize, Serialize, Clone)]
+pub struct GameRecord {
+    pub game_id: GameId,
+    pub room_id: RoomId,
+    pub config: RoomConfig,
+    pub seed: u64,
+    /// Unix time in milliseconds at which the room was created.
+    pub started_at: u64,
+    pub entries: Vec<RecordEntry>,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+pub struct RecordEntry {
+    /// Milliseconds since the room was created.
+    pub elapsed_ms: u64,
+    pub kind: RecordKind,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+pub enum RecordKind {
+    Command(RecordedCommand),
+    /// An event, sent to everyone when `to` is `None`.
+    Event {
+        to: Option<PlayerId>,
+        event: RoomEvent,
+    },
+    /// Every hand after a command, hidden cards included.
+    Hands(BTreeMap<PlayerId, Vec<Card>>),
+}
+
+/// Serializable counterpart of the `RoomCommand`s that change the game.
+#[derive(Deserialize, Serialize, Clone, PartialEq)]
+pub enum RecordedCommand {
+    /// The flag is set for bot seats.
+    AddPlayer(PlayerName, Option<PlayerProfile>, bool),
+    AddBot(PlayerId, PlayerName),
+    RemovePlayer(PlayerId),
+    DisconnectPlayer(PlayerId),
+    ResumePlayer(PlayerId),
+    ReconnectTimeout(PlayerId),
+    StartGame(PlayerId),
+    SetLobbyReady(PlayerId, bool),
+    AutoStartGame,
+    KickPlayer(PlayerId, PlayerId),
+    TransferHost(PlayerId, PlayerId),
+    SetPlayerReady(PlayerId),
+    NextTurn,
+    GoCrabul(PlayerId),
+    DrawCard(PlayerId),
+    SwapCard(PlayerId, usize),
+    DiscardCard(PlayerId),
+    PeekOwnCard(PlayerId, usize),
+    PeekOtherCard(PlayerId, PlayerId, usize),
+    BlindSwap(PlayerId, usize, PlayerId, usize),
+    CheckAndSwapStage1(PlayerId, PlayerId, usize),
+    CheckAndSwapStage2(PlayerId, Option<usize>),
+    ThrowSameCard(PlayerId, PlayerId, usize),
+    SelectCardToGiveAway(PlayerId, usize),
+    ForceEndTurn(PlayerId),
+    FinalizeGame,
+    StartNextRound,
+}
+
+impl RecordedCommand {
+    /// Queries, spectators and resumes are left out: queries change nothing and
+    /// resumes are only known by token, the room records them itself.
+    pub fn from_command(cmd: &RoomCommand) -> Option<Self> {
+        let recorded = match cmd {
+            RoomCommand::AddPlayer {
+                name, profile, bot, ..
+            } => Self::AddPlayer(name.clone(), profile.clone(), *bot),
+            RoomCommand::AddBot {
+                player_id, name, ..
+            } => Self::AddBot(*player_id, name.clone()),
+            RoomCommand::RemovePlayer { player_id, .. } => Self::RemovePlayer(*player_id),
+            RoomCommand::DisconnectPlayer { player_id, .. } => Self::DisconnectPlayer(*player_id),
+            RoomCommand::ReconnectTimeout(player_id) => Self::ReconnectTimeout(*player_id),
+            RoomCommand::StartGame { player_id, .. } => Self::StartGame(*player_id),
+            RoomCommand::SetLobbyReady {
+                player_id, ready, ..
+            } => Self::SetLobbyReady(*player_id, *ready),
+            RoomCommand::AutoStartGame => Self::AutoStartGame,
+            RoomCommand::KickPlayer {
+                player_id,
+                kicked_player_id,
+                ..
+            } => Self::KickPlayer(*player_id, *kicked_player_id),
+            RoomCommand::TransferHost {
+                player_id,
+                new_host_id,
+                ..
+            } => Self::TransferHost(*player_id, *new_host_id),
+            RoomCommand::SetPlayerReady { player_id, .. } => Self::SetPlayerReady(*player_id),
+            RoomCommand::NextTurn => Self::NextTurn,
+            RoomCommand::GoCrabul { player_id, .. } => Self::GoCrabul(*player_id),
+            RoomCommand::DrawCard { player_id, .. } => Self::DrawCard(*player_id),
+            RoomCommand::SwapCard {
+                player_id,
+                card_idx,
+                ..
+            } => Self::SwapCard(*player_id, *card_idx),
+            RoomCommand::DiscardCard { player_id, .. } => Self::DiscardCard(*player_id),
+            RoomCommand::PeekOwnCard {
+                player_id,
+                card_idx,
+                ..
+            } => Self::PeekOwnCard(*player_id, *card_idx),
+            RoomCommand::PeekOtherCard {
+                player_id,
+                other_player_id,
+                other_card_idx,
+                ..
+            } => Self::PeekOtherCard(*player_id, *other_player_id, *other_card_idx),
+            RoomCommand::BlindSwap {
+                player_id,
+                card_idx,
+                other_player_id,
+                other_card_idx,
+                ..
+            } => Self::BlindSwap(*player_id, *card_idx, *other_player_id, *other_card_idx),
+            RoomCommand::CheckAndSwapStage1 {
+                player_id,
+                other_player_id,
+                other_card_idx,
+                ..
+            } => Self::CheckAndSwapStage1(*player_id, *other_player_id, *other_card_idx),
+            RoomCommand::CheckAndSwapStage2 {
+                player_id,
+                card_idx,
+                ..
+            } => Self::CheckAndSwapStage2(*player_id, *card_idx),
+            RoomCommand::ThrowSameCard {
+                player_id,
+                picked_player_id,
+                picked_card_idx,
+                ..
+            } => Self::ThrowSameCard(*player_id, *picked_player_id, *picked_card_idx),
+            RoomCommand::SelectCardToGiveAway {
+                player_id,
+                card_idx,
+                ..
+            } => Self::SelectCardToGiveAway(*player_id, *card_idx),
+            RoomCommand::ForceEndTurn(player_id) => Self::ForceEndTurn(*player_id),
+            RoomCommand::FinalizeGame => Self::FinalizeGame,
+            RoomCommand::StartNextRound => Self::StartNextRound,
+            RoomCommand::ResumePlayer { .. }
+            | RoomCommand::GetState { .. }
+            | RoomCommand::GetLegalActions { .. }
+            | RoomCommand::GetSummary { .. }
+            | RoomCommand::AddSpectator { .. }
+            | RoomCommand::RemoveSpectator { .. }
+            | RoomCommand::StopRoomServer
+            | RoomCommand::EmptyRoomTimeout
+            | RoomCommand::CloseRoom => return None,
+        };
+        Some(recorded)
+    }
+
+    /// Rebuilds the command with replies going nowhere. Commands tied to a
+    /// player session (joining, disconnecting, resuming) need their channels
+    /// and are left to the caller.
+    pub fn into_command(self) -> Option<RoomCommand> {
+        let command = match self {
+            Self::AddPlayer(..)
+            | Self::AddBot(..)
+            | Self::DisconnectPlayer(_)
+            | Self::ResumePlayer(_) => return None,
+            Self::RemovePlayer(player_id) => RoomCommand::RemovePlayer {
+                player_id,
+                cmd_tx: oneshot::channel().0,
+            },
+            Self::ReconnectTimeout(player_id) => RoomCommand::ReconnectTimeout(player_id),
+            Self::StartGame(player_id) => RoomCommand::StartGame {
+                player_id,
+                cmd_tx: oneshot::channel().0,
+            },
+            Self::SetLobbyReady(player_id, ready) => RoomCommand::SetLobbyReady {
+                player_id,
+                ready,
+                cmd_tx: oneshot::channel().0,
+            },
+            Self::AutoStartGame => RoomCommand::AutoStartGame,
+            Self::KickPlayer(player_id, kicked_player_id) => RoomCommand::KickPlayer {
+                player_id,
+                kicked_player_id,
+                cmd_tx: oneshot::channel().0,
+            },
+            Self::TransferHost(player_id, new_host_id) => RoomCommand::TransferHost {
+                player_id,
+                new_host_id,
+                cmd_tx: oneshot::channel().0,
+            },
+            Self::SetPlayerReady(player_id) => RoomCommand::SetPlayerReady {
+                player_id,
+                cmd_tx: oneshot::channel().0,
+            },
+            Self::NextTurn => RoomCommand::NextTurn,
+            Self::GoCrabul(player_id) => RoomCommand::GoCrabul {
+                player_id,
+                cmd_tx: oneshot::channel().0,
+            },
+            Self::DrawCard(player_id) => RoomCommand::DrawCard {
+                player_id,
+                cmd_tx: oneshot::channel().0,
+            },
+            Self::SwapCard(player_id, card_idx) => RoomCommand::SwapCard {
+                player_id,
+                card_idx,
+                cmd_tx: oneshot::channel().0,
+            },
+            Self::DiscardCard(player_id) => RoomCommand::DiscardCard {
+                player_id,
+                cmd_tx: oneshot::channel().0,
+            },
+            Self::PeekOwnCard(player_id, card_idx) => RoomCommand::PeekOwnCard {
+                player_id,
+                card_idx,
+                cmd_tx: oneshot::channel().0,
+            },
+            Self::PeekOtherCard(player_id, other_player_id, other_card_idx) => {
+                RoomCommand::PeekOtherCard {
+                    player_id,
+                    other_player_id,
+                    other_card_idx,
+                    cmd_tx: oneshot::channel().0,
+                }
+            }
+            Self::BlindSwap(player_id, card_idx, other_player_id, other_card_idx) => {
+                RoomCommand::BlindSwap {
+                    player_id,
+                    card_idx,
+                    other_player_id,
+                    other_card_idx,
+                    cmd_tx: oneshot::channel().0,
+                }
+            }
+            Self::CheckAndSwapStage1(player_id, other_player_id, other_card_idx) => {
+                RoomCommand::CheckAndSwapStage1 {
+                    player_id,
+                    other_player_id,
+                    other_card_idx,
+                    cmd_tx: oneshot::channel().0,
+                }
+            }
+            Self::CheckAndSwapStage2(player_id, card_idx) => RoomCommand::CheckAndSwapStage2 {
+                player_id,
+                card_idx,
+                cmd_tx: oneshot::channel().0,
+            },
+            Self::ThrowSameCard(player_id, picked_player_id, picked_card_idx) => {
+                RoomCommand::ThrowSameCard {
+                    player_id,
+                    picked_player_id,
+                    picked_card_idx,
+                    cmd_tx: oneshot::channel().0,
+                }
+            }
+            Self::SelectCardToGiveAway(player_id, card_idx) => RoomCommand::SelectCardToGiveAway {
+                player_id,
+                card_idx,
+                cmd_tx: oneshot::channel().0,
+            },
+            Self::ForceEndTurn(player_id) => RoomCommand::ForceEndTurn(player_id),
+            Self::FinalizeGame => RoomCommand::FinalizeGame,
+            Self::StartNextRound => RoomCommand::StartNextRound,
+        };
+        Some(command)
+    }
+}
+
+impl GameRecord {
+    pub fn commands(&self) -> impl Iterator<Item = &RecordedCommand> {
+        self.entries.iter().filter_map(|entry| match &entry.kind {
+            RecordKind::Command(cmd) => Some(cmd),
+            _ => None,
+        })
+    }
+
+    pub fn events(&self) -> impl Iterator<Item = (Option<PlayerId>, &RoomEvent)> {
+        self.entries.iter().filter_map(|entry| match &entry.kind {
+            RecordKind::Event { to, event } => Some((*to, event)),
+            _ => None,
+        })
+    }
+}
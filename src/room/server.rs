@@ -1,27 +1,40 @@
-use std::{collections::HashMap, mem};
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, HashMap, HashSet},
+    future::Future,
+    mem,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
-use rand::{seq::IteratorRandom, thread_rng, Rng};
+use rand::{rngs::StdRng, seq::IteratorRandom, thread_rng, Rng, SeedableRng};
 use serde::{Deserialize, Serialize};
 use tokio::{
     spawn,
-    sync::mpsc::{self, UnboundedReceiver, UnboundedSender},
+    sync::{
+        mpsc::{self, UnboundedReceiver, UnboundedSender},
+        oneshot,
+    },
     task::JoinHandle,
-    time::sleep,
+    time::{sleep, Instant},
 };
 
 use crate::{
-    consts::{PlayerId, PlayerName, RoomId},
+    consts::{GameId, Identity, PlayerId, PlayerName, ReconnectToken, RoomId, SpectatorId},
     deck::{Card, Deck},
-    room::{commander::RoomCommander, commands::RoomCommand, events::RoomEvent},
-};
-
-use super::{
-    consts::{
-        FINALIZE_GAME_COUNTDOWN, MAX_PLAYERS, MIN_PLAYERS, PEEKING_PHASE_COUNTDOWN, TURN_COUNTDOWN,
+    protocol::ClientMessage,
+    rating::{self, PlayerProfile, Rating, RatingChange},
+    room::{
+        commander::RoomCommander,
+        commands::RoomCommand,
+        events::{RoomEvent, RoomNotification},
+        record::{GameRecord, RecordEntry, RecordKind, RecordedCommand},
+        snapshot::{GameSnapshot, Phase, PlayerSnapshot, RoomSummary},
     },
-    errors::GameError,
+    room_code,
 };
 
+use super::{config::RoomConfig, consts::MAX_PLAYERS, errors::GameError};
+
 #[derive(Deserialize, Serialize, Copy, Clone, PartialEq)]
 pub enum Power {
     PeekOwnCard,
@@ -40,21 +53,122 @@ pub enum DuplicateCardResult {
 
 #[derive(Deserialize, Serialize, Clone, PartialEq)]
 pub struct Score {
-    player_id: PlayerId,
-    cards: Vec<Card>,
-    total_score: i8,
+    pub player_id: PlayerId,
+    pub cards: Vec<Card>,
+    /// Summed as `i32`, long hands of high cards would not fit in a card score.
+    pub total_score: i32,
+    pub forfeited: bool,
+}
+/// Cumulative score of a player over the rounds of a match.
+#[derive(Deserialize, Serialize, Clone)]
+pub struct Standing {
+    pub player_id: PlayerId,
+    pub total_score: i32,
+    pub eliminated: bool,
 }
+
 #[derive(Deserialize, Serialize, Clone)]
 pub struct FinalScore {
+    /// Id under which the game record is kept. All the rounds of a match
+    /// share it, the record covers the whole match.
+    pub game_id: GameId,
     pub winner: PlayerId,
     pub scores: Vec<Score>,
+    /// Seed the room was played with, enough to replay the same deal.
+    pub seed: u64,
+    /// Rating moves of the players who joined with a profile.
+    pub rating_changes: Vec<RatingChange>,
 }
 
 pub struct Player {
     name: PlayerName,
+    identity: Option<Identity>,
+    /// Bot seats are prompted with their legal actions and play on a shorter clock.
+    bot: bool,
     tx: UnboundedSender<RoomEvent>,
     cards: Vec<Card>,
+    /// For each card in hand, the players who have seen it.
+    known_by: Vec<HashSet<PlayerId>>,
     ready: bool,
+    reconnect_token: ReconnectToken,
+    /// Holds the player's channel while they are disconnected.
+    parked_channel: Option<UnboundedReceiver<RoomEvent>>,
+    reconnect_countdown: Option<JoinHandle<()>>,
+}
+
+impl Player {
+    /// Cards put in a hand without going through these helpers are unknown to everybody.
+    fn sync_known_by(&mut self) {
+        self.known_by.resize_with(self.cards.len(), HashSet::new);
+    }
+
+    fn push_card(&mut self, card: Card, known_by: HashSet<PlayerId>) {
+        self.sync_known_by();
+        self.cards.push(card);
+        self.known_by.push(known_by);
+    }
+
+    fn insert_card(&mut self, idx: usize, card: Card, known_by: HashSet<PlayerId>) {
+        self.sync_known_by();
+        self.cards.insert(idx, card);
+        self.known_by.insert(idx, known_by);
+    }
+
+    fn remove_card(&mut self, idx: usize) -> (Card, HashSet<PlayerId>) {
+        self.sync_known_by();
+        (self.cards.remove(idx), self.known_by.remove(idx))
+    }
+
+    fn replace_card(
+        &mut self,
+        idx: usize,
+        card: Card,
+        known_by: HashSet<PlayerId>,
+    ) -> (Card, HashSet<PlayerId>) {
+        self.sync_known_by();
+        (
+            mem::replace(&mut self.cards[idx], card),
+            mem::replace(&mut self.known_by[idx], known_by),
+        )
+    }
+
+    fn reveal_card(&mut self, idx: usize, viewer: PlayerId) {
+        self.sync_known_by();
+        self.known_by[idx].insert(viewer);
+    }
+
+    fn visible_cards(&self, viewer: Option<PlayerId>) -> Vec<Option<Card>> {
+        self.cards
+            .iter()
+            .enumerate()
+            .map(|(idx, card)| {
+                viewer
+                    .filter(|viewer| {
+                        self.known_by
+                            .get(idx)
+                            .is_some_and(|known_by| known_by.contains(viewer))
+                    })
+                    .map(|_| *card)
+            })
+            .collect()
+    }
+}
+
+impl From<&State> for Phase {
+    fn from(state: &State) -> Self {
+        match state {
+            State::NotStarted => Phase::NotStarted,
+            State::PeekingPhase => Phase::PeekingPhase,
+            State::StartTurn(player_id) => Phase::StartTurn(*player_id),
+            State::MiddleTurn(player_id, _) => Phase::MiddleTurn(*player_id),
+            State::PowerStage(player_id, power) => Phase::PowerStage(*player_id, *power),
+            State::PauseForDuplicateCardThrow(player_id, other_player_id, other_card_idx, _) => {
+                Phase::PauseForDuplicateCardThrow(*player_id, *other_player_id, *other_card_idx)
+            }
+            State::Terminating => Phase::Terminating,
+            State::Terminated => Phase::Terminated,
+        }
+    }
 }
 
 #[derive(PartialEq, Clone)]
@@ -70,34 +184,91 @@ pub enum State {
 }
 pub struct RoomServer {
     id: RoomId,
+    config: RoomConfig,
     tx_channel: UnboundedSender<RoomCommand>,
     rx_channel: UnboundedReceiver<RoomCommand>,
-    players: HashMap<PlayerId, Player>,
+    players: BTreeMap<PlayerId, Player>,
+    /// First player to join, the only one allowed to start the game.
+    host: Option<PlayerId>,
+    spectators: HashMap<SpectatorId, UnboundedSender<RoomEvent>>,
     deck: Deck,
     state: State,
     duplicate_card_thrown: bool,
     current_player_idx: usize,
-    turn_order: HashMap<usize, PlayerId>,
+    turn_order: Vec<PlayerId>,
     crabul_player: Option<PlayerId>,
     current_count_down: Option<JoinHandle<()>>,
+    empty_room_countdown: Option<JoinHandle<()>>,
+    countdown_deadline: Option<Instant>,
+    /// Set when re-running a record, whose commands already hold every expiry.
+    replaying: bool,
+    forfeited_scores: Vec<Score>,
+    seed: u64,
+    rng: StdRng,
+    game_id: GameId,
+    created_at: Instant,
+    started_at: u64,
+    record_tx: UnboundedSender<RecordEntry>,
+    record_rx: UnboundedReceiver<RecordEntry>,
+    notification_tx: Option<UnboundedSender<RoomNotification>>,
+    round: u32,
+    standings: BTreeMap<PlayerId, Standing>,
+    /// Round in which each player went over the score limit.
+    eliminated_in: BTreeMap<PlayerId, u32>,
+    /// Ratings of the players who joined with a profile, kept after they leave.
+    ratings: BTreeMap<PlayerId, Rating>,
 }
 
 impl RoomServer {
-    pub fn new() -> (Self, RoomCommander) {
+    /// The id comes from the room rng, a seeded room always gets the same one.
+    pub fn new(config: RoomConfig) -> (Self, RoomCommander) {
+        let (mut room_server, room_commander) = Self::with_id(0, config);
+        room_server.id = room_server.rng.gen::<RoomId>();
+        (room_server, room_commander)
+    }
+
+    /// Room under an id picked by the caller, who makes sure it is free.
+    pub fn with_id(id: RoomId, config: RoomConfig) -> (Self, RoomCommander) {
         let (tx_channel, rx_channel) = mpsc::unbounded_channel();
+        let seed = config.seed.unwrap_or_else(|| thread_rng().gen());
+        let mut rng = StdRng::seed_from_u64(seed);
+        let (record_tx, record_rx) = mpsc::unbounded_channel();
+        let started_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
 
         let room_server = Self {
-            id: thread_rng().gen::<RoomId>(),
+            id,
+            config,
             tx_channel: tx_channel.clone(),
             rx_channel,
-            players: HashMap::with_capacity(6),
-            deck: Deck::new(),
+            players: BTreeMap::new(),
+            host: None,
+            spectators: HashMap::new(),
+            deck: Deck::new(&mut rng),
             state: State::NotStarted,
             duplicate_card_thrown: false,
             current_player_idx: 0,
-            turn_order: HashMap::with_capacity(6),
+            turn_order: Vec::with_capacity(MAX_PLAYERS),
             crabul_player: None,
             current_count_down: None,
+            empty_room_countdown: None,
+            countdown_deadline: None,
+            replaying: false,
+            forfeited_scores: vec![],
+            seed,
+            rng,
+            game_id: thread_rng().gen::<GameId>(),
+            created_at: Instant::now(),
+            started_at,
+            record_tx,
+            record_rx,
+            notification_tx: None,
+            round: 0,
+            standings: BTreeMap::new(),
+            eliminated_in: BTreeMap::new(),
+            ratings: BTreeMap::new(),
         };
 
         (room_server, RoomCommander::new(tx_channel))
@@ -107,977 +278,2867 @@ impl RoomServer {
         self.id
     }
 
+    /// Channel on which the room reports finished games.
+    pub fn notifications(&mut self) -> UnboundedReceiver<RoomNotification> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.notification_tx = Some(tx);
+        rx
+    }
+
     pub async fn run(mut self) {
+        if self.players.is_empty() {
+            self.empty_room_countdown = Some(self.start_timer(Self::empty_room_countdown(
+                self.config.empty_room_timeout,
+                self.tx_channel.clone(),
+            )));
+        }
         while self.process_next_command().await.is_some() {}
     }
 
-    async fn process_next_command(&mut self) -> Option<()> {
-        if let Some(cmd) = self.rx_channel.recv().await {
+    /// Re-runs the commands of a record on a fresh room and returns the record
+    /// of the new run. No countdown is started: their expiry is part of the
+    /// recorded commands. Async as the room still spawns its timer handles on
+    /// the runtime.
+    pub async fn replay(record: &GameRecord) -> GameRecord {
+        let config = RoomConfig {
+            seed: Some(record.seed),
+            ..record.config.clone()
+        };
+        // Rooms made by the server do not draw their id, a replay must not either.
+        let (mut room_server, _) = RoomServer::with_id(record.room_id, config);
+        room_server.game_id = record.game_id;
+        room_server.replaying = true;
+
+        let mut player_channels = HashMap::new();
+        for cmd in record.commands().cloned() {
             match cmd {
-                RoomCommand::AddPlayer { name, cmd_tx } => {
-                    let res = self.new_player(name);
-                    let _ = cmd_tx.send(res);
-                }
-                RoomCommand::RemovePlayer { player_id, cmd_tx } => {
-                    self.remove_player(player_id);
-                    let _ = cmd_tx.send(());
-                }
-                RoomCommand::StartGame { cmd_tx } => {
-                    let res = self.start_game();
-                    let _ = cmd_tx.send(res);
-                }
-                RoomCommand::SetPlayerReady { player_id, cmd_tx } => {
-                    let res = self.set_player_ready(player_id);
-                    let _ = cmd_tx.send(res);
-                }
-                RoomCommand::NextTurn => self.next_turn(),
-                RoomCommand::GoCrabul { player_id, cmd_tx } => {
-                    let res = self.go_crabul(player_id);
-                    let _ = cmd_tx.send(res);
-                }
-                RoomCommand::DrawCard { player_id, cmd_tx } => {
-                    let res = self.draw_card(player_id);
-                    let _ = cmd_tx.send(res);
-                }
-                RoomCommand::SwapCard {
-                    player_id,
-                    card_idx,
-                    cmd_tx,
-                } => {
-                    let res = self.swap_card(player_id, card_idx);
-                    let _ = cmd_tx.send(res);
-                }
-                RoomCommand::DiscardCard { player_id, cmd_tx } => {
-                    let res = self.discard_card(player_id);
-                    let _ = cmd_tx.send(res);
-                }
-                RoomCommand::PeekOwnCard {
-                    player_id,
-                    card_idx,
-                    cmd_tx,
-                } => {
-                    let res = self.peek_own_card(player_id, card_idx);
-                    let _ = cmd_tx.send(res);
-                }
-                RoomCommand::PeekOtherCard {
-                    player_id,
-                    other_player_id,
-                    other_card_idx,
-                    cmd_tx,
-                } => {
-                    let res = self.peek_other_card(player_id, other_player_id, other_card_idx);
-                    let _ = cmd_tx.send(res);
-                }
-                RoomCommand::BlindSwap {
-                    player_id,
-                    card_idx,
-                    other_player_id,
-                    other_card_idx,
-                    cmd_tx,
-                } => {
-                    let res = self.blind_swap(player_id, card_idx, other_player_id, other_card_idx);
-                    let _ = cmd_tx.send(res);
-                }
-                RoomCommand::CheckAndSwapStage1 {
-                    player_id,
-                    other_player_id,
-                    other_card_idx,
-                    cmd_tx,
-                } => {
-                    let res =
-                        self.check_and_swap_stage1(player_id, other_player_id, other_card_idx);
-                    let _ = cmd_tx.send(res);
-                }
-                RoomCommand::CheckAndSwapStage2 {
-                    player_id,
-                    card_idx,
-                    cmd_tx,
-                } => {
-                    let res = self.check_and_swap_stage2(player_id, card_idx);
-                    let _ = cmd_tx.send(res);
+                RecordedCommand::AddPlayer(name, profile, bot) => {
+                    let (cmd_tx, mut cmd_rx) = oneshot::channel();
+                    room_server.handle_command(RoomCommand::AddPlayer {
+                        name,
+                        profile,
+                        bot,
+                        cmd_tx,
+                    });
+                    if let Ok(Ok((player_id, player_channel))) = cmd_rx.try_recv() {
+                        player_channels.insert(player_id, player_channel);
+                    }
                 }
-                RoomCommand::ThrowSameCard {
-                    player_id,
-                    picked_player_id,
-                    picked_card_idx,
-                    cmd_tx,
-                } => {
-                    let res =
-                        self.throw_duplicate_card(player_id, picked_player_id, picked_card_idx);
-                    let _ = cmd_tx.send(res);
+                RecordedCommand::AddBot(player_id, name) => {
+                    let (cmd_tx, mut cmd_rx) = oneshot::channel();
+                    room_server.handle_command(RoomCommand::AddBot {
+                        player_id,
+                        name,
+                        cmd_tx,
+                    });
+                    if let Ok(Ok((player_id, player_channel))) = cmd_rx.try_recv() {
+                        player_channels.insert(player_id, player_channel);
+                    }
                 }
-                RoomCommand::SelectCardToGiveAway {
-                    player_id,
-                    card_idx,
-                    cmd_tx,
-                } => {
-                    let res = self.select_card_to_give_away(player_id, card_idx);
-                    let _ = cmd_tx.send(res);
+                RecordedCommand::DisconnectPlayer(player_id) => {
+                    let player_channel = player_channels
+                        .remove(&player_id)
+                        .unwrap_or_else(|| mpsc::unbounded_channel().1);
+                    room_server.handle_command(RoomCommand::DisconnectPlayer {
+                        player_id,
+                        player_channel,
+                        cmd_tx: oneshot::channel().0,
+                    });
                 }
-                RoomCommand::StopRoomServer => return None,
-                RoomCommand::ForceEndTurn(player_id) => {
-                    self.force_end_turn(player_id);
+                RecordedCommand::ResumePlayer(player_id) => {
+                    let Some(player) = room_server.players.get(&player_id) else {
+                        continue;
+                    };
+                    let (cmd_tx, mut cmd_rx) = oneshot::channel();
+                    room_server.handle_command(RoomCommand::ResumePlayer {
+                        reconnect_token: player.reconnect_token.clone(),
+                        cmd_tx,
+                    });
+                    if let Ok(Ok((player_id, player_channel))) = cmd_rx.try_recv() {
+                        player_channels.insert(player_id, player_channel);
+                    }
                 }
-                RoomCommand::FinalizeGame => {
-                    self.finalize_game();
+                cmd => {
+                    if let Some(cmd) = cmd.into_command() {
+                        room_server.handle_command(cmd);
+                    }
                 }
             }
-            return Some(());
         }
-        None
+        room_server.take_record()
     }
 
-    fn start_game(&mut self) -> Result<(), GameError> {
-        if self.state != State::NotStarted {
-            return Err(GameError::OperationNotAllowedAtCurrentState);
-        }
+    async fn process_next_command(&mut self) -> Option<()> {
+        let cmd = self.rx_channel.recv().await?;
+        self.handle_command(cmd)
+    }
 
-        if self.players.len() < MIN_PLAYERS {
-            return Err(GameError::NotEnoughPlayers);
+    fn handle_command(&mut self, cmd: RoomCommand) -> Option<()> {
+        let previous_state = self.state.clone();
+        let previous_basis = self.actions_basis();
+        let recorded = RecordedCommand::from_command(&cmd);
+        if let Some(recorded) = recorded.clone() {
+            self.record(RecordKind::Command(recorded));
         }
-
-        for (i, (&player_id, _)) in self.players.iter().enumerate() {
-            self.turn_order.insert(i, player_id);
+        match cmd {
+            RoomCommand::AddPlayer {
+                name,
+                profile,
+                bot,
+                cmd_tx,
+            } => {
+                let res = self.new_player(name, profile, bot);
+                let _ = cmd_tx.send(res);
+            }
+            RoomCommand::AddBot {
+                player_id,
+                name,
+                cmd_tx,
+            } => {
+                let res = self
+                    .validate_host(player_id)
+                    .and_then(|_| self.new_player(name, None, true));
+                let _ = cmd_tx.send(res);
+            }
+            RoomCommand::RemovePlayer { player_id, cmd_tx } => {
+                self.remove_player(player_id);
+                let _ = cmd_tx.send(());
+            }
+            RoomCommand::DisconnectPlayer {
+                player_id,
+                player_channel,
+                cmd_tx,
+            } => {
+                self.disconnect_player(player_id, player_channel);
+                let _ = cmd_tx.send(());
+            }
+            RoomCommand::ResumePlayer {
+                reconnect_token,
+                cmd_tx,
+            } => {
+                let res = self.resume_player(reconnect_token);
+                let _ = cmd_tx.send(res);
+            }
+            RoomCommand::ReconnectTimeout(player_id) => {
+                self.reconnect_timeout(player_id);
+            }
+            RoomCommand::GetState { player_id, cmd_tx } => {
+                let res = self.get_state(player_id);
+                let _ = cmd_tx.send(res);
+            }
+            RoomCommand::GetLegalActions { player_id, cmd_tx } => {
+                let res = self.legal_actions(player_id);
+                let _ = cmd_tx.send(res);
+            }
+            RoomCommand::GetSummary { cmd_tx } => {
+                let _ = cmd_tx.send(self.summary());
+            }
+            RoomCommand::AddSpectator { cmd_tx } => {
+                let res = self.new_spectator();
+                let _ = cmd_tx.send(res);
+            }
+            RoomCommand::RemoveSpectator { spectator_id } => {
+                self.spectators.remove(&spectator_id);
+            }
+            RoomCommand::StartGame { player_id, cmd_tx } => {
+                let res = self.start_game(player_id);
+                let _ = cmd_tx.send(res);
+            }
+            RoomCommand::SetLobbyReady {
+                player_id,
+                ready,
+                cmd_tx,
+            } => {
+                let res = self.set_lobby_ready(player_id, ready);
+                let _ = cmd_tx.send(res);
+            }
+            RoomCommand::AutoStartGame => self.auto_start_game(),
+            RoomCommand::KickPlayer {
+                player_id,
+                kicked_player_id,
+                cmd_tx,
+            } => {
+                let res = self.kick_player(player_id, kicked_player_id);
+                let _ = cmd_tx.send(res);
+            }
+            RoomCommand::TransferHost {
+                player_id,
+                new_host_id,
+                cmd_tx,
+            } => {
+                let res = self.transfer_host(player_id, new_host_id);
+                let _ = cmd_tx.send(res);
+            }
+            RoomCommand::SetPlayerReady { player_id, cmd_tx } => {
+                let res = self.set_player_ready(player_id);
+                let _ = cmd_tx.send(res);
+            }
+            RoomCommand::NextTurn => self.next_turn(),
+            RoomCommand::GoCrabul { player_id, cmd_tx } => {
+                let res = self.go_crabul(player_id);
+                let _ = cmd_tx.send(res);
+            }
+            RoomCommand::DrawCard { player_id, cmd_tx } => {
+                let res = self.draw_card(player_id);
+                let _ = cmd_tx.send(res);
+            }
+            RoomCommand::SwapCard {
+                player_id,
+                card_idx,
+                cmd_tx,
+            } => {
+                let res = self.swap_card(player_id, card_idx);
+                let _ = cmd_tx.send(res);
+            }
+            RoomCommand::DiscardCard { player_id, cmd_tx } => {
+                let res = self.discard_card(player_id);
+                let _ = cmd_tx.send(res);
+            }
+            RoomCommand::PeekOwnCard {
+                player_id,
+                card_idx,
+                cmd_tx,
+            } => {
+                let res = self.peek_own_card(player_id, card_idx);
+                let _ = cmd_tx.send(res);
+            }
+            RoomCommand::PeekOtherCard {
+                player_id,
+                other_player_id,
+                other_card_idx,
+                cmd_tx,
+            } => {
+                let res = self.peek_other_card(player_id, other_player_id, other_card_idx);
+                let _ = cmd_tx.send(res);
+            }
+            RoomCommand::BlindSwap {
+                player_id,
+                card_idx,
+                other_player_id,
+                other_card_idx,
+                cmd_tx,
+            } => {
+                let res = self.blind_swap(player_id, card_idx, other_player_id, other_card_idx);
+                let _ = cmd_tx.send(res);
+            }
+            RoomCommand::CheckAndSwapStage1 {
+                player_id,
+                other_player_id,
+                other_card_idx,
+                cmd_tx,
+            } => {
+                let res = self.check_and_swap_stage1(player_id, other_player_id, other_card_idx);
+                let _ = cmd_tx.send(res);
+            }
+            RoomCommand::CheckAndSwapStage2 {
+                player_id,
+                card_idx,
+                cmd_tx,
+            } => {
+                let res = self.check_and_swap_stage2(player_id, card_idx);
+                let _ = cmd_tx.send(res);
+            }
+            RoomCommand::ThrowSameCard {
+                player_id,
+                picked_player_id,
+                picked_card_idx,
+                cmd_tx,
+            } => {
+                let res = self.throw_duplicate_card(player_id, picked_player_id, picked_card_idx);
+                let _ = cmd_tx.send(res);
+            }
+            RoomCommand::SelectCardToGiveAway {
+                player_id,
+                card_idx,
+                cmd_tx,
+            } => {
+                let res = self.select_card_to_give_away(player_id, card_idx);
+                let _ = cmd_tx.send(res);
+            }
+            RoomCommand::StopRoomServer => return None,
+            RoomCommand::EmptyRoomTimeout => {
+                if self.players.is_empty() {
+                    return None;
+                }
+            }
+            RoomCommand::CloseRoom => {
+                self.send_all_players(RoomEvent::RoomClosed);
+                return None;
+            }
+            RoomCommand::ForceEndTurn(player_id) => {
+                self.force_end_turn(player_id);
+            }
+            RoomCommand::FinalizeGame => {
+                self.finalize_game();
+            }
+            RoomCommand::StartNextRound => {
+                self.start_next_round();
+            }
         }
+        if self.state != previous_state || self.actions_basis() != previous_basis {
+            self.send_legal_actions();
+            self.prompt_bots();
+        }
+        if recorded.is_some() && !matches!(self.state, State::NotStarted | State::Terminated) {
+            self.record_hands();
+        }
+        Some(())
+    }
 
-        self.state = State::PeekingPhase;
-
-        self.deal_cards_and_peek();
+    fn record(&self, kind: RecordKind) {
+        let _ = self.record_tx.send(RecordEntry {
+            elapsed_ms: self.created_at.elapsed().as_millis() as u64,
+            kind,
+        });
+    }
 
-        Ok(())
+    fn record_hands(&self) {
+        let hands = self
+            .players
+            .iter()
+            .map(|(id, player)| (*id, player.cards.clone()))
+            .collect();
+        self.record(RecordKind::Hands(hands));
     }
 
-    fn deal_cards_and_peek(&mut self) {
-        self.players.iter_mut().for_each(|(_, player)| {
-            for _ in 0..4 {
-                player.cards.push(self.deck.draw());
-            }
-            let _ = player.tx.send(RoomEvent::PeekingPhaseStarted((
-                player.cards[0],
-                player.cards[1],
-            )));
-        });
-        spawn(Self::peeking_phase_countdown(self.tx_channel.clone()));
+    fn take_record(&mut self) -> GameRecord {
+        let mut entries = vec![];
+        while let Ok(entry) = self.record_rx.try_recv() {
+            entries.push(entry);
+        }
+        GameRecord {
+            game_id: self.game_id,
+            room_id: self.id,
+            config: self.config.clone(),
+            seed: self.seed,
+            started_at: self.started_at,
+            entries,
+        }
     }
 
-    fn new_player(
-        &mut self,
-        name: PlayerName,
-    ) -> Result<(PlayerId, UnboundedReceiver<RoomEvent>), GameError> {
+    fn start_game(&mut self, player_id: PlayerId) -> Result<(), GameError> {
         if self.state != State::NotStarted {
             return Err(GameError::OperationNotAllowedAtCurrentState);
         }
 
-        if self.players.len() >= MAX_PLAYERS {
-            return Err(GameError::TooManyPlayers);
-        }
+        self.validate_host(player_id)?;
 
-        if self.players.iter().any(|(_, player)| player.name == name) {
-            return Err(GameError::NameAlreadyExists);
+        if self.players.len() < self.config.min_players {
+            return Err(GameError::NotEnoughPlayers);
         }
 
-        if name.is_empty() {
-            return Err(GameError::EmptyName);
-        }
+        self.begin_game();
+        Ok(())
+    }
 
-        let (tx_channel, rx_channel) = mpsc::unbounded_channel();
-        let player_id = thread_rng().gen::<PlayerId>();
+    fn begin_game(&mut self) {
+        if let Some(count_down) = self.current_count_down.take() {
+            count_down.abort();
+        }
+        self.countdown_deadline = None;
+        // Lobby readiness is spent, the peeking phase asks for it again.
+        for player in self.players.values_mut() {
+            player.ready = false;
+        }
+        self.turn_order = self.players.keys().copied().collect();
+        self.round = 1;
 
-        self.players.insert(
-            player_id,
-            Player {
-                name: name.clone(),
-                tx: tx_channel,
-                cards: vec![],
-                ready: false,
-            },
-        );
+        self.state = State::PeekingPhase;
 
-        let event = RoomEvent::PlayerJoined {
-            room_id: self.id,
-            player_id,
-            player_name: name,
-            player_list: self
-                .players
-                .iter()
-                .map(|(id, player)| (*id, player.name.clone()))
-                .collect(),
-        };
+        self.deal_cards_and_peek();
+    }
 
+    fn set_lobby_ready(&mut self, player_id: PlayerId, ready: bool) -> Result<(), GameError> {
+        if self.state != State::NotStarted {
+            return Err(GameError::OperationNotAllowedAtCurrentState);
+        }
+        let player = self
+            .players
+            .get_mut(&player_id)
+            .ok_or(GameError::PlayerNotFound)?;
+        player.ready = ready;
+        let event = RoomEvent::LobbyReadyChanged(player_id, ready);
         self.send_all_players(event);
-
-        Ok((player_id, rx_channel))
+        self.update_auto_start();
+        Ok(())
     }
 
-    fn remove_player(&mut self, id: PlayerId) {
-        self.players.remove(&id);
-        if self.players.is_empty() {
-            let _ = self.tx_channel.send(RoomCommand::StopRoomServer);
+    /// Schedules the game start once enough players are all ready in the lobby,
+    /// and calls it off as soon as that is no longer true.
+    fn update_auto_start(&mut self) {
+        if self.state != State::NotStarted {
             return;
         }
-        let event = RoomEvent::PlayerLeft(id);
-        self.send_all_players(event);
+        let all_ready = self.players.len() >= self.config.min_players
+            && self.players.values().all(|player| player.ready);
+        match (all_ready, self.current_count_down.take()) {
+            (true, None) => {
+                self.set_countdown_deadline(self.config.auto_start_countdown);
+                self.current_count_down = Some(self.start_timer(Self::auto_start_countdown(
+                    self.config.auto_start_countdown,
+                    self.tx_channel.clone(),
+                )));
+                let event =
+                    RoomEvent::AutoStartScheduled(self.config.auto_start_countdown.as_secs());
+                self.send_all_players(event);
+            }
+            (false, Some(count_down)) => {
+                count_down.abort();
+                self.countdown_deadline = None;
+                let event = RoomEvent::AutoStartCancelled;
+                self.send_all_players(event);
+            }
+            (_, count_down) => self.current_count_down = count_down,
+        }
     }
 
-    fn set_player_ready(&mut self, id: PlayerId) -> Result<(), GameError> {
-        if self.state != State::PeekingPhase {
-            return Err(GameError::OperationNotAllowedAtCurrentState);
+    fn auto_start_game(&mut self) {
+        if self.state != State::NotStarted || self.current_count_down.take().is_none() {
+            return;
         }
+        self.begin_game();
+    }
 
-        let player = self.players.get_mut(&id).unwrap();
-        player.ready = true;
-        let event = RoomEvent::PlayerIsReady(id);
-        self.send_all_players(event);
-        if self.players.iter().all(|(_, player)| player.ready) {
-            let _ = self.tx_channel.send(RoomCommand::NextTurn);
+    fn validate_host(&self, player_id: PlayerId) -> Result<(), GameError> {
+        if self.host != Some(player_id) {
+            return Err(GameError::NotHost);
         }
         Ok(())
     }
 
-    fn go_crabul(&mut self, player_id: PlayerId) -> Result<(), GameError> {
-        if self.state != State::StartTurn(player_id) || self.crabul_player.is_some() {
+    /// The host can send players away while the room is still in the lobby.
+    fn kick_player(
+        &mut self,
+        player_id: PlayerId,
+        kicked_player_id: PlayerId,
+    ) -> Result<(), GameError> {
+        self.validate_host(player_id)?;
+        if self.state != State::NotStarted || kicked_player_id == player_id {
             return Err(GameError::OperationNotAllowedAtCurrentState);
         }
-        self.crabul_player = Some(player_id);
-        let event = RoomEvent::PlayerWentCrabul(player_id);
+        if !self.players.contains_key(&kicked_player_id) {
+            return Err(GameError::PlayerNotFound);
+        }
+
+        let event = RoomEvent::PlayerKicked(kicked_player_id);
         self.send_all_players(event);
-        self.next_turn();
+        self.players.remove(&kicked_player_id);
+        self.update_auto_start();
         Ok(())
     }
 
-    fn next_turn(&mut self) {
-        if let Some(current_count_down) = self.current_count_down.take() {
-            current_count_down.abort();
-        }
-        self.duplicate_card_thrown = false;
-        self.current_player_idx += 1;
-        self.current_player_idx %= self.players.len();
-        let current_player_id = self.turn_order[&self.current_player_idx];
-
-        if let Some(crabul_player) = self.crabul_player {
-            if current_player_id == crabul_player {
-                spawn(Self::finalize_game_countdown(self.tx_channel.clone()));
-                self.state = State::Terminating;
-                return;
-            }
+    fn transfer_host(
+        &mut self,
+        player_id: PlayerId,
+        new_host_id: PlayerId,
+    ) -> Result<(), GameError> {
+        self.validate_host(player_id)?;
+        let Some(new_host) = self.players.get(&new_host_id) else {
+            return Err(GameError::PlayerNotFound);
+        };
+        if new_host.bot {
+            return Err(GameError::OperationNotAllowedAtCurrentState);
         }
 
-        self.state = State::StartTurn(current_player_id);
-
-        let event = RoomEvent::PlayerTurn(current_player_id);
+        self.host = Some(new_host_id);
+        let event = RoomEvent::HostChanged(new_host_id);
         self.send_all_players(event);
+        Ok(())
+    }
 
-        let count_down = spawn(Self::turn_countdown(
-            current_player_id,
-            self.tx_channel.clone(),
-        ));
-
-        self.current_count_down = Some(count_down);
+    /// Hands the host role over to another player when the host is gone.
+    fn migrate_host(&mut self) {
+        if self
+            .host
+            .is_none_or(|host| self.players.contains_key(&host))
+        {
+            return;
+        }
+        // Bots cannot host, they would never start the game.
+        self.host = self
+            .players
+            .iter()
+            .find(|(_, player)| !player.bot)
+            .map(|(&player_id, _)| player_id);
+        if let Some(host) = self.host {
+            let event = RoomEvent::HostChanged(host);
+            self.send_all_players(event);
+        }
     }
 
-    fn finalize_game(&mut self) {
-        let scores = self.players.iter().map(|(player_id, player)| Score {
-            player_id: *player_id,
-            cards: player.cards.clone(),
-            total_score: player.cards.iter().map(|card| card.get_score()).sum(),
-        });
-        self.state = State::Terminated;
-
-        let mut sorted_scores: Vec<Score> = scores.collect();
-        sorted_scores.sort_by(|a, b| a.total_score.cmp(&b.total_score));
-        let (winner1, winner2) = (sorted_scores[0].clone(), sorted_scores[1].clone());
-        let mut final_winner = winner1.clone();
-
-        if winner1 == winner2 && winner1.player_id == self.crabul_player.unwrap() {
-            final_winner = winner2
+    /// Re-deals a fresh deck to the players still in the match. The first
+    /// player moves one seat each round.
+    fn start_next_round(&mut self) {
+        if self.state != State::Terminated {
+            return;
         }
+        self.current_count_down = None;
+        if self.players.len() < self.config.min_players {
+            self.end_match();
+            return;
+        }
+        self.round += 1;
+        self.deck = Deck::new(&mut self.rng);
+        for player in self.players.values_mut() {
+            player.cards.clear();
+            player.known_by.clear();
+            player.ready = false;
+        }
+        self.crabul_player = None;
+        self.duplicate_card_thrown = false;
+        self.turn_order = self.players.keys().copied().collect();
+        self.current_player_idx = (self.round as usize - 1) % self.turn_order.len();
 
-        let event = RoomEvent::GameTerminated(FinalScore {
-            winner: final_winner.player_id,
-            scores: sorted_scores,
-        });
+        self.state = State::PeekingPhase;
+        let event = RoomEvent::RoundStarted(self.round);
         self.send_all_players(event);
-        let _ = self.tx_channel.send(RoomCommand::StopRoomServer);
+
+        self.deal_cards_and_peek();
     }
 
-    fn draw_card(&mut self, player_id: PlayerId) -> Result<(), GameError> {
-        if self.state != State::StartTurn(player_id) {
-            return Err(GameError::OperationNotAllowedAtCurrentState);
+    fn end_round(&mut self, round_scores: Vec<(PlayerId, i32)>, score_limit: i32) {
+        for (player_id, score) in round_scores {
+            self.standings
+                .entry(player_id)
+                .or_insert(Standing {
+                    player_id,
+                    total_score: 0,
+                    eliminated: false,
+                })
+                .total_score += score;
+        }
+        for standing in self.standings.values_mut() {
+            if !self.players.contains_key(&standing.player_id) {
+                standing.eliminated = true;
+            }
+        }
+        let eliminated: Vec<PlayerId> = self
+            .standings
+            .values()
+            .filter(|standing| !standing.eliminated && standing.total_score > score_limit)
+            .map(|standing| standing.player_id)
+            .collect();
+        for player_id in eliminated {
+            self.eliminate_player(player_id);
         }
-        let card = self.deck.draw();
-        self.state = State::MiddleTurn(player_id, card);
 
-        let event = RoomEvent::CardWasDrawn(player_id);
+        let event = RoomEvent::RoundEnded {
+            round: self.round,
+            standings: self.sorted_standings(),
+        };
         self.send_all_players(event);
 
-        let event = RoomEvent::DrawnCard(card);
-        self.send_to_player(player_id, event);
-
-        Ok(())
+        if self.players.len() < self.config.min_players {
+            self.end_match();
+            return;
+        }
+        self.set_countdown_deadline(self.config.next_round_countdown);
+        self.current_count_down = Some(self.start_timer(Self::next_round_countdown(
+            self.config.next_round_countdown,
+            self.tx_channel.clone(),
+        )));
     }
 
-    fn swap_card(&mut self, player_id: PlayerId, card_idx: usize) -> Result<(), GameError> {
-        if let State::MiddleTurn(stored_player_id, mut card) = self.state {
-            self.validate_player_turn(player_id, stored_player_id)?;
-            self.validate_idx_card(player_id, card_idx)?;
+    /// Eliminated players keep watching the match as spectators.
+    fn eliminate_player(&mut self, player_id: PlayerId) {
+        if let Some(standing) = self.standings.get_mut(&player_id) {
+            standing.eliminated = true;
+        }
+        self.eliminated_in.insert(player_id, self.round);
+        let event = RoomEvent::PlayerEliminated(player_id);
+        self.send_all_players(event);
 
-            let player = self.players.get_mut(&player_id).unwrap();
-            mem::swap(&mut card, &mut player.cards[card_idx]);
-            self.deck.discard(card);
-            let event = RoomEvent::CardSwapped(player_id, card_idx);
-            self.send_all_players(event);
-            let event = RoomEvent::CardDiscarded(player_id, card);
-            self.send_all_players(event);
-            self.next_turn();
-            return Ok(());
+        let Some(player) = self.players.remove(&player_id) else {
+            return;
+        };
+        if let Some(reconnect_countdown) = player.reconnect_countdown {
+            reconnect_countdown.abort();
         }
-        Err(GameError::OperationNotAllowedAtCurrentState)
+        if player.parked_channel.is_none() {
+            let spectator_id = self.free_spectator_id();
+            self.spectators.insert(spectator_id, player.tx);
+        }
+        self.migrate_host();
     }
 
-    fn discard_card(&mut self, player_id: PlayerId) -> Result<(), GameError> {
-        if let State::MiddleTurn(stored_player_id, card) = self.state {
-            self.validate_player_turn(player_id, stored_player_id)?;
-
-            self.deck.discard(card);
-            let event = RoomEvent::CardDiscarded(player_id, card);
-            self.send_all_players(event);
+    fn end_match(&mut self) {
+        let standings = self.sorted_standings();
+        let Some(winner) = standings.first().map(|standing| standing.player_id) else {
+            self.finish();
+            return;
+        };
+        let event = RoomEvent::MatchEnded { winner, standings };
+        self.send_all_players(event);
+        self.finish();
+    }
 
-            if let Some(power) = self.match_power(card) {
-                if self.crabul_player.is_some() && self.players.len() == 2 {
-                    let event = RoomEvent::PowerDiscarded(player_id, power);
-                    self.send_all_players(event);
-                    self.next_turn();
-                    return Ok(());
-                }
-                let event = RoomEvent::PowerActivated(player_id, power);
-                self.send_all_players(event);
-                self.state = State::PowerStage(player_id, power);
-                return Ok(());
-            }
+    /// Players still in the match first, then the ones who lasted longest,
+    /// then by cumulative score. When everybody goes over the limit in the
+    /// same round, the lowest score of that round wins the match.
+    fn sorted_standings(&self) -> Vec<Standing> {
+        let mut standings: Vec<Standing> = self.standings.values().cloned().collect();
+        standings.sort_by_key(|standing| {
+            let eliminated_in = self.eliminated_in.get(&standing.player_id).copied();
+            (
+                standing.eliminated,
+                Reverse(eliminated_in.unwrap_or(0)),
+                standing.total_score,
+            )
+        });
+        standings
+    }
 
-            self.next_turn();
-            return Ok(());
+    /// Hands the record over and shuts the room down.
+    fn finish(&mut self) {
+        if let Some(notification_tx) = self.notification_tx.clone() {
+            let record = self.take_record();
+            let _ = notification_tx.send(RoomNotification::GameFinished(Box::new(record)));
         }
-        Err(GameError::OperationNotAllowedAtCurrentState)
+        let _ = self.tx_channel.send(RoomCommand::StopRoomServer);
     }
 
-    fn peek_own_card(&mut self, player_id: PlayerId, card_idx: usize) -> Result<(), GameError> {
-        if let State::PowerStage(stored_player_id, Power::PeekOwnCard) = self.state {
-            self.validate_player_turn(player_id, stored_player_id)?;
-
-            let player = self.players.get(&player_id).unwrap();
-            if card_idx >= player.cards.len() {
-                return Err(GameError::InvalidCardIndex);
+    fn deal_cards_and_peek(&mut self) {
+        self.players.iter_mut().for_each(|(&player_id, player)| {
+            for idx in 0..self.config.hand_size {
+                let known_by = if idx < self.config.peeked_cards {
+                    HashSet::from([player_id])
+                } else {
+                    HashSet::new()
+                };
+                player.push_card(self.deck.draw(), known_by);
             }
-            let card = player.cards[card_idx];
-            let event = RoomEvent::PeekedCard(card);
-            self.send_to_player(player_id, event);
-
             let event =
-                RoomEvent::PowerUsed(Power::PeekOwnCard, player_id, Some(card_idx), None, None);
-            self.send_all_players(event);
-            self.next_turn();
-            return Ok(());
-        }
-        Err(GameError::OperationNotAllowedAtCurrentState)
+                RoomEvent::PeekingPhaseStarted(player.cards[..self.config.peeked_cards].to_vec());
+            let _ = self.record_tx.send(RecordEntry {
+                elapsed_ms: self.created_at.elapsed().as_millis() as u64,
+                kind: RecordKind::Event {
+                    to: Some(player_id),
+                    event: event.clone(),
+                },
+            });
+            let _ = player.tx.send(event);
+        });
+        self.set_countdown_deadline(self.config.peeking_phase_countdown);
+        self.start_timer(Self::peeking_phase_countdown(
+            self.config.peeking_phase_countdown,
+            self.tx_channel.clone(),
+        ));
     }
-    fn peek_other_card(
+
+    fn new_player(
         &mut self,
-        player_id: PlayerId,
-        other_player_id: PlayerId,
-        other_card_idx: usize,
-    ) -> Result<(), GameError> {
-        if let State::PowerStage(stored_player_id, Power::PeekOtherCard) = self.state {
-            self.validate_player_turn(player_id, stored_player_id)?;
-            self.validate_crabul_player(other_player_id)?;
+        name: PlayerName,
+        profile: Option<PlayerProfile>,
+        bot: bool,
+    ) -> Result<(PlayerId, UnboundedReceiver<RoomEvent>), GameError> {
+        if self.state != State::NotStarted {
+            return Err(GameError::OperationNotAllowedAtCurrentState);
+        }
 
-            let player = self.players.get(&other_player_id).unwrap();
-            if other_card_idx >= player.cards.len() {
-                return Err(GameError::InvalidCardIndex);
-            }
-            let card = player.cards[other_card_idx];
-            let event = RoomEvent::PeekedCard(card);
-            self.send_to_player(player_id, event);
+        if self.players.len() >= self.config.max_players {
+            return Err(GameError::TooManyPlayers);
+        }
 
-            let event = RoomEvent::PowerUsed(
-                Power::PeekOtherCard,
-                player_id,
-                None,
-                Some(other_player_id),
-                Some(other_card_idx),
-            );
-            self.send_all_players(event);
-            self.next_turn();
-            return Ok(());
+        if self.players.iter().any(|(_, player)| player.name == name) {
+            return Err(GameError::NameAlreadyExists);
         }
-        Err(GameError::OperationNotAllowedAtCurrentState)
-    }
 
-    fn blind_swap(
-        &mut self,
-        player_id: PlayerId,
-        card_idx: usize,
-        other_player_id: PlayerId,
-        other_card_idx: usize,
-    ) -> Result<(), GameError> {
-        if let State::PowerStage(stored_player_id, Power::BlindSwap) = self.state {
-            self.validate_player_turn(player_id, stored_player_id)?;
-            self.validate_crabul_player(other_player_id)?;
+        if name.is_empty() {
+            return Err(GameError::EmptyName);
+        }
 
-            self.swap_players_card(player_id, card_idx, other_player_id, other_card_idx)?;
+        if let Some(empty_room_countdown) = self.empty_room_countdown.take() {
+            empty_room_countdown.abort();
+        }
 
-            let event = RoomEvent::PowerUsed(
-                Power::BlindSwap,
+        let (tx_channel, rx_channel) = mpsc::unbounded_channel();
+        let player_id = self.free_player_id();
+        let reconnect_token = format!("{:016x}", thread_rng().gen::<u64>());
+
+        match profile.as_ref() {
+            Some(profile) => self.ratings.insert(player_id, profile.rating),
+            None => self.ratings.remove(&player_id),
+        };
+        self.players.insert(
+            player_id,
+            Player {
+                name: name.clone(),
+                identity: profile.as_ref().map(|profile| profile.identity.clone()),
+                bot,
+                tx: tx_channel,
+                cards: vec![],
+                known_by: vec![],
+                ready: false,
+                reconnect_token: reconnect_token.clone(),
+                parked_channel: None,
+                reconnect_countdown: None,
+            },
+        );
+
+        let host = *self.host.get_or_insert(player_id);
+
+        let player_list: BTreeMap<PlayerId, PlayerName> = self
+            .players
+            .iter()
+            .map(|(id, player)| (*id, player.name.clone()))
+            .collect();
+
+        for (&id, player) in self.players.iter() {
+            let _ = player.tx.send(RoomEvent::PlayerJoined {
+                room_id: self.id,
+                room_code: room_code::to_code(self.id),
                 player_id,
-                Some(card_idx),
-                Some(other_player_id),
-                Some(other_card_idx),
-            );
-            self.send_all_players(event);
-            self.next_turn();
-            return Ok(());
+                player_name: name.clone(),
+                player_list: player_list.clone(),
+                host,
+                bot,
+                reconnect_token: (id == player_id).then(|| reconnect_token.clone()),
+            });
         }
-        Err(GameError::OperationNotAllowedAtCurrentState)
-    }
+        let event = RoomEvent::PlayerJoined {
+            room_id: self.id,
+            room_code: room_code::to_code(self.id),
+            player_id,
+            player_name: name,
+            player_list,
+            host,
+            bot,
+            reconnect_token: None,
+        };
+        self.record(RecordKind::Event {
+            to: None,
+            event: event.clone(),
+        });
+        self.send_to_spectators(event);
+        self.update_auto_start();
 
-    fn check_and_swap_stage1(
-        &mut self,
-        player_id: PlayerId,
-        other_player_id: PlayerId,
-        other_card_idx: usize,
-    ) -> Result<(), GameError> {
-        if let State::PowerStage(stored_player_id, Power::CheckAndSwapStage1) = self.state {
-            self.validate_player_turn(player_id, stored_player_id)?;
-            self.validate_crabul_player(other_player_id)?;
+        Ok((player_id, rx_channel))
+    }
 
-            let player = self.players.get(&other_player_id).unwrap();
-            if other_card_idx >= player.cards.len() {
-                return Err(GameError::InvalidCardIndex);
-            }
-            let card = player.cards[other_card_idx];
-            let event = RoomEvent::PeekedCard(card);
-            self.send_to_player(player_id, event);
+    /// Spectators do not take a seat, so they can join at any time.
+    fn new_spectator(&mut self) -> (SpectatorId, UnboundedReceiver<RoomEvent>) {
+        let (tx_channel, rx_channel) = mpsc::unbounded_channel();
+        let spectator_id = self.free_spectator_id();
+        let _ = tx_channel.send(RoomEvent::StateSnapshot(self.snapshot(None)));
+        self.spectators.insert(spectator_id, tx_channel);
+        (spectator_id, rx_channel)
+    }
 
-            let event = RoomEvent::PowerUsed(
-                Power::CheckAndSwapStage1,
-                player_id,
-                None,
-                Some(other_player_id),
-                Some(other_card_idx),
-            );
-            self.send_all_players(event);
-            self.state = State::PowerStage(
-                stored_player_id,
-                Power::CheckAndSwapStage2(other_player_id, other_card_idx),
-            );
-            return Ok(());
+    /// Drawn from the room rng so replays seat players under the same ids.
+    fn free_player_id(&mut self) -> PlayerId {
+        let mut player_id = self.rng.gen::<PlayerId>();
+        while self.players.contains_key(&player_id) {
+            player_id = self.rng.gen::<PlayerId>();
         }
-        Err(GameError::OperationNotAllowedAtCurrentState)
+        player_id
     }
 
-    fn check_and_swap_stage2(
-        &mut self,
-        player_id: PlayerId,
-        card_idx: Option<usize>,
-    ) -> Result<(), GameError> {
-        if let State::PowerStage(
-            stored_player_id,
-            Power::CheckAndSwapStage2(other_player_id, other_card_idx),
-        ) = self.state
-        {
-            self.validate_player_turn(player_id, stored_player_id)?;
+    fn free_spectator_id(&self) -> SpectatorId {
+        let mut spectator_id = thread_rng().gen::<SpectatorId>();
+        while self.spectators.contains_key(&spectator_id) {
+            spectator_id = thread_rng().gen::<SpectatorId>();
+        }
+        spectator_id
+    }
 
-            if let Some(card_idx) = card_idx {
-                self.swap_players_card(player_id, card_idx, other_player_id, other_card_idx)?;
+    fn remove_player(&mut self, id: PlayerId) {
+        let Some(player) = self.players.remove(&id) else {
+            return;
+        };
+        if let Some(reconnect_countdown) = player.reconnect_countdown {
+            reconnect_countdown.abort();
+        }
+        // Bots only play along, nobody is left to play with.
+        if self.players.values().all(|player| player.bot) {
+            if !self.players.is_empty() {
+                self.send_all_players(RoomEvent::RoomClosed);
             }
-
-            let event = RoomEvent::PowerUsed(
-                Power::CheckAndSwapStage2(other_player_id, other_card_idx),
-                player_id,
-                card_idx,
-                Some(other_player_id),
-                Some(other_card_idx),
-            );
-            self.send_all_players(event);
-            self.next_turn();
-            return Ok(());
+            let _ = self.tx_channel.send(RoomCommand::StopRoomServer);
+            return;
         }
-        Err(GameError::OperationNotAllowedAtCurrentState)
-    }
-
-    fn throw_duplicate_card(
-        &mut self,
-        player_id: PlayerId,
-        picked_player_id: PlayerId,
-        picked_card_idx: usize,
-    ) -> Result<(), GameError> {
         match self.state {
-            State::NotStarted | State::PeekingPhase | State::Terminated => {
-                Err(GameError::OperationNotAllowedAtCurrentState)
-            }
-            State::StartTurn(_)
-            | State::MiddleTurn(_, _)
-            | State::PowerStage(_, _)
-            | State::PauseForDuplicateCardThrow(_, _, _, _)
-            | State::Terminating => {
-                if self.duplicate_card_thrown {
-                    self.give_penalty(
-                        player_id,
-                        picked_player_id,
-                        picked_card_idx,
-                        None,
-                        DuplicateCardResult::TooLate,
-                    );
-                    return Ok(());
-                }
-                let chosen_card = self.players[&picked_player_id].cards[picked_card_idx];
-                if let Some(discarded_card) = self.deck.get_last_discarded() {
-                    self.validate_idx_card(picked_player_id, picked_card_idx)?;
-
-                    if chosen_card.get_value() == discarded_card.get_value() {
-                        self.handle_success_duplicate(picked_player_id, picked_card_idx, player_id);
-                    } else {
-                        self.give_penalty(
-                            player_id,
-                            picked_player_id,
-                            picked_card_idx,
-                            Some(chosen_card),
-                            DuplicateCardResult::NotTheSame,
-                        );
-                    }
-                } else {
-                    self.give_penalty(
-                        player_id,
-                        picked_player_id,
-                        picked_card_idx,
-                        Some(chosen_card),
-                        DuplicateCardResult::NotTheSame,
-                    );
-                }
-
-                Ok(())
+            State::NotStarted | State::Terminated => {
+                let event = RoomEvent::PlayerLeft(id);
+                self.send_all_players(event);
             }
+            _ => self.forfeit_player(id, player.cards),
         }
+        self.migrate_host();
+        self.update_auto_start();
     }
 
-    fn handle_success_duplicate(
-        &mut self,
-        picked_player_id: u16,
-        picked_card_idx: usize,
-        player_id: u16,
-    ) {
-        let card = self
-            .players
-            .get_mut(&picked_player_id)
-            .unwrap()
-            .cards
-            .remove(picked_card_idx);
-        self.deck.discard(card);
-        let event = RoomEvent::DuplicateCardAttempt(
-            player_id,
-            picked_player_id,
-            picked_card_idx,
-            Some(card),
-            DuplicateCardResult::Success,
-        );
+    /// A player leaving a running game forfeits: their hand is scored and ranked
+    /// after everyone else, and their seat is removed from the turn order. When
+    /// too few players remain, or the crabul caller leaves, the game ends at once.
+    fn forfeit_player(&mut self, id: PlayerId, cards: Vec<Card>) {
+        self.forfeited_scores.push(Score {
+            player_id: id,
+            total_score: cards
+                .iter()
+                .map(|card| i32::from(self.config.card_score(*card)))
+                .sum(),
+            cards,
+            forfeited: true,
+        });
+        let event = RoomEvent::PlayerForfeited(id);
         self.send_all_players(event);
-        self.duplicate_card_thrown = true;
-        if player_id != picked_player_id {
-            self.state = State::PauseForDuplicateCardThrow(
-                player_id,
-                picked_player_id,
-                picked_card_idx,
-                Box::new(self.state.clone()),
-            )
-        }
-    }
 
-    fn select_card_to_give_away(
-        &mut self,
-        player_id: PlayerId,
-        card_idx: usize,
-    ) -> Result<(), GameError> {
-        if let State::PauseForDuplicateCardThrow(
-            stored_player_id,
-            other_player_id,
-            other_card_idx,
-            state,
-        ) = self.state.clone()
+        if let Some(seat) = self
+            .turn_order
+            .iter()
+            .position(|player_id| *player_id == id)
         {
-            if player_id != stored_player_id {
-                return Err(GameError::OperationNotAllowedAtCurrentState);
+            self.turn_order.remove(seat);
+            if seat <= self.current_player_idx {
+                self.current_player_idx =
+                    (self.current_player_idx + self.turn_order.len() - 1) % self.turn_order.len();
             }
+        }
 
-            self.validate_idx_card(stored_player_id, card_idx)?;
-
-            let card = self
-                .players
-                .get_mut(&player_id)
-                .unwrap()
-                .cards
-                .remove(card_idx);
-            self.players
-                .get_mut(&other_player_id)
-                .unwrap()
-                .cards
-                .insert(other_card_idx, card);
+        if self.state == State::Terminating {
+            return;
+        }
 
-            let event =
-                RoomEvent::CardReplaced(player_id, card_idx, other_player_id, other_card_idx);
-            self.send_all_players(event);
-            self.state = *state;
-            return Ok(());
+        if self.players.len() < self.config.min_players || self.crabul_player == Some(id) {
+            if let Some(current_count_down) = self.current_count_down.take() {
+                current_count_down.abort();
+            }
+            self.finalize_game();
+            return;
         }
-        Err(GameError::OperationNotAllowedAtCurrentState)
-    }
 
-    fn give_penalty(
-        &mut self,
-        player_id: u16,
-        picked_player_id: u16,
-        picked_card_idx: usize,
-        chosen_card: Option<Card>,
-        result: DuplicateCardResult,
-    ) {
-        let event = RoomEvent::DuplicateCardAttempt(
-            player_id,
-            picked_player_id,
-            picked_card_idx,
-            chosen_card,
-            result,
-        );
-        self.send_all_players(event);
-        let new_card = self.deck.draw();
-        self.players
-            .get_mut(&player_id)
-            .unwrap()
-            .cards
-            .push(new_card);
+        self.resume_after_departure(id);
     }
 
-    fn force_end_turn(&mut self, player_id: PlayerId) {
-        if self.turn_order[&self.current_player_idx] != player_id {
-            return;
-        }
-        match self.state {
-            State::NotStarted | State::PeekingPhase | State::Terminating | State::Terminated => {}
-            State::StartTurn(_) => {
-                let event = RoomEvent::TurnEndedByTimeout(player_id);
-                self.send_all_players(event);
-                let card = self.deck.draw();
-                let event = RoomEvent::CardWasDrawn(player_id);
-                self.send_all_players(event);
-                self.auto_discard_drawn_card(card, player_id);
+    fn resume_after_departure(&mut self, id: PlayerId) {
+        match self.state.clone() {
+            State::PeekingPhase if self.players.iter().all(|(_, player)| player.ready) => {
+                let _ = self.tx_channel.send(RoomCommand::NextTurn);
             }
-            State::MiddleTurn(_, card) => {
-                let event = RoomEvent::TurnEndedByTimeout(player_id);
-                self.send_all_players(event);
-                self.auto_discard_drawn_card(card, player_id);
+            State::StartTurn(player_id) | State::PowerStage(player_id, _) if player_id == id => {
+                self.next_turn();
             }
-            State::PowerStage(_, power) => {
-                let event = RoomEvent::TurnEndedByTimeout(player_id);
+            State::MiddleTurn(player_id, card) if player_id == id => {
+                self.deck.discard(card);
+                let event = RoomEvent::CardDiscarded(player_id, card);
                 self.send_all_players(event);
-                self.discard_power(player_id, power);
                 self.next_turn();
             }
-            State::PauseForDuplicateCardThrow(_, _, _, _) => {
-                //reset timer;
-                spawn(Self::turn_countdown(player_id, self.tx_channel.clone()));
+            State::PauseForDuplicateCardThrow(player_id, other_player_id, _, state)
+                if player_id == id || other_player_id == id =>
+            {
+                self.state = *state;
+                self.resume_after_departure(id);
             }
+            _ => {}
         }
     }
 
-    fn discard_power(&mut self, player_id: PlayerId, power: Power) {
-        match power {
-            Power::PeekOwnCard
-            | Power::PeekOtherCard
-            | Power::CheckAndSwapStage1
-            | Power::CheckAndSwapStage2(..) => {
-                let event = RoomEvent::PowerDiscarded(player_id, power);
-                self.send_all_players(event);
-            }
-            Power::BlindSwap => {
-                let rng = &mut rand::thread_rng();
-                let player_list = self.players.iter().filter(|(id, _)| {
-                    **id != player_id
-                        && (self.crabul_player.is_none() || **id != self.crabul_player.unwrap())
-                });
+    fn disconnect_player(&mut self, id: PlayerId, player_channel: UnboundedReceiver<RoomEvent>) {
+        if matches!(self.state, State::NotStarted | State::Terminated) {
+            self.remove_player(id);
+            return;
+        }
+        if !self.players.contains_key(&id) {
+            return;
+        }
+        let reconnect_countdown = self.start_timer(Self::reconnect_countdown(
+            self.config.reconnect_grace_period,
+            id,
+            self.tx_channel.clone(),
+        ));
+        let player = self.players.get_mut(&id).unwrap();
+        player.parked_channel = Some(player_channel);
+        player.reconnect_countdown = Some(reconnect_countdown);
+        let event = RoomEvent::PlayerDisconnected(id);
+        self.send_all_players(event);
+    }
 
-                if player_list.clone().count() == 0 {
-                    let event = RoomEvent::PowerDiscarded(player_id, power);
-                    self.send_all_players(event);
-                    return;
-                }
+    fn resume_player(
+        &mut self,
+        reconnect_token: ReconnectToken,
+    ) -> Result<(PlayerId, UnboundedReceiver<RoomEvent>), GameError> {
+        let (&id, player) = self
+            .players
+            .iter_mut()
+            .find(|(_, player)| player.reconnect_token == reconnect_token)
+            .ok_or(GameError::InvalidReconnectToken)?;
+        let mut player_channel = player
+            .parked_channel
+            .take()
+            .ok_or(GameError::PlayerAlreadyConnected)?;
+        if let Some(reconnect_countdown) = player.reconnect_countdown.take() {
+            reconnect_countdown.abort();
+        }
+        // Only a resume that went through is replayed.
+        self.record(RecordKind::Command(RecordedCommand::ResumePlayer(id)));
 
-                let (other_player_id, other_player) = player_list.choose(rng).unwrap();
-                let card_idx = rng.gen_range(0..self.players[&player_id].cards.len());
-                let other_card_idx = rng.gen_range(0..other_player.cards.len());
+        // The snapshot supersedes whatever was sent while the player was away.
+        while player_channel.try_recv().is_ok() {}
 
-                let other_player_id = *other_player_id;
+        let event = RoomEvent::PlayerReconnected(id);
+        self.send_all_players(event);
+        let event = RoomEvent::StateSnapshot(self.snapshot(Some(id)));
+        self.send_to_player(id, event);
 
-                self.swap_players_card(player_id, card_idx, other_player_id, other_card_idx)
-                    .unwrap();
+        Ok((id, player_channel))
+    }
 
-                let event = RoomEvent::ForcedBlindSwap(
-                    player_id,
-                    card_idx,
-                    other_player_id,
-                    other_card_idx,
-                );
-                self.send_all_players(event);
-            }
+    fn reconnect_timeout(&mut self, id: PlayerId) {
+        if self
+            .players
+            .get(&id)
+            .is_some_and(|player| player.parked_channel.is_some())
+        {
+            self.remove_player(id);
         }
     }
 
-    fn auto_discard_drawn_card(&mut self, card: Card, player_id: u16) {
-        self.deck.discard(card);
-        let event = RoomEvent::CardDiscarded(player_id, card);
-        self.send_all_players(event);
-
-        if let Some(power) = self.match_power(card) {
-            self.discard_power(player_id, power);
+    fn snapshot(&self, viewer: Option<PlayerId>) -> GameSnapshot {
+        let drawn_card = match self.state {
+            State::MiddleTurn(player_id, card) if Some(player_id) == viewer => Some(card),
+            _ => None,
+        };
+        GameSnapshot {
+            room_id: self.id,
+            phase: Phase::from(&self.state),
+            host: self.host,
+            turn_order: self.turn_order.clone(),
+            players: self
+                .players
+                .iter()
+                .map(|(&player_id, player)| PlayerSnapshot {
+                    player_id,
+                    name: player.name.clone(),
+                    identity: player.identity.clone(),
+                    rating: self.ratings.get(&player_id).copied(),
+                    bot: player.bot,
+                    hand_size: player.cards.len(),
+                    known_cards: player.visible_cards(viewer),
+                    ready: player.ready,
+                    connected: player.parked_channel.is_none(),
+                })
+                .collect(),
+            discard_pile_top: self.deck.get_last_discarded().copied(),
+            draw_pile_size: self.deck.remaining(),
+            crabul_player: self.crabul_player,
+            drawn_card,
+            remaining_time_ms: self.remaining_time_ms(),
+            round: self.round,
+            standings: self.sorted_standings(),
         }
-
-        self.next_turn();
     }
 
-    fn swap_players_card(
-        &mut self,
-        player_id_1: PlayerId,
-        card_idx_1: usize,
-        player_id_2: PlayerId,
-        card_idx_2: usize,
-    ) -> Result<(), GameError> {
-        self.validate_idx_card(player_id_1, card_idx_1)?;
-        self.validate_idx_card(player_id_2, card_idx_2)?;
+    fn summary(&self) -> RoomSummary {
+        RoomSummary {
+            room_id: self.id,
+            room_code: room_code::to_code(self.id),
+            phase: Phase::from(&self.state),
+            players: self.players.len(),
+            player_names: self
+                .players
+                .values()
+                .map(|player| player.name.clone())
+                .collect(),
+            capacity: self.config.max_players,
+            host_name: self
+                .host
+                .and_then(|host| self.players.get(&host))
+                .map(|host| host.name.clone()),
+            hand_size: self.config.hand_size,
+            score_limit: self.config.score_limit,
+            turn_countdown: self.config.turn_countdown,
+        }
+    }
 
-        let mut cards_2 = std::mem::take(&mut self.players.get_mut(&player_id_2).unwrap().cards);
+    fn get_state(&self, viewer: PlayerId) -> Result<GameSnapshot, GameError> {
+        if !self.players.contains_key(&viewer) {
+            return Err(GameError::PlayerNotFound);
+        }
+        Ok(self.snapshot(Some(viewer)))
+    }
 
-        mem::swap(
-            &mut self.players.get_mut(&player_id_1).unwrap().cards[card_idx_1],
-            &mut cards_2[card_idx_2],
-        );
+    fn set_countdown_deadline(&mut self, countdown: Duration) {
+        self.countdown_deadline = Some(Instant::now() + countdown);
+    }
 
-        let _ = mem::replace(
-            &mut self.players.get_mut(&player_id_2).unwrap().cards,
-            cards_2,
-        );
-        Ok(())
+    fn remaining_time_ms(&self) -> Option<u64> {
+        self.countdown_deadline.map(|deadline| {
+            deadline
+                .saturating_duration_since(Instant::now())
+                .as_millis() as u64
+        })
     }
 
-    fn validate_idx_card(&self, player_id: PlayerId, card_idx: usize) -> Result<(), GameError> {
-        let player = self.players.get(&player_id).unwrap();
-        if card_idx >= player.cards.len() {
-            return Err(GameError::InvalidCardIndex);
+    fn set_player_ready(&mut self, id: PlayerId) -> Result<(), GameError> {
+        if self.state != State::PeekingPhase {
+            return Err(GameError::OperationNotAllowedAtCurrentState);
+        }
+
+        let player = self.players.get_mut(&id).ok_or(GameError::PlayerNotFound)?;
+        player.ready = true;
+        let event = RoomEvent::PlayerIsReady(id);
+        self.send_all_players(event);
+        if self.players.iter().all(|(_, player)| player.ready) {
+            let _ = self.tx_channel.send(RoomCommand::NextTurn);
+        }
+        Ok(())
+    }
+
+    fn go_crabul(&mut self, player_id: PlayerId) -> Result<(), GameError> {
+        if self.state != State::StartTurn(player_id) || self.crabul_player.is_some() {
+            return Err(GameError::OperationNotAllowedAtCurrentState);
+        }
+        self.crabul_player = Some(player_id);
+        let event = RoomEvent::PlayerWentCrabul(player_id);
+        self.send_all_players(event);
+        self.next_turn();
+        Ok(())
+    }
+
+    fn next_turn(&mut self) {
+        if let Some(current_count_down) = self.current_count_down.take() {
+            current_count_down.abort();
+        }
+        self.duplicate_card_thrown = false;
+        self.current_player_idx += 1;
+        self.current_player_idx %= self.turn_order.len();
+        let current_player_id = self.turn_order[self.current_player_idx];
+
+        if let Some(crabul_player) = self.crabul_player {
+            if current_player_id == crabul_player {
+                self.set_countdown_deadline(self.config.finalize_game_countdown);
+                self.start_timer(Self::finalize_game_countdown(
+                    self.config.finalize_game_countdown,
+                    self.tx_channel.clone(),
+                ));
+                self.state = State::Terminating;
+                return;
+            }
+        }
+
+        self.state = State::StartTurn(current_player_id);
+
+        let event = RoomEvent::PlayerTurn(current_player_id);
+        self.send_all_players(event);
+
+        self.start_turn_countdown(current_player_id);
+    }
+
+    fn start_turn_countdown(&mut self, player_id: PlayerId) {
+        if let Some(current_count_down) = self.current_count_down.take() {
+            current_count_down.abort();
+        }
+        let countdown = self.turn_countdown_for(player_id);
+        self.set_countdown_deadline(countdown);
+        let count_down = self.start_timer(Self::turn_countdown(
+            countdown,
+            player_id,
+            self.tx_channel.clone(),
+        ));
+        self.current_count_down = Some(count_down);
+    }
+
+    /// The handle of a replayed room's timer belongs to a task that is already
+    /// done, so that the room still sees a countdown as started.
+    fn start_timer(&self, timer: impl Future<Output = ()> + Send + 'static) -> JoinHandle<()> {
+        match self.replaying {
+            true => spawn(async {}),
+            false => spawn(timer),
+        }
+    }
+
+    fn turn_countdown_for(&self, player_id: PlayerId) -> Duration {
+        match self.players.get(&player_id) {
+            Some(player) if player.bot => self.config.bot_turn_countdown,
+            _ => self.config.turn_countdown,
+        }
+    }
+
+    fn finalize_game(&mut self) {
+        let scores = self.players.iter().map(|(player_id, player)| Score {
+            player_id: *player_id,
+            cards: player.cards.clone(),
+            total_score: player
+                .cards
+                .iter()
+                .map(|card| i32::from(self.config.card_score(*card)))
+                .sum(),
+            forfeited: false,
+        });
+        self.state = State::Terminated;
+        self.countdown_deadline = None;
+
+        let mut sorted_scores: Vec<Score> = scores.collect();
+        sorted_scores.sort_by_key(|score| score.total_score);
+
+        // The crabul caller loses ties.
+        let best_score = sorted_scores[0].total_score;
+        let final_winner = sorted_scores
+            .iter()
+            .find(|score| {
+                score.total_score == best_score && Some(score.player_id) != self.crabul_player
+            })
+            .unwrap_or(&sorted_scores[0])
+            .player_id;
+
+        sorted_scores.append(&mut self.forfeited_scores);
+        let round_scores = sorted_scores
+            .iter()
+            .map(|score| (score.player_id, score.total_score))
+            .collect();
+
+        // The winner places first even when tied, forfeits place last.
+        let rated: Vec<(PlayerId, Rating, i32)> = sorted_scores
+            .iter()
+            .filter_map(|score| {
+                let rating = *self.ratings.get(&score.player_id)?;
+                let place = match score.player_id == final_winner {
+                    true => i32::MIN,
+                    false if score.forfeited => i32::MAX,
+                    false => score.total_score,
+                };
+                Some((score.player_id, rating, place))
+            })
+            .collect();
+        let rating_changes = rating::rating_changes(&rated);
+        for change in rating_changes.iter() {
+            self.ratings.insert(change.player_id, change.rating);
+        }
+
+        let event = RoomEvent::GameTerminated(FinalScore {
+            game_id: self.game_id,
+            winner: final_winner,
+            scores: sorted_scores,
+            seed: self.seed,
+            rating_changes,
+        });
+        self.send_all_players(event);
+
+        match self.config.score_limit {
+            Some(score_limit) => self.end_round(round_scores, score_limit),
+            None => self.finish(),
+        }
+    }
+
+    fn draw_card(&mut self, player_id: PlayerId) -> Result<(), GameError> {
+        if self.state != State::StartTurn(player_id) {
+            return Err(GameError::OperationNotAllowedAtCurrentState);
+        }
+        let card = self.deck.draw();
+        self.state = State::MiddleTurn(player_id, card);
+
+        let event = RoomEvent::CardWasDrawn(player_id);
+        self.send_all_players(event);
+
+        let event = RoomEvent::DrawnCard(card);
+        self.send_to_player(player_id, event);
+
+        Ok(())
+    }
+
+    fn swap_card(&mut self, player_id: PlayerId, card_idx: usize) -> Result<(), GameError> {
+        if let State::MiddleTurn(stored_player_id, card) = self.state {
+            self.validate_player_turn(player_id, stored_player_id)?;
+            self.validate_idx_card(player_id, card_idx)?;
+
+            let player = self.players.get_mut(&player_id).unwrap();
+            let (card, _) = player.replace_card(card_idx, card, HashSet::from([player_id]));
+            self.deck.discard(card);
+            let event = RoomEvent::CardSwapped(player_id, card_idx);
+            self.send_all_players(event);
+            let event = RoomEvent::CardDiscarded(player_id, card);
+            self.send_all_players(event);
+            self.next_turn();
+            return Ok(());
+        }
+        Err(GameError::OperationNotAllowedAtCurrentState)
+    }
+
+    fn discard_card(&mut self, player_id: PlayerId) -> Result<(), GameError> {
+        if let State::MiddleTurn(stored_player_id, card) = self.state {
+            self.validate_player_turn(player_id, stored_player_id)?;
+
+            self.deck.discard(card);
+            let event = RoomEvent::CardDiscarded(player_id, card);
+            self.send_all_players(event);
+
+            if let Some(power) = self.config.match_power(card) {
+                if self.crabul_player.is_some() && self.players.len() == 2 {
+                    let event = RoomEvent::PowerDiscarded(player_id, power);
+                    self.send_all_players(event);
+                    self.next_turn();
+                    return Ok(());
+                }
+                let event = RoomEvent::PowerActivated(player_id, power);
+                self.send_all_players(event);
+                self.state = State::PowerStage(player_id, power);
+                return Ok(());
+            }
+
+            self.next_turn();
+            return Ok(());
+        }
+        Err(GameError::OperationNotAllowedAtCurrentState)
+    }
+
+    fn peek_own_card(&mut self, player_id: PlayerId, card_idx: usize) -> Result<(), GameError> {
+        if let State::PowerStage(stored_player_id, Power::PeekOwnCard) = self.state {
+            self.validate_player_turn(player_id, stored_player_id)?;
+
+            let player = self.players.get_mut(&player_id).unwrap();
+            if card_idx >= player.cards.len() {
+                return Err(GameError::InvalidCardIndex);
+            }
+            let card = player.cards[card_idx];
+            player.reveal_card(card_idx, player_id);
+            let event = RoomEvent::PeekedCard(card);
+            self.send_to_player(player_id, event);
+
+            let event =
+                RoomEvent::PowerUsed(Power::PeekOwnCard, player_id, Some(card_idx), None, None);
+            self.send_all_players(event);
+            self.next_turn();
+            return Ok(());
+        }
+        Err(GameError::OperationNotAllowedAtCurrentState)
+    }
+    fn peek_other_card(
+        &mut self,
+        player_id: PlayerId,
+        other_player_id: PlayerId,
+        other_card_idx: usize,
+    ) -> Result<(), GameError> {
+        if let State::PowerStage(stored_player_id, Power::PeekOtherCard) = self.state {
+            self.validate_player_turn(player_id, stored_player_id)?;
+            self.validate_crabul_player(other_player_id)?;
+
+            self.validate_idx_card(other_player_id, other_card_idx)?;
+
+            let other_player = self.players.get_mut(&other_player_id).unwrap();
+            let card = other_player.cards[other_card_idx];
+            other_player.reveal_card(other_card_idx, player_id);
+            let event = RoomEvent::PeekedCard(card);
+            self.send_to_player(player_id, event);
+
+            let event = RoomEvent::PowerUsed(
+                Power::PeekOtherCard,
+                player_id,
+                None,
+                Some(other_player_id),
+                Some(other_card_idx),
+            );
+            self.send_all_players(event);
+            self.next_turn();
+            return Ok(());
+        }
+        Err(GameError::OperationNotAllowedAtCurrentState)
+    }
+
+    fn blind_swap(
+        &mut self,
+        player_id: PlayerId,
+        card_idx: usize,
+        other_player_id: PlayerId,
+        other_card_idx: usize,
+    ) -> Result<(), GameError> {
+        if let State::PowerStage(stored_player_id, Power::BlindSwap) = self.state {
+            self.validate_player_turn(player_id, stored_player_id)?;
+            self.validate_crabul_player(other_player_id)?;
+
+            self.swap_players_card(player_id, card_idx, other_player_id, other_card_idx)?;
+
+            let event = RoomEvent::PowerUsed(
+                Power::BlindSwap,
+                player_id,
+                Some(card_idx),
+                Some(other_player_id),
+                Some(other_card_idx),
+            );
+            self.send_all_players(event);
+            self.next_turn();
+            return Ok(());
+        }
+        Err(GameError::OperationNotAllowedAtCurrentState)
+    }
+
+    fn check_and_swap_stage1(
+        &mut self,
+        player_id: PlayerId,
+        other_player_id: PlayerId,
+        other_card_idx: usize,
+    ) -> Result<(), GameError> {
+        if let State::PowerStage(stored_player_id, Power::CheckAndSwapStage1) = self.state {
+            self.validate_player_turn(player_id, stored_player_id)?;
+            self.validate_crabul_player(other_player_id)?;
+
+            self.validate_idx_card(other_player_id, other_card_idx)?;
+
+            let other_player = self.players.get_mut(&other_player_id).unwrap();
+            let card = other_player.cards[other_card_idx];
+            other_player.reveal_card(other_card_idx, player_id);
+            let event = RoomEvent::PeekedCard(card);
+            self.send_to_player(player_id, event);
+
+            let event = RoomEvent::PowerUsed(
+                Power::CheckAndSwapStage1,
+                player_id,
+                None,
+                Some(other_player_id),
+                Some(other_card_idx),
+            );
+            self.send_all_players(event);
+            self.state = State::PowerStage(
+                stored_player_id,
+                Power::CheckAndSwapStage2(other_player_id, other_card_idx),
+            );
+            return Ok(());
+        }
+        Err(GameError::OperationNotAllowedAtCurrentState)
+    }
+
+    fn check_and_swap_stage2(
+        &mut self,
+        player_id: PlayerId,
+        card_idx: Option<usize>,
+    ) -> Result<(), GameError> {
+        if let State::PowerStage(
+            stored_player_id,
+            Power::CheckAndSwapStage2(other_player_id, other_card_idx),
+        ) = self.state
+        {
+            self.validate_player_turn(player_id, stored_player_id)?;
+
+            if let Some(card_idx) = card_idx {
+                self.swap_players_card(player_id, card_idx, other_player_id, other_card_idx)?;
+            }
+
+            let event = RoomEvent::PowerUsed(
+                Power::CheckAndSwapStage2(other_player_id, other_card_idx),
+                player_id,
+                card_idx,
+                Some(other_player_id),
+                Some(other_card_idx),
+            );
+            self.send_all_players(event);
+            self.next_turn();
+            return Ok(());
+        }
+        Err(GameError::OperationNotAllowedAtCurrentState)
+    }
+
+    fn throw_duplicate_card(
+        &mut self,
+        player_id: PlayerId,
+        picked_player_id: PlayerId,
+        picked_card_idx: usize,
+    ) -> Result<(), GameError> {
+        match self.state {
+            State::NotStarted | State::PeekingPhase | State::Terminated => {
+                Err(GameError::OperationNotAllowedAtCurrentState)
+            }
+            State::StartTurn(_)
+            | State::MiddleTurn(_, _)
+            | State::PowerStage(_, _)
+            | State::PauseForDuplicateCardThrow(_, _, _, _)
+            | State::Terminating => {
+                if !self.players.contains_key(&player_id) {
+                    return Err(GameError::PlayerNotFound);
+                }
+                if self.duplicate_card_thrown {
+                    self.give_penalty(
+                        player_id,
+                        picked_player_id,
+                        picked_card_idx,
+                        None,
+                        DuplicateCardResult::TooLate,
+                    );
+                    return Ok(());
+                }
+                self.validate_idx_card(picked_player_id, picked_card_idx)?;
+                let chosen_card = self.players[&picked_player_id].cards[picked_card_idx];
+                if let Some(discarded_card) = self.deck.get_last_discarded() {
+                    if chosen_card.get_value() == discarded_card.get_value() {
+                        self.handle_success_duplicate(picked_player_id, picked_card_idx, player_id);
+                    } else {
+                        self.give_penalty(
+                            player_id,
+                            picked_player_id,
+                            picked_card_idx,
+                            Some(chosen_card),
+                            DuplicateCardResult::NotTheSame,
+                        );
+                    }
+                } else {
+                    self.give_penalty(
+                        player_id,
+                        picked_player_id,
+                        picked_card_idx,
+                        Some(chosen_card),
+                        DuplicateCardResult::NotTheSame,
+                    );
+                }
+
+                Ok(())
+            }
+        }
+    }
+
+    fn handle_success_duplicate(
+        &mut self,
+        picked_player_id: u16,
+        picked_card_idx: usize,
+        player_id: u16,
+    ) {
+        let (card, _) = self
+            .players
+            .get_mut(&picked_player_id)
+            .unwrap()
+            .remove_card(picked_card_idx);
+        self.deck.discard(card);
+        let event = RoomEvent::DuplicateCardAttempt(
+            player_id,
+            picked_player_id,
+            picked_card_idx,
+            Some(card),
+            DuplicateCardResult::Success,
+        );
+        self.send_all_players(event);
+        self.duplicate_card_thrown = true;
+        if player_id != picked_player_id {
+            self.state = State::PauseForDuplicateCardThrow(
+                player_id,
+                picked_player_id,
+                picked_card_idx,
+                Box::new(self.state.clone()),
+            )
+        }
+    }
+
+    fn select_card_to_give_away(
+        &mut self,
+        player_id: PlayerId,
+        card_idx: usize,
+    ) -> Result<(), GameError> {
+        if let State::PauseForDuplicateCardThrow(
+            stored_player_id,
+            other_player_id,
+            other_card_idx,
+            state,
+        ) = self.state.clone()
+        {
+            if player_id != stored_player_id {
+                return Err(GameError::OperationNotAllowedAtCurrentState);
+            }
+
+            self.validate_idx_card(stored_player_id, card_idx)?;
+
+            let (card, known_by) = self
+                .players
+                .get_mut(&player_id)
+                .unwrap()
+                .remove_card(card_idx);
+            self.players.get_mut(&other_player_id).unwrap().insert_card(
+                other_card_idx,
+                card,
+                known_by,
+            );
+
+            let event =
+                RoomEvent::CardReplaced(player_id, card_idx, other_player_id, other_card_idx);
+            self.send_all_players(event);
+            self.state = *state;
+            return Ok(());
+        }
+        Err(GameError::OperationNotAllowedAtCurrentState)
+    }
+
+    fn give_penalty(
+        &mut self,
+        player_id: u16,
+        picked_player_id: u16,
+        picked_card_idx: usize,
+        chosen_card: Option<Card>,
+        result: DuplicateCardResult,
+    ) {
+        let event = RoomEvent::DuplicateCardAttempt(
+            player_id,
+            picked_player_id,
+            picked_card_idx,
+            chosen_card,
+            result,
+        );
+        self.send_all_players(event);
+        let new_card = self.deck.draw();
+        self.players
+            .get_mut(&player_id)
+            .unwrap()
+            .push_card(new_card, HashSet::new());
+    }
+
+    fn force_end_turn(&mut self, player_id: PlayerId) {
+        if self.turn_order.get(self.current_player_idx) != Some(&player_id) {
+            return;
+        }
+        match self.state {
+            State::NotStarted | State::PeekingPhase | State::Terminating | State::Terminated => {}
+            State::StartTurn(_) => {
+                let event = RoomEvent::TurnEndedByTimeout(player_id);
+                self.send_all_players(event);
+                let card = self.deck.draw();
+                let event = RoomEvent::CardWasDrawn(player_id);
+                self.send_all_players(event);
+                self.auto_discard_drawn_card(card, player_id);
+            }
+            State::MiddleTurn(_, card) => {
+                let event = RoomEvent::TurnEndedByTimeout(player_id);
+                self.send_all_players(event);
+                self.auto_discard_drawn_card(card, player_id);
+            }
+            State::PowerStage(_, power) => {
+                let event = RoomEvent::TurnEndedByTimeout(player_id);
+                self.send_all_players(event);
+                self.discard_power(player_id, power);
+                self.next_turn();
+            }
+            State::PauseForDuplicateCardThrow(_, _, _, _) => {
+                //reset timer;
+                let countdown = self.turn_countdown_for(player_id);
+                self.set_countdown_deadline(countdown);
+                self.start_timer(Self::turn_countdown(
+                    countdown,
+                    player_id,
+                    self.tx_channel.clone(),
+                ));
+            }
+        }
+    }
+
+    fn discard_power(&mut self, player_id: PlayerId, power: Power) {
+        match power {
+            Power::PeekOwnCard
+            | Power::PeekOtherCard
+            | Power::CheckAndSwapStage1
+            | Power::CheckAndSwapStage2(..) => {
+                let event = RoomEvent::PowerDiscarded(player_id, power);
+                self.send_all_players(event);
+            }
+            Power::BlindSwap => {
+                let player_list = self.players.iter().filter(|(id, player)| {
+                    **id != player_id
+                        && (self.crabul_player.is_none() || **id != self.crabul_player.unwrap())
+                        && !player.cards.is_empty()
+                });
+
+                if player_list.clone().count() == 0 || self.players[&player_id].cards.is_empty() {
+                    let event = RoomEvent::PowerDiscarded(player_id, power);
+                    self.send_all_players(event);
+                    return;
+                }
+
+                let (other_player_id, other_player) = player_list.choose(&mut self.rng).unwrap();
+                let card_idx = self.rng.gen_range(0..self.players[&player_id].cards.len());
+                let other_card_idx = self.rng.gen_range(0..other_player.cards.len());
+
+                let other_player_id = *other_player_id;
+
+                self.swap_players_card(player_id, card_idx, other_player_id, other_card_idx)
+                    .unwrap();
+
+                let event = RoomEvent::ForcedBlindSwap(
+                    player_id,
+                    card_idx,
+                    other_player_id,
+                    other_card_idx,
+                );
+                self.send_all_players(event);
+            }
+        }
+    }
+
+    fn auto_discard_drawn_card(&mut self, card: Card, player_id: u16) {
+        self.deck.discard(card);
+        let event = RoomEvent::CardDiscarded(player_id, card);
+        self.send_all_players(event);
+
+        if let Some(power) = self.config.match_power(card) {
+            self.discard_power(player_id, power);
+        }
+
+        self.next_turn();
+    }
+
+    fn swap_players_card(
+        &mut self,
+        player_id_1: PlayerId,
+        card_idx_1: usize,
+        player_id_2: PlayerId,
+        card_idx_2: usize,
+    ) -> Result<(), GameError> {
+        self.validate_idx_card(player_id_1, card_idx_1)?;
+        self.validate_idx_card(player_id_2, card_idx_2)?;
+
+        // Whoever knew a card keeps knowing it at its new position.
+        let player_2 = self.players.get_mut(&player_id_2).unwrap();
+        let (card_2, known_by_2) = player_2.replace_card(card_idx_2, Card::Joker, HashSet::new());
+        let (card_1, known_by_1) = self
+            .players
+            .get_mut(&player_id_1)
+            .unwrap()
+            .replace_card(card_idx_1, card_2, known_by_2);
+        self.players
+            .get_mut(&player_id_2)
+            .unwrap()
+            .replace_card(card_idx_2, card_1, known_by_1);
+        Ok(())
+    }
+
+    fn validate_idx_card(&self, player_id: PlayerId, card_idx: usize) -> Result<(), GameError> {
+        let player = self
+            .players
+            .get(&player_id)
+            .ok_or(GameError::PlayerNotFound)?;
+        if card_idx >= player.cards.len() {
+            return Err(GameError::InvalidCardIndex);
+        }
+        Ok(())
+    }
+
+    fn validate_player_turn(
+        &self,
+        player_id: PlayerId,
+        stored_player_id: PlayerId,
+    ) -> Result<(), GameError> {
+        if player_id != stored_player_id {
+            return Err(GameError::OperationNotAllowedAtCurrentState);
+        }
+        Ok(())
+    }
+
+    fn validate_crabul_player(&self, other_player_id: PlayerId) -> Result<(), GameError> {
+        if let Some(crabul_player) = self.crabul_player {
+            if crabul_player == other_player_id {
+                return Err(GameError::OperationNotAllowedAtCurrentState);
+            }
+        }
+        Ok(())
+    }
+
+    /// Every command of the game the room would accept from the player right
+    /// now: what they owe, throws of a duplicate while the discard pile top can
+    /// still be matched, and readiness and start in the lobby.
+    fn legal_actions(&self, player_id: PlayerId) -> Result<Vec<ClientMessage>, GameError> {
+        let player = self
+            .players
+            .get(&player_id)
+            .ok_or(GameError::PlayerNotFound)?;
+        let mut actions = self.pending_decision(player_id).unwrap_or_default();
+        match self.state {
+            State::NotStarted => {
+                actions.push(ClientMessage::SetLobbyReady {
+                    ready: !player.ready,
+                });
+                if self.host == Some(player_id) && self.players.len() >= self.config.min_players {
+                    actions.push(ClientMessage::StartGame);
+                }
+            }
+            State::StartTurn(_)
+            | State::MiddleTurn(..)
+            | State::PowerStage(..)
+            | State::PauseForDuplicateCardThrow(..)
+            | State::Terminating => {
+                if !self.duplicate_card_thrown && self.deck.get_last_discarded().is_some() {
+                    actions.extend(self.players.iter().flat_map(|(&picked_player_id, picked)| {
+                        (0..picked.cards.len()).map(move |picked_card_idx| {
+                            ClientMessage::ThrowSameCard {
+                                picked_player_id,
+                                picked_card_idx,
+                            }
+                        })
+                    }));
+                }
+            }
+            State::PeekingPhase | State::Terminated => {}
+        }
+        Ok(actions)
+    }
+
+    /// What legal actions follow from besides the state: whether a duplicate
+    /// can still be thrown, on which card, and the cards it can be picked from.
+    /// Empty hands are left out, lobby comings and goings change nothing.
+    fn actions_basis(&self) -> (bool, Option<Card>, Vec<(PlayerId, usize)>) {
+        (
+            self.duplicate_card_thrown,
+            self.deck.get_last_discarded().copied(),
+            self.players
+                .iter()
+                .filter(|(_, player)| !player.cards.is_empty())
+                .map(|(&player_id, player)| (player_id, player.cards.len()))
+                .collect(),
+        )
+    }
+
+    /// Nothing is sent once terminated, `GameTerminated` says it all. Left
+    /// out of the record, as they follow from the state it already holds.
+    fn send_legal_actions(&self) {
+        if self.state == State::Terminated {
+            return;
+        }
+        for (&player_id, player) in self.players.iter() {
+            if let Ok(actions) = self.legal_actions(player_id) {
+                let _ = player.tx.send(RoomEvent::LegalActions(actions));
+            }
+        }
+    }
+
+    /// Moves the player has to choose from for the game to go on, `None` when
+    /// nothing is expected from them.
+    fn pending_decision(&self, player_id: PlayerId) -> Option<Vec<ClientMessage>> {
+        let player = self.players.get(&player_id)?;
+        let own_cards = 0..player.cards.len();
+        match self.state {
+            State::PeekingPhase if !player.ready => Some(vec![ClientMessage::SetPlayerReady]),
+            State::StartTurn(turn_player) if turn_player == player_id => {
+                let mut actions = vec![ClientMessage::DrawCard];
+                if self.crabul_player.is_none() {
+                    actions.push(ClientMessage::GoCrabul);
+                }
+                Some(actions)
+            }
+            State::MiddleTurn(turn_player, _) if turn_player == player_id => Some(
+                own_cards
+                    .map(|card_idx| ClientMessage::SwapCard { card_idx })
+                    .chain([ClientMessage::DiscardCard])
+                    .collect(),
+            ),
+            State::PowerStage(turn_player, power) if turn_player == player_id => {
+                Some(self.power_actions(player_id, power))
+            }
+            State::PauseForDuplicateCardThrow(thrower, ..) if thrower == player_id => Some(
+                own_cards
+                    .map(|card_idx| ClientMessage::SelectCardToGiveAway { card_idx })
+                    .collect(),
+            ),
+            _ => None,
+        }
+    }
+
+    fn power_actions(&self, player_id: PlayerId, power: Power) -> Vec<ClientMessage> {
+        let own_cards = 0..self.players[&player_id].cards.len();
+        // Cards of the other players, the crabul caller's are off limits.
+        let targets: Vec<(PlayerId, usize)> = self
+            .players
+            .iter()
+            .filter(|(&other_player_id, _)| {
+                other_player_id != player_id && self.validate_crabul_player(other_player_id).is_ok()
+            })
+            .flat_map(|(&other_player_id, other_player)| {
+                (0..other_player.cards.len())
+                    .map(move |other_card_idx| (other_player_id, other_card_idx))
+            })
+            .collect();
+        match power {
+            Power::PeekOwnCard => own_cards
+                .map(|card_idx| ClientMessage::PeekOwnCard { card_idx })
+                .collect(),
+            Power::PeekOtherCard => targets
+                .into_iter()
+                .map(
+                    |(other_player_id, other_card_idx)| ClientMessage::PeekOtherCard {
+                        other_player_id,
+                        other_card_idx,
+                    },
+                )
+                .collect(),
+            Power::BlindSwap => own_cards
+                .flat_map(|card_idx| {
+                    targets
+                        .iter()
+                        .map(
+                            move |&(other_player_id, other_card_idx)| ClientMessage::BlindSwap {
+                                card_idx,
+                                other_player_id,
+                                other_card_idx,
+                            },
+                        )
+                })
+                .collect(),
+            Power::CheckAndSwapStage1 => targets
+                .into_iter()
+                .map(
+                    |(other_player_id, other_card_idx)| ClientMessage::CheckAndSwapStage1 {
+                        other_player_id,
+                        other_card_idx,
+                    },
+                )
+                .collect(),
+            Power::CheckAndSwapStage2(..) => [None]
+                .into_iter()
+                .chain(own_cards.map(Some))
+                .map(|card_idx| ClientMessage::CheckAndSwapStage2 { card_idx })
+                .collect(),
+        }
+    }
+
+    /// Tells every bot seat owing a decision what it can play, and restarts
+    /// the clock of a bot moving on to the next decision of its turn.
+    fn prompt_bots(&mut self) {
+        if let State::MiddleTurn(player_id, _) | State::PowerStage(player_id, _) = self.state {
+            if self
+                .players
+                .get(&player_id)
+                .is_some_and(|player| player.bot)
+            {
+                self.start_turn_countdown(player_id);
+            }
+        }
+        let prompts: Vec<(PlayerId, Vec<ClientMessage>)> = self
+            .players
+            .iter()
+            .filter(|(_, player)| player.bot)
+            .filter_map(|(&player_id, _)| {
+                self.pending_decision(player_id)
+                    .map(|actions| (player_id, actions))
+            })
+            .collect();
+        for (player_id, actions) in prompts {
+            let event = RoomEvent::YourMove {
+                actions,
+                remaining_time_ms: self.remaining_time_ms(),
+            };
+            self.send_to_player(player_id, event);
+        }
+    }
+
+    fn send_to_player(&self, player_id: PlayerId, event: RoomEvent) {
+        if let Some(player) = self.players.get(&player_id) {
+            self.record(RecordKind::Event {
+                to: Some(player_id),
+                event: event.clone(),
+            });
+            let _ = player.tx.send(event);
+        }
+    }
+
+    fn send_all_players(&self, event: RoomEvent) {
+        self.record(RecordKind::Event {
+            to: None,
+            event: event.clone(),
+        });
+        self.players.iter().for_each(|(_, player)| {
+            let _ = player.tx.send(event.clone());
+        });
+        // Whatever every player gets is public, so spectators see it as well.
+        self.send_to_spectators(event);
+    }
+
+    fn send_to_spectators(&self, event: RoomEvent) {
+        self.spectators.values().for_each(|spectator| {
+            let _ = spectator.send(event.clone());
+        });
+    }
+
+    async fn auto_start_countdown(countdown: Duration, tx_channel: UnboundedSender<RoomCommand>) {
+        sleep(countdown).await;
+        let _ = tx_channel.send(RoomCommand::AutoStartGame);
+    }
+
+    async fn peeking_phase_countdown(
+        countdown: Duration,
+        tx_channel: UnboundedSender<RoomCommand>,
+    ) {
+        sleep(countdown).await;
+        let _ = tx_channel.send(RoomCommand::NextTurn);
+    }
+
+    async fn turn_countdown(
+        countdown: Duration,
+        player_id: PlayerId,
+        tx_channel: UnboundedSender<RoomCommand>,
+    ) {
+        sleep(countdown).await;
+        let _ = tx_channel.send(RoomCommand::ForceEndTurn(player_id));
+    }
+
+    async fn reconnect_countdown(
+        countdown: Duration,
+        player_id: PlayerId,
+        tx_channel: UnboundedSender<RoomCommand>,
+    ) {
+        sleep(countdown).await;
+        let _ = tx_channel.send(RoomCommand::ReconnectTimeout(player_id));
+    }
+
+    async fn empty_room_countdown(countdown: Duration, tx_channel: UnboundedSender<RoomCommand>) {
+        sleep(countdown).await;
+        let _ = tx_channel.send(RoomCommand::EmptyRoomTimeout);
+    }
+
+    async fn next_round_countdown(countdown: Duration, tx_channel: UnboundedSender<RoomCommand>) {
+        sleep(countdown).await;
+        let _ = tx_channel.send(RoomCommand::StartNextRound);
+    }
+
+    async fn finalize_game_countdown(
+        countdown: Duration,
+        tx_channel: UnboundedSender<RoomCommand>,
+    ) {
+        sleep(countdown).await;
+        let _ = tx_channel.send(RoomCommand::FinalizeGame);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Add;
+
+    use tokio::{runtime::Handle, task::yield_now, time::pause};
+
+    use crate::{
+        history::GameSummary,
+        rating::INITIAL_RATING,
+        room::consts::{
+            AUTO_START_COUNTDOWN, BOT_TURN_COUNTDOWN, EMPTY_ROOM_TIMEOUT, FINALIZE_GAME_COUNTDOWN,
+            HAND_SIZE, NEXT_ROUND_COUNTDOWN, PEEKING_PHASE_COUNTDOWN, TURN_COUNTDOWN,
+        },
+        stats::StatsAggregator,
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn new_player() {
+        let (room_server, mut room_commander) = RoomServer::new(RoomConfig::default());
+        spawn(room_server.run());
+        let mut players = create_n_players(&mut room_commander, 1, false).await;
+
+        let received_event = get_nth_event(&mut players[0].1, 1).await;
+
+        if let RoomEvent::PlayerJoined {
+            room_id: _,
+            player_id,
+            player_name,
+            player_list,
+            ..
+        } = received_event
+        {
+            assert!(player_id == players[0].0);
+            assert!(player_name == "name_0");
+            assert!(player_list == BTreeMap::from([(players[0].0, "name_0".into())]));
+        }
+    }
+
+    #[tokio::test]
+    async fn new_player_previous_player_should_receive_the_join_event() {
+        let (room_server, mut room_commander) = RoomServer::new(RoomConfig::default());
+        spawn(room_server.run());
+        let mut players = create_n_players(&mut room_commander, 2, false).await;
+
+        let received_event = get_nth_event(&mut players[0].1, 2).await;
+        if let RoomEvent::PlayerJoined {
+            room_id: _,
+            player_id,
+            player_name,
+            player_list,
+            ..
+        } = received_event
+        {
+            assert!(player_id == players[1].0);
+            assert!(player_name == "name_1");
+            assert!(
+                player_list
+                    == BTreeMap::from([
+                        (players[0].0, "name_0".into()),
+                        (players[1].0, "name_1".into())
+                    ])
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn seeded_rooms_get_the_same_id() {
+        let config = RoomConfig {
+            seed: Some(3),
+            ..Default::default()
+        };
+        let (first, _) = RoomServer::new(config.clone());
+        let (second, _) = RoomServer::new(config);
+        assert!(first.get_id() == second.get_id());
+    }
+
+    #[tokio::test]
+    async fn new_player_never_takes_an_existing_id() {
+        let (mut room_server, _) = RoomServer::new(RoomConfig::default());
+        let rng = room_server.rng.clone();
+        let (first, _first_rx) = room_server
+            .new_player(
+                "first".into(),
+                Some(PlayerProfile {
+                    identity: "guest-1".into(),
+                    rating: INITIAL_RATING,
+                }),
+                false,
+            )
+            .unwrap();
+        // Same draw again, the id must be skipped.
+        room_server.rng = rng;
+        let (second, _second_rx) = room_server
+            .new_player("second".into(), None, false)
+            .unwrap();
+        assert!(first != second);
+        assert!(room_server.players.len() == 2);
+        assert!(room_server.players[&first].identity.as_deref() == Some("guest-1"));
+    }
+
+    #[tokio::test]
+    async fn new_player_should_fail_when_name_exists() {
+        let (room_server, room_commander) = RoomServer::new(RoomConfig::default());
+        spawn(room_server.run());
+        let (player_name_1, player_name_2) = ("name1", "name1");
+        let _ = room_commander
+            .new_player(player_name_1.into())
+            .await
+            .unwrap();
+        let res = room_commander.new_player(player_name_2.into()).await;
+
+        assert!(matches!(res, Err(GameError::NameAlreadyExists)));
+    }
+
+    #[tokio::test]
+    async fn new_player_should_fail_when_there_are_too_many_players() {
+        let (room_server, mut room_commander) = RoomServer::new(RoomConfig::default());
+        spawn(room_server.run());
+        create_n_players(&mut room_commander, 6, false).await;
+        let res = room_commander.new_player("name_7".into()).await;
+
+        assert!(matches!(res, Err(GameError::TooManyPlayers)));
+    }
+
+    #[tokio::test]
+    async fn remove_player() {
+        let (room_server, mut room_commander) = RoomServer::new(RoomConfig::default());
+        spawn(room_server.run());
+        let mut players = create_n_players(&mut room_commander, 3, false).await;
+        room_commander.remove_player(players[2].0).await;
+
+        let received_event = get_nth_event(&mut players[0].1, 4).await;
+        assert!(
+            matches!(received_event, RoomEvent::PlayerLeft(received_player_id) if received_player_id == players[2].0)
+        );
+
+        let received_event = get_nth_event(&mut players[1].1, 3).await;
+        assert!(
+            matches!(received_event, RoomEvent::PlayerLeft(received_player_id) if received_player_id == players[2].0)
+        );
+    }
+
+    #[tokio::test]
+    async fn disconnect_player_in_lobby_removes_them() {
+        let (room_server, mut room_commander) = RoomServer::new(RoomConfig::default());
+        spawn(room_server.run());
+        let mut players = create_n_players(&mut room_commander, 2, true).await;
+        let (player_id, player_rx) = players.remove(1);
+        room_commander.disconnect_player(player_id, player_rx).await;
+
+        let received_event = get_nth_event(&mut players[0].1, 1).await;
+        assert!(matches!(received_event, RoomEvent::PlayerLeft(id) if id == player_id));
+    }
+
+    #[tokio::test]
+    async fn resume_player_after_disconnect() {
+        let (room_server, mut room_commander) = RoomServer::new(RoomConfig::default());
+        spawn(room_server.run());
+        let mut players = create_n_players(&mut room_commander, 2, false).await;
+        let reconnect_token = match get_nth_event(&mut players[1].1, 1).await {
+            RoomEvent::PlayerJoined {
+                reconnect_token: Some(reconnect_token),
+                ..
+            } => reconnect_token,
+            _ => panic!("Did not receive reconnect token"),
+        };
+        assert!(matches!(
+            get_nth_event(&mut players[0].1, 2).await,
+            RoomEvent::PlayerJoined {
+                reconnect_token: None,
+                ..
+            }
+        ));
+        room_commander.start_game(players[0].0).await.unwrap();
+        clean_events(&mut players).await;
+
+        assert!(matches!(
+            room_commander.resume_player(reconnect_token.clone()).await,
+            Err(GameError::PlayerAlreadyConnected)
+        ));
+
+        let (player_id, player_rx) = players.remove(1);
+        room_commander.disconnect_player(player_id, player_rx).await;
+        let received_event = get_nth_event(&mut players[0].1, 1).await;
+        assert!(matches!(received_event, RoomEvent::PlayerDisconnected(id) if id == player_id));
+
+        assert!(matches!(
+            room_commander.resume_player("wrong".into()).await,
+            Err(GameError::InvalidReconnectToken)
+        ));
+        let (resumed_id, mut player_rx) =
+            room_commander.resume_player(reconnect_token).await.unwrap();
+        assert!(resumed_id == player_id);
+
+        let received_event = get_nth_event(&mut player_rx, 1).await;
+        assert!(matches!(received_event, RoomEvent::PlayerReconnected(id) if id == player_id));
+        let received_event = get_nth_event(&mut player_rx, 1).await;
+        assert!(matches!(
+            received_event,
+            RoomEvent::StateSnapshot(snapshot)
+                if snapshot.phase == Phase::PeekingPhase
+                    && snapshot.players.iter().all(|player| player.hand_size == 4 && player.connected)
+        ));
+    }
+
+    #[tokio::test]
+    async fn remove_player_when_grace_period_expires() {
+        pause();
+        let config = RoomConfig {
+            reconnect_grace_period: Duration::from_secs(5),
+            ..Default::default()
+        };
+        let (room_server, mut room_commander) = RoomServer::new(config);
+        spawn(room_server.run());
+        let mut players = create_n_players(&mut room_commander, 3, true).await;
+        room_commander.start_game(players[0].0).await.unwrap();
+        clean_events(&mut players).await;
+
+        let (player_id, player_rx) = players.remove(2);
+        room_commander.disconnect_player(player_id, player_rx).await;
+        sleep(Duration::from_secs(6)).await;
+
+        let received_event = get_nth_event(&mut players[0].1, 1).await;
+        assert!(matches!(received_event, RoomEvent::PlayerDisconnected(id) if id == player_id));
+        let received_event = get_nth_event(&mut players[0].1, 1).await;
+        assert!(matches!(received_event, RoomEvent::PlayerForfeited(id) if id == player_id));
+    }
+
+    #[tokio::test]
+    async fn forfeit_on_own_turn_passes_the_turn() {
+        let (mut server, commander, mut players_rxs) = get_basic_server();
+        server.state = State::MiddleTurn(0, Card::Clubs(4));
+        spawn(server.run());
+
+        commander.remove_player(0).await;
+
+        for player_rx in players_rxs.iter_mut().skip(1) {
+            let received_event = get_nth_event(player_rx, 1).await;
+            assert!(matches!(received_event, RoomEvent::PlayerForfeited(0)));
+            let received_event = get_nth_event(player_rx, 1).await;
+            assert!(matches!(
+                received_event,
+                RoomEvent::CardDiscarded(0, Card::Clubs(4))
+            ));
+            let received_event = get_nth_event(player_rx, 1).await;
+            assert!(matches!(received_event, RoomEvent::PlayerTurn(1)));
+        }
+    }
+
+    #[tokio::test]
+    async fn forfeit_keeps_turn_order() {
+        let (mut server, commander, mut players_rxs) = get_seeded_server(Some(2));
+        server.current_player_idx = 2;
+        server.state = State::StartTurn(2);
+        spawn(server.run());
+
+        commander.remove_player(1).await;
+        commander.remove_player(3).await;
+        commander.draw_card(2).await.unwrap();
+        commander.discard_card(2).await.unwrap();
+
+        let player_rx = &mut players_rxs[0];
+        let received_event = get_nth_event(player_rx, 1).await;
+        assert!(matches!(received_event, RoomEvent::PlayerForfeited(1)));
+        let received_event = get_nth_event(player_rx, 1).await;
+        assert!(matches!(received_event, RoomEvent::PlayerForfeited(3)));
+        let received_event = get_nth_event(player_rx, 3).await;
+        assert!(matches!(received_event, RoomEvent::PlayerTurn(4)));
+    }
+
+    #[tokio::test]
+    async fn match_deals_a_new_round_after_eliminations() {
+        pause();
+        let (mut server, _commander, mut players_rxs) = get_basic_server();
+        for i in 3..6 {
+            server.players.remove(&i);
+            server.turn_order.retain(|id| *id != i);
+        }
+        players_rxs.truncate(3);
+        server.config.score_limit = Some(10);
+        server.round = 1;
+        server.players.get_mut(&0).unwrap().cards = vec![Card::Clubs(1)];
+        server.players.get_mut(&1).unwrap().cards = vec![Card::Clubs(9)];
+        server.players.get_mut(&2).unwrap().cards = vec![Card::Clubs(12)];
+        server.finalize_game();
+        spawn(server.run());
+        sleep(NEXT_ROUND_COUNTDOWN + Duration::from_secs(1)).await;
+
+        for player_rx in players_rxs.iter_mut() {
+            let received_event = get_nth_event(player_rx, 2).await;
+            assert!(matches!(received_event, RoomEvent::PlayerEliminated(2)));
+            let received_event = get_nth_event(player_rx, 1).await;
+            assert!(matches!(
+                received_event,
+                RoomEvent::RoundEnded { round: 1, standings }
+                    if standings[0].player_id == 0 && standings[0].total_score == 1
+                        && standings[2].player_id == 2 && standings[2].eliminated
+            ));
+            let received_event = get_nth_event(player_rx, 1).await;
+            assert!(matches!(received_event, RoomEvent::RoundStarted(2)));
+        }
+        // Only the players still in the match get a new hand.
+        let received_event = get_nth_event(&mut players_rxs[1], 1).await;
+        assert!(matches!(received_event, RoomEvent::PeekingPhaseStarted(_)));
+        assert!(players_rxs[2].try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn match_ends_with_a_single_player_left() {
+        let (mut server, _commander, mut players_rxs) = get_basic_server();
+        for i in 2..6 {
+            server.players.remove(&i);
+            server.turn_order.retain(|id| *id != i);
+        }
+        players_rxs.truncate(2);
+        server.config.score_limit = Some(10);
+        server.round = 1;
+        server.players.get_mut(&0).unwrap().cards = vec![Card::Clubs(2)];
+        server.players.get_mut(&1).unwrap().cards = vec![Card::Clubs(11)];
+        server.finalize_game();
+
+        for player_rx in players_rxs.iter_mut() {
+            let received_event = get_nth_event(player_rx, 3).await;
+            assert!(matches!(
+                received_event,
+                RoomEvent::RoundEnded { round: 1, .. }
+            ));
+            let received_event = get_nth_event(player_rx, 1).await;
+            assert!(matches!(
+                received_event,
+                RoomEvent::MatchEnded { winner: 0, standings } if standings.len() == 2
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn match_goes_to_the_last_round_when_everybody_is_eliminated() {
+        let (mut server, _commander, mut players_rxs) = get_basic_server();
+        for i in 3..6 {
+            server.players.remove(&i);
+            server.turn_order.retain(|id| *id != i);
+        }
+        players_rxs.truncate(3);
+        server.config.score_limit = Some(10);
+        server.round = 2;
+        server.eliminate_player(2);
+        server.standings = BTreeMap::from([
+            (
+                0,
+                Standing {
+                    player_id: 0,
+                    total_score: 8,
+                    eliminated: false,
+                },
+            ),
+            (
+                1,
+                Standing {
+                    player_id: 1,
+                    total_score: 5,
+                    eliminated: false,
+                },
+            ),
+            (
+                2,
+                Standing {
+                    player_id: 2,
+                    total_score: 11,
+                    eliminated: true,
+                },
+            ),
+        ]);
+        server.eliminated_in.insert(2, 1);
+        server.players.get_mut(&0).unwrap().cards = vec![Card::Clubs(4)];
+        server.players.get_mut(&1).unwrap().cards = vec![Card::Clubs(9)];
+        server.finalize_game();
+
+        // Player 2 has the lowest total, but was out a round earlier.
+        let received_event = get_nth_event(&mut players_rxs[0], 6).await;
+        assert!(matches!(
+            received_event,
+            RoomEvent::MatchEnded { winner: 0, standings }
+                if standings.iter().map(|standing| standing.player_id).eq([0, 1, 2])
+        ));
+    }
+
+    #[tokio::test]
+    async fn game_ends_when_not_enough_players_are_left() {
+        let (mut server, commander, mut players_rxs) = get_basic_server();
+        for i in 2..6 {
+            server.players.remove(&i);
+            server.turn_order.retain(|id| *id != i);
+        }
+        players_rxs.truncate(2);
+        server.players.get_mut(&0).unwrap().cards = vec![Card::Clubs(9)];
+        server.players.get_mut(&1).unwrap().cards = vec![Card::Clubs(1)];
+        server.state = State::StartTurn(0);
+        spawn(server.run());
+
+        commander.remove_player(1).await;
+
+        let received_event = get_nth_event(&mut players_rxs[0], 1).await;
+        assert!(matches!(received_event, RoomEvent::PlayerForfeited(1)));
+        let received_event = get_nth_event(&mut players_rxs[0], 1).await;
+        if let RoomEvent::GameTerminated(score) = received_event {
+            assert!(score.winner == 0);
+            assert!(score.scores.len() == 2);
+            assert!(score.scores[1].player_id == 1 && score.scores[1].forfeited);
+        } else {
+            panic!("Game not terminated");
+        }
+    }
+
+    #[tokio::test]
+    async fn game_ends_when_crabul_player_leaves() {
+        let (mut server, commander, mut players_rxs) = get_basic_server();
+        server.crabul_player = Some(3);
+        server.current_player_idx = 4;
+        server.state = State::StartTurn(4);
+        spawn(server.run());
+
+        commander.remove_player(3).await;
+
+        let received_event = get_nth_event(&mut players_rxs[0], 1).await;
+        assert!(matches!(received_event, RoomEvent::PlayerForfeited(3)));
+        let received_event = get_nth_event(&mut players_rxs[0], 1).await;
+        assert!(matches!(received_event, RoomEvent::GameTerminated(_)));
+    }
+
+    #[tokio::test]
+    async fn spectator_receives_only_public_events() {
+        let (room_server, mut room_commander) = RoomServer::new(RoomConfig::default());
+        spawn(room_server.run());
+        let mut players = create_n_players(&mut room_commander, 6, true).await;
+        let (_, mut spectator_rx) = room_commander.new_spectator().await;
+        assert!(matches!(
+            get_nth_event(&mut spectator_rx, 1).await,
+            RoomEvent::StateSnapshot(snapshot)
+                if snapshot.phase == Phase::NotStarted && snapshot.players.len() == 6
+        ));
+
+        room_commander.start_game(players[0].0).await.unwrap();
+        for (player_id, _) in players.iter() {
+            room_commander.set_player_ready(*player_id).await.unwrap();
+        }
+        clean_events(&mut players).await;
+        let player_id = next_player_turn(&mut spectator_rx).await;
+        room_commander.draw_card(player_id).await.unwrap();
+
+        let events: Vec<RoomEvent> = std::iter::from_fn(|| spectator_rx.try_recv().ok()).collect();
+        assert!(matches!(events.last(), Some(RoomEvent::CardWasDrawn(id)) if *id == player_id));
+        assert!(events.iter().all(|event| !matches!(
+            event,
+            RoomEvent::DrawnCard(_) | RoomEvent::PeekedCard(_) | RoomEvent::PeekingPhaseStarted(_)
+        )));
+    }
+
+    #[tokio::test]
+    async fn spectators_do_not_count_against_max_players() {
+        let (room_server, mut room_commander) = RoomServer::new(RoomConfig::default());
+        spawn(room_server.run());
+        let _ = room_commander.new_spectator().await;
+        let players = create_n_players(&mut room_commander, 6, true).await;
+        room_commander.start_game(players[0].0).await.unwrap();
+        let (_, mut spectator_rx) = room_commander.new_spectator().await;
+        assert!(matches!(
+            get_nth_event(&mut spectator_rx, 1).await,
+            RoomEvent::StateSnapshot(snapshot)
+                if snapshot.phase == Phase::PeekingPhase
+                    && snapshot.players.iter().all(|player| player.hand_size == 4)
+        ));
+    }
+
+    #[tokio::test]
+    async fn start_game_should_fail_when_not_enough_players() {
+        let (room_server, mut room_commander) = RoomServer::new(RoomConfig::default());
+        spawn(room_server.run());
+        let players = create_n_players(&mut room_commander, 1, false).await;
+        assert!(matches!(
+            room_commander.start_game(players[0].0).await,
+            Err(GameError::NotEnoughPlayers)
+        ));
+    }
+
+    #[tokio::test]
+    async fn start_game() {
+        let (room_server, mut room_commander) = RoomServer::new(RoomConfig::default());
+        spawn(room_server.run());
+        let mut players = create_n_players(&mut room_commander, 6, true).await;
+        room_commander.start_game(players[0].0).await.unwrap();
+
+        for (_, player_rx) in players.iter_mut() {
+            let received_event = get_nth_event(player_rx, 1).await;
+            assert!(matches!(
+                received_event,
+                RoomEvent::PeekingPhaseStarted(peeked) if peeked.len() == 2
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn start_game_with_custom_hand_size() {
+        let config = RoomConfig {
+            hand_size: 6,
+            peeked_cards: 3,
+            ..Default::default()
+        };
+        let (room_server, mut room_commander) = RoomServer::new(config);
+        spawn(room_server.run());
+        let mut players = create_n_players(&mut room_commander, 2, true).await;
+        room_commander.start_game(players[0].0).await.unwrap();
+
+        for (_, player_rx) in players.iter_mut() {
+            let received_event = get_nth_event(player_rx, 1).await;
+            assert!(matches!(
+                received_event,
+                RoomEvent::PeekingPhaseStarted(peeked) if peeked.len() == 3
+            ));
         }
-        Ok(())
     }
 
-    fn validate_player_turn(
-        &self,
-        player_id: PlayerId,
-        stored_player_id: PlayerId,
-    ) -> Result<(), GameError> {
-        if player_id != stored_player_id {
-            return Err(GameError::OperationNotAllowedAtCurrentState);
+    #[tokio::test]
+    async fn same_seed_deals_same_game() {
+        let mut games = vec![];
+        for _ in 0..2 {
+            let config = RoomConfig {
+                seed: Some(42),
+                ..Default::default()
+            };
+            let (room_server, mut room_commander) = RoomServer::new(config);
+            spawn(room_server.run());
+            let players = create_n_players(&mut room_commander, 3, true).await;
+            room_commander.start_game(players[0].0).await.unwrap();
+            let snapshot = room_commander.get_state(players[0].0).await.unwrap();
+            let known_cards: Vec<_> = snapshot
+                .players
+                .into_iter()
+                .map(|player| player.known_cards)
+                .collect();
+            games.push((snapshot.turn_order, known_cards));
         }
-        Ok(())
+        assert!(games[0] == games[1]);
     }
 
-    fn validate_crabul_player(&self, other_player_id: PlayerId) -> Result<(), GameError> {
-        if let Some(crabul_player) = self.crabul_player {
-            if crabul_player == other_player_id {
-                return Err(GameError::OperationNotAllowedAtCurrentState);
+    async fn next_prompt(
+        player_rx: &mut UnboundedReceiver<RoomEvent>,
+    ) -> (Vec<ClientMessage>, u64) {
+        loop {
+            if let RoomEvent::YourMove {
+                actions,
+                remaining_time_ms,
+            } = player_rx.recv().await.unwrap()
+            {
+                return (actions, remaining_time_ms.unwrap());
             }
         }
-        Ok(())
     }
 
-    fn match_power(&self, card: Card) -> Option<Power> {
-        match card {
-            Card::Clubs(n) | Card::Diamonds(n) | Card::Hearts(n) | Card::Spade(n) => match n {
-                n if n < 7 => None,
-                7 | 8 => Some(Power::PeekOwnCard),
-                9 | 10 => Some(Power::PeekOtherCard),
-                11 | 12 => Some(Power::BlindSwap),
-                13 => Some(Power::CheckAndSwapStage1),
-                _ => unreachable!(),
-            },
-            Card::Joker => None,
+    #[tokio::test]
+    async fn bot_seats_are_prompted_and_play_on_a_shorter_clock() {
+        pause();
+        let (room_server, room_commander) = RoomServer::new(RoomConfig::default());
+        spawn(room_server.run());
+        let (player_id, _player_rx) = room_commander.new_player("human".into()).await.unwrap();
+        let (bot_id, mut bot_rx) = room_commander.new_bot("bot".into(), None).await.unwrap();
+        assert!(matches!(
+            bot_rx.recv().await.unwrap(),
+            RoomEvent::PlayerJoined { bot: true, .. }
+        ));
+        let snapshot = room_commander.get_state(player_id).await.unwrap();
+        assert!(snapshot
+            .players
+            .iter()
+            .all(|player| player.bot == (player.player_id == bot_id)));
+
+        room_commander.start_game(player_id).await.unwrap();
+        let (actions, _) = next_prompt(&mut bot_rx).await;
+        assert!(actions == vec![ClientMessage::SetPlayerReady]);
+
+        // The human's turn times out if they play first, the bot gets its own.
+        let (actions, remaining_time_ms) = next_prompt(&mut bot_rx).await;
+        assert!(actions == vec![ClientMessage::DrawCard, ClientMessage::GoCrabul]);
+        assert!(remaining_time_ms <= BOT_TURN_COUNTDOWN.as_millis() as u64);
+
+        room_commander.draw_card(bot_id).await.unwrap();
+        let (actions, _) = next_prompt(&mut bot_rx).await;
+        assert!(actions.len() == HAND_SIZE + 1);
+        assert!(actions.contains(&ClientMessage::SwapCard { card_idx: 0 }));
+        assert!(actions.contains(&ClientMessage::DiscardCard));
+
+        let started = Instant::now();
+        loop {
+            if let RoomEvent::TurnEndedByTimeout(id) = bot_rx.recv().await.unwrap() {
+                assert!(id == bot_id);
+                break;
+            }
         }
+        assert!(started.elapsed() < BOT_TURN_COUNTDOWN + Duration::from_secs(1));
     }
 
-    fn send_to_player(&self, player_id: PlayerId, event: RoomEvent) {
-        let player = self.players.get(&player_id).unwrap();
-        let _ = player.tx.send(event);
-    }
+    #[tokio::test]
+    async fn room_nobody_joins_closes() {
+        pause();
+        let (room_server, room_commander) = RoomServer::new(RoomConfig::default());
+        spawn(room_server.run());
+        let (joined_server, joined_commander) = RoomServer::new(RoomConfig::default());
+        spawn(joined_server.run());
+        let (player_id, _player_rx) = joined_commander.new_player("host".into()).await.unwrap();
 
-    fn send_all_players(&self, event: RoomEvent) {
-        self.players.iter().for_each(|(_, player)| {
-            let _ = player.tx.send(event.clone());
-        });
+        sleep(EMPTY_ROOM_TIMEOUT + Duration::from_secs(1)).await;
+        assert!(matches!(
+            room_commander.get_state(0).await,
+            Err(GameError::RoomClosed)
+        ));
+        assert!(joined_commander.get_state(player_id).await.is_ok());
     }
 
-    async fn peeking_phase_countdown(tx_channel: UnboundedSender<RoomCommand>) {
-        sleep(PEEKING_PHASE_COUNTDOWN).await;
-        let _ = tx_channel.send(RoomCommand::NextTurn);
-    }
+    #[tokio::test]
+    async fn room_closes_when_only_bots_remain() {
+        let (room_server, room_commander) = RoomServer::new(RoomConfig::default());
+        spawn(room_server.run());
+        let (host, _host_rx) = room_commander.new_player("host".into()).await.unwrap();
+        let (bot_id, mut bot_rx) = room_commander.new_bot("bot".into(), None).await.unwrap();
+        let (guest, _guest_rx) = room_commander.new_player("guest".into()).await.unwrap();
+        assert!(matches!(
+            room_commander.transfer_host(host, bot_id).await,
+            Err(GameError::OperationNotAllowedAtCurrentState)
+        ));
 
-    async fn turn_countdown(player_id: PlayerId, tx_channel: UnboundedSender<RoomCommand>) {
-        sleep(TURN_COUNTDOWN).await;
-        let _ = tx_channel.send(RoomCommand::ForceEndTurn(player_id));
-    }
+        room_commander.remove_player(host).await;
+        let snapshot = room_commander.get_state(guest).await.unwrap();
+        assert!(snapshot.host == Some(guest));
 
-    async fn finalize_game_countdown(tx_channel: UnboundedSender<RoomCommand>) {
-        sleep(FINALIZE_GAME_COUNTDOWN).await;
-        let _ = tx_channel.send(RoomCommand::FinalizeGame);
+        room_commander.remove_player(guest).await;
+        loop {
+            match bot_rx.recv().await {
+                Some(RoomEvent::RoomClosed) => break,
+                Some(_) => continue,
+                None => panic!("Bot was not told the room closed"),
+            }
+        }
+        assert!(bot_rx.recv().await.is_none());
+        assert!(matches!(
+            room_commander.get_state(bot_id).await,
+            Err(GameError::RoomClosed)
+        ));
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use std::{ops::Add, time::Duration};
-
-    use tokio::time::pause;
 
-    use crate::deck;
+    #[tokio::test]
+    async fn legal_actions_follow_the_state() {
+        pause();
+        let (mut server, commander, mut players_rxs) = get_basic_server();
+        for player_id in 0..3 {
+            server.players.get_mut(&player_id).unwrap().cards = vec![Card::Clubs(5); 2];
+        }
+        server.crabul_player = Some(2);
+        server.state = State::PowerStage(0, Power::PeekOtherCard);
+        assert!(matches!(
+            server.legal_actions(9),
+            Err(GameError::PlayerNotFound)
+        ));
+        // The crabul player's cards are off limits, the others have none.
+        assert!(
+            server.legal_actions(0).unwrap()
+                == vec![
+                    ClientMessage::PeekOtherCard {
+                        other_player_id: 1,
+                        other_card_idx: 0
+                    },
+                    ClientMessage::PeekOtherCard {
+                        other_player_id: 1,
+                        other_card_idx: 1
+                    },
+                ]
+        );
+        assert!(server.legal_actions(1).unwrap().is_empty());
+
+        server.deck.discard(Card::Hearts(5));
+        let throws = server.legal_actions(1).unwrap();
+        assert!(throws.len() == 6);
+        assert!(throws
+            .iter()
+            .all(|action| matches!(action, ClientMessage::ThrowSameCard { .. })));
+        server.duplicate_card_thrown = true;
+        assert!(server.legal_actions(1).unwrap().is_empty());
+        spawn(server.run());
 
-    use super::*;
+        commander.peek_other_card(0, 1, 0).await.unwrap();
+        // Crabul was called already, and throwing opens again with the new turn.
+        let actions = commander.get_legal_actions(1).await.unwrap();
+        assert!(actions.len() == 7 && actions[0] == ClientMessage::DrawCard);
+        let mut pushed = None;
+        while let Ok(event) = players_rxs[1].try_recv() {
+            if let RoomEvent::LegalActions(actions) = event {
+                pushed = Some(actions);
+            }
+        }
+        assert!(pushed == Some(actions));
+
+        // A duplicate thrown from one's own hand leaves the state as it was,
+        // yet closes the throwing for everybody.
+        while players_rxs[0].try_recv().is_ok() {}
+        commander.throw_same_card(0, 0, 0).await.unwrap();
+        let mut pushed = None;
+        while let Ok(event) = players_rxs[0].try_recv() {
+            if let RoomEvent::LegalActions(actions) = event {
+                pushed = Some(actions);
+            }
+        }
+        assert!(pushed == Some(vec![]));
+    }
 
     #[tokio::test]
-    async fn new_player() {
-        let (room_server, mut room_commander) = RoomServer::new();
+    async fn finished_game_can_be_replayed() {
+        tokio::time::pause();
+        let config = RoomConfig {
+            seed: Some(7),
+            finalize_game_countdown: Duration::from_secs(1),
+            ..Default::default()
+        };
+        let (mut room_server, mut room_commander) = RoomServer::with_id(7, config);
+        let mut notifications = room_server.notifications();
         spawn(room_server.run());
-        let mut players = create_n_players(&mut room_commander, 1, false).await;
+        let players = create_n_players(&mut room_commander, 2, true).await;
+        room_commander.start_game(players[0].0).await.unwrap();
+        for (player_id, _) in players.iter() {
+            room_commander.set_player_ready(*player_id).await.unwrap();
+        }
 
-        let received_event = get_nth_event(&mut players[0].1, 1).await;
+        let snapshot = room_commander.get_state(players[0].0).await.unwrap();
+        let Phase::StartTurn(first_player) = snapshot.phase else {
+            panic!("Game did not start");
+        };
+        let second_player = players
+            .iter()
+            .map(|(player_id, _)| *player_id)
+            .find(|player_id| *player_id != first_player)
+            .unwrap();
+        room_commander.go_crabul(first_player).await.unwrap();
+        room_commander.draw_card(second_player).await.unwrap();
+        room_commander.swap_card(second_player, 0).await.unwrap();
+        sleep(Duration::from_secs(2)).await;
 
-        if let RoomEvent::PlayerJoined {
-            room_id: _,
-            player_id,
-            player_name,
-            player_list,
-        } = received_event
-        {
-            assert!(player_id == players[0].0);
-            assert!(player_name == "name_0");
-            assert!(player_list == HashMap::from([(players[0].0, "name_0".into())]));
-        }
+        let Some(RoomNotification::GameFinished(record)) = notifications.recv().await else {
+            panic!("Did not receive the game record");
+        };
+        assert!(record.seed == 7);
+        assert!(record
+            .entries
+            .iter()
+            .any(|entry| matches!(entry.kind, RecordKind::Hands(_))));
+        assert!(!record
+            .events()
+            .any(|(_, event)| matches!(event, RoomEvent::LegalActions(_))));
+
+        let summary = GameSummary::from_record(&record).unwrap();
+        assert!(summary.players.len() == 2);
+        assert!(summary.rounds.len() == 1);
+        assert!(summary.winner == summary.final_score.winner);
+
+        let stats = StatsAggregator::from_record(&record).finish();
+        assert!(stats.len() == 2);
+        assert!(stats
+            .values()
+            .all(|stats| stats.games == 1 && stats.hands == 1));
+
+        // Replays leave no countdown behind.
+        let alive_tasks = || Handle::current().metrics().num_alive_tasks();
+        let before_replay = alive_tasks();
+        let replayed = RoomServer::replay(&record).await;
+        yield_now().await;
+        assert!(alive_tasks() == before_replay);
+        let events = |record: &GameRecord| -> Vec<String> {
+            record
+                .events()
+                .map(|event| serde_json::to_string(&event).unwrap())
+                .collect()
+        };
+        assert!(events(&record) == events(&replayed));
+        assert!(replayed.events().any(|(_, event)| matches!(
+            event,
+            RoomEvent::GameTerminated(score) if score.game_id == record.game_id
+        )));
     }
 
     #[tokio::test]
-    async fn new_player_previous_player_should_receive_the_join_event() {
-        let (room_server, mut room_commander) = RoomServer::new();
+    async fn only_host_can_start_game() {
+        let (room_server, mut room_commander) = RoomServer::new(RoomConfig::default());
         spawn(room_server.run());
-        let mut players = create_n_players(&mut room_commander, 2, false).await;
-
-        let received_event = get_nth_event(&mut players[0].1, 2).await;
-        if let RoomEvent::PlayerJoined {
-            room_id: _,
-            player_id,
-            player_name,
-            player_list,
-        } = received_event
-        {
-            assert!(player_id == players[1].0);
-            assert!(player_name == "name_1");
-            assert!(
-                player_list
-                    == HashMap::from([
-                        (players[0].0, "name_0".into()),
-                        (players[1].0, "name_1".into())
-                    ])
-            );
-        }
+        let players = create_n_players(&mut room_commander, 2, true).await;
+        assert!(matches!(
+            room_commander.start_game(players[1].0).await,
+            Err(GameError::NotHost)
+        ));
+        room_commander.start_game(players[0].0).await.unwrap();
     }
 
     #[tokio::test]
-    async fn new_player_should_fail_when_name_exists() {
-        let (room_server, room_commander) = RoomServer::new();
+    async fn game_starts_once_everyone_is_ready_in_lobby() {
+        pause();
+        let (room_server, mut room_commander) = RoomServer::new(RoomConfig::default());
         spawn(room_server.run());
-        let (player_name_1, player_name_2) = ("name1", "name1");
-        let _ = room_commander
-            .new_player(player_name_1.into())
+        let mut players = create_n_players(&mut room_commander, 2, true).await;
+        room_commander
+            .set_lobby_ready(players[0].0, true)
+            .await
+            .unwrap();
+        room_commander
+            .set_lobby_ready(players[1].0, true)
             .await
             .unwrap();
-        let res = room_commander.new_player(player_name_2.into()).await;
 
-        assert!(matches!(res, Err(GameError::NameAlreadyExists)));
+        let received_event = get_nth_event(&mut players[0].1, 1).await;
+        assert!(
+            matches!(received_event, RoomEvent::LobbyReadyChanged(id, true) if id == players[0].0)
+        );
+        let received_event = get_nth_event(&mut players[0].1, 2).await;
+        assert!(matches!(received_event, RoomEvent::AutoStartScheduled(10)));
+
+        sleep(AUTO_START_COUNTDOWN + Duration::from_secs(1)).await;
+        let received_event = get_nth_event(&mut players[0].1, 1).await;
+        assert!(matches!(received_event, RoomEvent::PeekingPhaseStarted(_)));
+        let snapshot = room_commander.get_state(players[0].0).await.unwrap();
+        assert!(matches!(snapshot.phase, Phase::PeekingPhase));
+        assert!(snapshot.players.iter().all(|player| !player.ready));
     }
 
     #[tokio::test]
-    async fn new_player_should_fail_when_there_are_too_many_players() {
-        let (room_server, mut room_commander) = RoomServer::new();
+    async fn auto_start_is_cancelled_when_someone_unreadies_or_joins() {
+        pause();
+        let (room_server, mut room_commander) = RoomServer::new(RoomConfig::default());
         spawn(room_server.run());
-        create_n_players(&mut room_commander, 6, false).await;
-        let res = room_commander.new_player("name_7".into()).await;
+        let mut players = create_n_players(&mut room_commander, 2, true).await;
+        for (player_id, _) in players.iter() {
+            room_commander
+                .set_lobby_ready(*player_id, true)
+                .await
+                .unwrap();
+        }
+        room_commander
+            .set_lobby_ready(players[1].0, false)
+            .await
+            .unwrap();
+        let received_event = get_nth_event(&mut players[0].1, 5).await;
+        assert!(matches!(received_event, RoomEvent::AutoStartCancelled));
 
-        assert!(matches!(res, Err(GameError::TooManyPlayers)));
+        room_commander
+            .set_lobby_ready(players[1].0, true)
+            .await
+            .unwrap();
+        room_commander.new_player("late".to_string()).await.unwrap();
+        let received_event = get_nth_event(&mut players[0].1, 4).await;
+        assert!(matches!(received_event, RoomEvent::AutoStartCancelled));
+
+        sleep(AUTO_START_COUNTDOWN + Duration::from_secs(1)).await;
+        let snapshot = room_commander.get_state(players[0].0).await.unwrap();
+        assert!(matches!(snapshot.phase, Phase::NotStarted));
     }
 
     #[tokio::test]
-    async fn remove_player() {
-        let (room_server, mut room_commander) = RoomServer::new();
+    async fn host_kicks_player_in_lobby() {
+        let (room_server, mut room_commander) = RoomServer::new(RoomConfig::default());
         spawn(room_server.run());
-        let mut players = create_n_players(&mut room_commander, 3, false).await;
-        room_commander.remove_player(players[2].0).await;
-
-        let received_event = get_nth_event(&mut players[0].1, 4).await;
-        assert!(
-            matches!(received_event, RoomEvent::PlayerLeft(received_player_id) if received_player_id == players[2].0)
-        );
+        let mut players = create_n_players(&mut room_commander, 3, true).await;
+        let (host, kicked) = (players[0].0, players[2].0);
+        assert!(matches!(
+            room_commander.kick_player(players[1].0, kicked).await,
+            Err(GameError::NotHost)
+        ));
+        room_commander.kick_player(host, kicked).await.unwrap();
 
-        let received_event = get_nth_event(&mut players[1].1, 3).await;
-        assert!(
-            matches!(received_event, RoomEvent::PlayerLeft(received_player_id) if received_player_id == players[2].0)
-        );
+        for (_, player_rx) in players.iter_mut() {
+            let received_event = get_nth_event(player_rx, 1).await;
+            assert!(matches!(received_event, RoomEvent::PlayerKicked(id) if id == kicked));
+        }
+        assert!(matches!(
+            players[2].1.try_recv(),
+            Err(mpsc::error::TryRecvError::Disconnected)
+        ));
+        let snapshot = room_commander.get_state(host).await.unwrap();
+        assert!(snapshot.players.len() == 2);
     }
 
     #[tokio::test]
-    async fn start_game_should_fail_when_not_enough_players() {
-        let (room_server, mut room_commander) = RoomServer::new();
+    async fn only_host_adds_bots() {
+        let (room_server, mut room_commander) = RoomServer::new(RoomConfig::default());
         spawn(room_server.run());
-        create_n_players(&mut room_commander, 1, false).await;
+        let players = create_n_players(&mut room_commander, 2, true).await;
         assert!(matches!(
-            room_commander.start_game().await,
-            Err(GameError::NotEnoughPlayers)
+            room_commander.add_bot(players[1].0, "bot".into()).await,
+            Err(GameError::NotHost)
         ));
+        let (bot_id, _bot_rx) = room_commander
+            .add_bot(players[0].0, "bot".into())
+            .await
+            .unwrap();
+
+        let snapshot = room_commander.get_state(players[0].0).await.unwrap();
+        assert!(snapshot
+            .players
+            .iter()
+            .any(|player| player.player_id == bot_id && player.bot));
     }
 
     #[tokio::test]
-    async fn start_game() {
-        let (room_server, mut room_commander) = RoomServer::new();
+    async fn transfer_and_migrate_host() {
+        let (room_server, mut room_commander) = RoomServer::new(RoomConfig::default());
         spawn(room_server.run());
-        let mut players = create_n_players(&mut room_commander, 6, true).await;
-        room_commander.start_game().await.unwrap();
+        let mut players = create_n_players(&mut room_commander, 3, true).await;
+        let (first, second) = (players[0].0, players[1].0);
 
-        for (_, player_rx) in players.iter_mut() {
-            let received_event = get_nth_event(player_rx, 1).await;
-            assert!(matches!(
-                received_event,
-                RoomEvent::PeekingPhaseStarted((_, _))
-            ));
-        }
+        room_commander.transfer_host(first, second).await.unwrap();
+        let received_event = get_nth_event(&mut players[2].1, 1).await;
+        assert!(matches!(received_event, RoomEvent::HostChanged(id) if id == second));
+        assert!(matches!(
+            room_commander.start_game(first).await,
+            Err(GameError::NotHost)
+        ));
+
+        room_commander.remove_player(second).await;
+        let received_event = get_nth_event(&mut players[2].1, 1).await;
+        assert!(matches!(received_event, RoomEvent::PlayerLeft(id) if id == second));
+        let received_event = get_nth_event(&mut players[2].1, 1).await;
+        let RoomEvent::HostChanged(new_host) = received_event else {
+            panic!("Host did not change");
+        };
+        assert!(new_host != second);
+        let snapshot = room_commander.get_state(first).await.unwrap();
+        assert!(snapshot.host == Some(new_host));
     }
 
     #[tokio::test]
     async fn cannot_start_game_if_state_different_from_not_started() {
-        let (room_server, mut room_commander) = RoomServer::new();
+        let (room_server, mut room_commander) = RoomServer::new(RoomConfig::default());
         spawn(room_server.run());
-        create_n_players(&mut room_commander, 6, true).await;
-        room_commander.start_game().await.unwrap();
+        let players = create_n_players(&mut room_commander, 6, true).await;
+        room_commander.start_game(players[0].0).await.unwrap();
         assert!(matches!(
-            room_commander.start_game().await,
+            room_commander.start_game(players[0].0).await,
             Err(GameError::OperationNotAllowedAtCurrentState)
         ));
     }
 
     #[tokio::test]
     async fn new_player_should_fail_when_game_started() {
-        let (room_server, mut room_commander) = RoomServer::new();
+        let (room_server, mut room_commander) = RoomServer::new(RoomConfig::default());
         spawn(room_server.run());
-        create_n_players(&mut room_commander, 6, true).await;
-        room_commander.start_game().await.unwrap();
+        let players = create_n_players(&mut room_commander, 6, true).await;
+        room_commander.start_game(players[0].0).await.unwrap();
 
         assert!(matches!(
             room_commander.new_player("test".into()).await,
@@ -1087,10 +3148,10 @@ mod tests {
 
     #[tokio::test]
     async fn start_turn_when_everyone_is_ready() {
-        let (room_server, mut room_commander) = RoomServer::new();
+        let (room_server, mut room_commander) = RoomServer::new(RoomConfig::default());
         spawn(room_server.run());
         let mut players = create_n_players(&mut room_commander, 6, true).await;
-        room_commander.start_game().await.unwrap();
+        room_commander.start_game(players[0].0).await.unwrap();
 
         clean_events(&mut players).await;
 
@@ -1109,10 +3170,10 @@ mod tests {
     #[tokio::test]
     async fn cannot_set_ready_when_stage_is_not_peeking_phase() {
         pause();
-        let (room_server, mut room_commander) = RoomServer::new();
+        let (room_server, mut room_commander) = RoomServer::new(RoomConfig::default());
         spawn(room_server.run());
         let mut players = create_n_players(&mut room_commander, 6, true).await;
-        room_commander.start_game().await.unwrap();
+        room_commander.start_game(players[0].0).await.unwrap();
 
         clean_events(&mut players).await;
         sleep(PEEKING_PHASE_COUNTDOWN).await;
@@ -1128,10 +3189,10 @@ mod tests {
     #[tokio::test]
     async fn automatic_start_turn_after_timeout() {
         pause();
-        let (room_server, mut room_commander) = RoomServer::new();
+        let (room_server, mut room_commander) = RoomServer::new(RoomConfig::default());
         spawn(room_server.run());
         let mut players = create_n_players(&mut room_commander, 6, true).await;
-        room_commander.start_game().await.unwrap();
+        room_commander.start_game(players[0].0).await.unwrap();
 
         clean_events(&mut players).await;
         sleep(PEEKING_PHASE_COUNTDOWN).await;
@@ -1146,10 +3207,10 @@ mod tests {
     #[tokio::test]
     async fn draw_card() {
         pause();
-        let (room_server, mut room_commander) = RoomServer::new();
+        let (room_server, mut room_commander) = RoomServer::new(RoomConfig::default());
         spawn(room_server.run());
         let mut players = create_n_players(&mut room_commander, 6, true).await;
-        room_commander.start_game().await.unwrap();
+        room_commander.start_game(players[0].0).await.unwrap();
 
         clean_events(&mut players).await;
         sleep(PEEKING_PHASE_COUNTDOWN).await;
@@ -1173,17 +3234,17 @@ mod tests {
     #[tokio::test]
     async fn swap_card() {
         pause();
-        let (room_server, mut room_commander) = RoomServer::new();
+        let (room_server, mut room_commander) = RoomServer::new(RoomConfig::default());
         spawn(room_server.run());
         let mut players = create_n_players(&mut room_commander, 6, true).await;
-        room_commander.start_game().await.unwrap();
+        room_commander.start_game(players[0].0).await.unwrap();
 
         let peeked_cards: Vec<(Card, Card)> = players
             .iter_mut()
             .map(|(_, player)| {
                 let peeked = player.try_recv().unwrap();
-                if let RoomEvent::PeekingPhaseStarted((card1, card2)) = peeked {
-                    (card1, card2)
+                if let RoomEvent::PeekingPhaseStarted(peeked) = peeked {
+                    (peeked[0], peeked[1])
                 } else {
                     panic!("Did not return peeking phase event")
                 }
@@ -1232,7 +3293,7 @@ mod tests {
     async fn discard_card_normal_card() {
         let drawn_card = Card::Diamonds(1);
         let state = State::MiddleTurn(0, drawn_card);
-        let deck = Deck::new();
+        let deck = Deck::default();
         let cards = vec![
             Card::Clubs(1),
             Card::Clubs(2),
@@ -1260,7 +3321,7 @@ mod tests {
     async fn discard_power() {
         let drawn_card = Card::Diamonds(7);
         let state = State::MiddleTurn(0, drawn_card);
-        let deck = Deck::new();
+        let deck = Deck::default();
         let cards = vec![
             Card::Clubs(1),
             Card::Clubs(2),
@@ -1287,7 +3348,7 @@ mod tests {
     #[tokio::test]
     async fn use_power_peek_own_card() {
         let state = State::PowerStage(0, Power::PeekOwnCard);
-        let deck = Deck::new();
+        let deck = Deck::default();
         let cards = vec![
             Card::Clubs(1),
             Card::Clubs(2),
@@ -1320,7 +3381,7 @@ mod tests {
     #[tokio::test]
     async fn use_power_peek_other_card() {
         let state = State::PowerStage(1, Power::PeekOtherCard);
-        let deck = Deck::new();
+        let deck = Deck::default();
         let cards = vec![
             Card::Clubs(1),
             Card::Clubs(2),
@@ -1350,10 +3411,63 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn get_state_shows_only_known_cards() {
+        let state = State::PowerStage(1, Power::PeekOtherCard);
+        let deck = Deck::default();
+        let cards = vec![
+            Card::Clubs(1),
+            Card::Clubs(2),
+            Card::Clubs(3),
+            Card::Clubs(4),
+        ];
+
+        let (room_commander, _players_rxs) =
+            init_specific_game_room(1, state, deck, cards.clone(), None);
+
+        room_commander.peek_other_card(1, 0, 3).await.unwrap();
+
+        let snapshot = room_commander.get_state(1).await.unwrap();
+        assert!(snapshot.phase == Phase::StartTurn(2));
+        assert!(snapshot.turn_order == vec![0, 1, 2, 3, 4, 5]);
+        assert!(snapshot.remaining_time_ms.is_some());
+        let player_0 = &snapshot.players[0];
+        assert!(player_0.hand_size == 4);
+        assert!(player_0.known_cards == vec![None, None, None, Some(cards[3])]);
+
+        let snapshot = room_commander.get_state(0).await.unwrap();
+        assert!(snapshot.players[0].known_cards.iter().all(Option::is_none));
+
+        assert!(matches!(
+            room_commander.get_state(42).await,
+            Err(GameError::PlayerNotFound)
+        ));
+    }
+
+    #[tokio::test]
+    async fn known_cards_follow_blind_swap() {
+        let state = State::PowerStage(0, Power::BlindSwap);
+        let deck = Deck::default();
+        let cards = vec![Card::Clubs(1), Card::Clubs(2)];
+        let other_cards = vec![Card::Diamonds(1), Card::Diamonds(2)];
+
+        let (room_commander, _players_rxs) =
+            init_specific_game_room(0, state, deck, cards, Some(other_cards));
+
+        room_commander.blind_swap(0, 0, 1, 1).await.unwrap();
+        room_commander.draw_card(1).await.unwrap();
+        room_commander.discard_card(1).await.unwrap();
+
+        // Nobody has seen the swapped cards, the owner still knows nothing.
+        let snapshot = room_commander.get_state(1).await.unwrap();
+        assert!(snapshot.players[1].known_cards == vec![None, None]);
+        assert!(snapshot.discard_pile_top.is_some());
+    }
+
     #[tokio::test]
     async fn use_power_blind_swap() {
         let state = State::PowerStage(0, Power::BlindSwap);
-        let deck = Deck::new();
+        let deck = Deck::default();
         let cards_1 = vec![
             Card::Clubs(1),
             Card::Clubs(2),
@@ -1398,7 +3512,7 @@ mod tests {
     #[tokio::test]
     async fn use_power_check_and_swap_stage1() {
         let state = State::PowerStage(0, Power::CheckAndSwapStage1);
-        let deck = Deck::new();
+        let deck = Deck::default();
         let cards_1 = vec![
             Card::Clubs(1),
             Card::Clubs(2),
@@ -1435,7 +3549,7 @@ mod tests {
     #[tokio::test]
     async fn use_power_check_and_swap_decide_to_swap() {
         let state = State::PowerStage(0, Power::CheckAndSwapStage2(1, 3));
-        let deck = Deck::new();
+        let deck = Deck::default();
         let cards_1 = vec![
             Card::Clubs(1),
             Card::Clubs(2),
@@ -1489,7 +3603,7 @@ mod tests {
     #[tokio::test]
     async fn use_power_check_and_swap_decide_to_not_swap() {
         let state = State::PowerStage(0, Power::CheckAndSwapStage2(1, 3));
-        let deck = Deck::new();
+        let deck = Deck::default();
         let cards_1 = vec![
             Card::Clubs(1),
             Card::Clubs(2),
@@ -1622,7 +3736,7 @@ mod tests {
     #[tokio::test]
     async fn throw_same_card_penalty_when_is_not_the_same() {
         let state = State::StartTurn(5);
-        let mut deck = Deck::new();
+        let mut deck = Deck::default();
         deck.discard(Card::Hearts(1));
         let cards_1 = vec![
             Card::Clubs(5),
@@ -1883,14 +3997,12 @@ mod tests {
     #[tokio::test]
     async fn turn_timeout() {
         pause();
-        let (mut server, commander, mut players_rxs) = get_basic_server();
+        let (mut server, commander, mut players_rxs) = get_seeded_server(Some(2));
         for (_, player) in server.players.iter_mut() {
             player
                 .cards
                 .extend_from_slice(&[Card::Clubs(10), Card::Clubs(10)]);
         }
-        let special_deck = deck::testing_deck(vec![Card::Clubs(2), Card::Clubs(3)]);
-        server.deck = special_deck;
         server.current_player_idx = 0;
         server.state = State::StartTurn(0);
         server.draw_card(0).unwrap();
@@ -1911,7 +4023,7 @@ mod tests {
             let received_event = get_nth_event(player_rx, 1).await;
             assert!(matches!(
                 received_event,
-                RoomEvent::CardDiscarded(1, Card::Clubs(2))
+                RoomEvent::CardDiscarded(1, card) if card.get_value() == Some(4)
             ));
             let received_event = get_nth_event(player_rx, 1).await;
             assert!(matches!(received_event, RoomEvent::PlayerTurn(2)));
@@ -1921,14 +4033,12 @@ mod tests {
     #[tokio::test]
     async fn turn_timeout_when_card_has_power() {
         pause();
-        let (mut server, commander, mut players_rxs) = get_basic_server();
+        let (mut server, commander, mut players_rxs) = get_seeded_server(Some(4));
         for (_, player) in server.players.iter_mut() {
             player
                 .cards
                 .extend_from_slice(&[Card::Clubs(10), Card::Clubs(10)]);
         }
-        let special_deck = deck::testing_deck(vec![Card::Clubs(7), Card::Clubs(3)]);
-        server.deck = special_deck;
         server.current_player_idx = 0;
         server.state = State::StartTurn(0);
         server.draw_card(0).unwrap();
@@ -1949,7 +4059,7 @@ mod tests {
             let received_event = get_nth_event(player_rx, 1).await;
             assert!(matches!(
                 received_event,
-                RoomEvent::CardDiscarded(1, Card::Clubs(7))
+                RoomEvent::CardDiscarded(1, card) if card.get_value() == Some(8)
             ));
             let received_event = get_nth_event(player_rx, 1).await;
             assert!(matches!(
@@ -1964,14 +4074,12 @@ mod tests {
     #[tokio::test]
     async fn turn_timeout_when_card_has_power_and_power_is_blind_swap() {
         pause();
-        let (mut server, commander, mut players_rxs) = get_basic_server();
+        let (mut server, commander, mut players_rxs) = get_seeded_server(Some(9));
         for (_, player) in server.players.iter_mut() {
             player
                 .cards
                 .extend_from_slice(&[Card::Clubs(10), Card::Clubs(10)]);
         }
-        let special_deck = deck::testing_deck(vec![Card::Clubs(11), Card::Clubs(3)]);
-        server.deck = special_deck;
         server.current_player_idx = 0;
         server.state = State::StartTurn(0);
         server.draw_card(0).unwrap();
@@ -1992,7 +4100,7 @@ mod tests {
             let received_event = get_nth_event(player_rx, 1).await;
             assert!(matches!(
                 received_event,
-                RoomEvent::CardDiscarded(1, Card::Clubs(11))
+                RoomEvent::CardDiscarded(1, card) if card.get_value() == Some(11)
             ));
             let received_event = get_nth_event(player_rx, 1).await;
             assert!(matches!(received_event, RoomEvent::ForcedBlindSwap(1, ..)));
@@ -2001,12 +4109,82 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn failed_resumes_are_not_recorded() {
+        let (mut server, _commander, _players_rxs) = get_basic_server();
+        assert!(matches!(
+            server.resume_player("token_0".into()),
+            Err(GameError::PlayerAlreadyConnected)
+        ));
+        assert!(!server
+            .take_record()
+            .commands()
+            .any(|cmd| matches!(cmd, RecordedCommand::ResumePlayer(_))));
+    }
+
+    #[tokio::test]
+    async fn penalty_cards_can_be_thrown() {
+        let (mut server, commander, mut players_rxs) = get_basic_server();
+        for player in server.players.values_mut() {
+            player.cards = vec![Card::Clubs(2)];
+        }
+        server.deck.discard(Card::Hearts(5));
+        server.state = State::StartTurn(0);
+        spawn(server.run());
+
+        commander.throw_same_card(1, 1, 0).await.unwrap();
+        let mut pushed = None;
+        while let Ok(event) = players_rxs[1].try_recv() {
+            if let RoomEvent::LegalActions(actions) = event {
+                pushed = Some(actions);
+            }
+        }
+        assert!(
+            pushed.is_some_and(|actions| actions.contains(&ClientMessage::ThrowSameCard {
+                picked_player_id: 1,
+                picked_card_idx: 1,
+            }))
+        );
+    }
+
+    #[tokio::test]
+    async fn long_hands_of_kings_are_scored_in_full() {
+        let (mut server, _commander, mut players_rxs) = get_basic_server();
+        for (player_id, player) in server.players.iter_mut() {
+            let hand_size = if *player_id == 0 { 11 } else { 1 };
+            player.cards = vec![Card::Clubs(13); hand_size];
+        }
+        server.finalize_game();
+
+        let terminated = std::iter::from_fn(|| players_rxs[1].try_recv().ok())
+            .find_map(|event| match event {
+                RoomEvent::GameTerminated(score) => Some(score),
+                _ => None,
+            })
+            .unwrap();
+        let last = terminated.scores.last().unwrap();
+        assert!(last.player_id == 0 && last.total_score == 143);
+        assert!(terminated.winner != 0);
+    }
+
     // UTILS
+    async fn next_player_turn(rx: &mut UnboundedReceiver<RoomEvent>) -> PlayerId {
+        while let Ok(event) = rx.try_recv() {
+            if let RoomEvent::PlayerTurn(player_id) = event {
+                return player_id;
+            }
+        }
+        panic!("Did not return PlayerTurn event")
+    }
+
+    /// Skips the legal actions pushed at each state change.
     async fn get_nth_event(rcv: &mut UnboundedReceiver<RoomEvent>, nth: u8) -> RoomEvent {
+        let mut events = std::iter::from_fn(|| rcv.try_recv().ok())
+            .filter(|event| !matches!(event, RoomEvent::LegalActions(_)));
         for _ in 1..nth {
-            rcv.try_recv().unwrap();
+            events.next().unwrap();
         }
-        rcv.try_recv().unwrap()
+        events.next().unwrap()
     }
 
     async fn create_n_players(
@@ -2041,9 +4219,7 @@ mod tests {
         current_player_cards: Vec<Card>,
         other_player_cards: Option<Vec<Card>>,
     ) -> (RoomCommander, Vec<UnboundedReceiver<RoomEvent>>) {
-        let (tx_channel, rx_channel) = mpsc::unbounded_channel();
-
-        let mut players = HashMap::new();
+        let mut players = BTreeMap::new();
 
         let mut players_rxs = vec![];
 
@@ -2063,23 +4239,16 @@ mod tests {
             players_rxs.push(player_rx);
         }
 
-        let room_server = RoomServer {
-            id: thread_rng().gen::<RoomId>(),
-            tx_channel: tx_channel.clone(),
-            rx_channel,
-            players,
-            deck,
-            state,
-            duplicate_card_thrown: false,
-            current_player_idx,
-            crabul_player: None,
-            current_count_down: None,
-            turn_order: HashMap::from([(0, 0), (1, 1), (2, 2), (3, 3), (4, 4), (5, 5)]),
-        };
+        let (mut room_server, room_commander) = RoomServer::new(RoomConfig::default());
+        room_server.players = players;
+        room_server.deck = deck;
+        room_server.state = state;
+        room_server.current_player_idx = current_player_idx;
+        room_server.turn_order = vec![0, 1, 2, 3, 4, 5];
 
         spawn(room_server.run());
 
-        (RoomCommander::new(tx_channel), players_rxs)
+        (room_commander, players_rxs)
     }
 
     fn init_specific_player(
@@ -2090,16 +4259,34 @@ mod tests {
         (
             Player {
                 name: format!("Player_{id}"),
+                identity: None,
+                bot: false,
                 tx: tx_channel,
                 cards,
+                known_by: vec![],
                 ready: true,
+                reconnect_token: format!("token_{id}"),
+                parked_channel: None,
+                reconnect_countdown: None,
             },
             rx_channel,
         )
     }
 
     fn get_basic_server() -> (RoomServer, RoomCommander, Vec<UnboundedReceiver<RoomEvent>>) {
-        let (mut server, commander) = RoomServer::new();
+        get_seeded_server(None)
+    }
+
+    /// Cards come off the end of the deck. Seed 2 draws a 2 then a 4, seed 4
+    /// a 10 then an 8, seed 9 two jacks.
+    fn get_seeded_server(
+        seed: Option<u64>,
+    ) -> (RoomServer, RoomCommander, Vec<UnboundedReceiver<RoomEvent>>) {
+        let config = RoomConfig {
+            seed,
+            ..Default::default()
+        };
+        let (mut server, commander) = RoomServer::new(config);
         let mut player_rxs = vec![];
 
         for i in 0..6 {
@@ -2108,12 +4295,18 @@ mod tests {
                 i,
                 Player {
                     name: format!("p{i}"),
+                    identity: None,
+                    bot: false,
                     tx,
                     cards: vec![],
+                    known_by: vec![],
                     ready: true,
+                    reconnect_token: format!("token_{i}"),
+                    parked_channel: None,
+                    reconnect_countdown: None,
                 },
             );
-            server.turn_order.insert(i as usize, i);
+            server.turn_order.push(i);
             player_rxs.push(rx);
         }
 
@@ -0,0 +1,76 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    consts::{Identity, PlayerId, PlayerName, RoomCode, RoomId},
+    deck::Card,
+    rating::Rating,
+};
+
+use super::{
+    config::duration_secs,
+    server::{Power, Standing},
+};
+
+/// Public counterpart of `State`: it never exposes the drawn card.
+#[derive(Deserialize, Serialize, Clone, PartialEq)]
+pub enum Phase {
+    NotStarted,
+    PeekingPhase,
+    StartTurn(PlayerId),
+    MiddleTurn(PlayerId),
+    PowerStage(PlayerId, Power),
+    PauseForDuplicateCardThrow(PlayerId, PlayerId, usize),
+    Terminating,
+    Terminated,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+pub struct PlayerSnapshot {
+    pub player_id: PlayerId,
+    pub name: PlayerName,
+    pub identity: Option<Identity>,
+    pub rating: Option<Rating>,
+    pub bot: bool,
+    pub hand_size: usize,
+    /// Cards of this hand the viewer has seen, by position.
+    pub known_cards: Vec<Option<Card>>,
+    pub ready: bool,
+    pub connected: bool,
+}
+
+/// What the room browser shows about a room, nothing about the cards.
+#[derive(Deserialize, Serialize, Clone)]
+pub struct RoomSummary {
+    pub room_id: RoomId,
+    pub room_code: RoomCode,
+    pub phase: Phase,
+    pub players: usize,
+    /// In seating order.
+    pub player_names: Vec<PlayerName>,
+    pub capacity: usize,
+    pub host_name: Option<PlayerName>,
+    pub hand_size: usize,
+    pub score_limit: Option<i32>,
+    #[serde(with = "duration_secs")]
+    pub turn_countdown: Duration,
+}
+
+/// Picture of a room as seen by one viewer, enough to rebuild a client from scratch.
+#[derive(Deserialize, Serialize, Clone)]
+pub struct GameSnapshot {
+    pub room_id: RoomId,
+    pub phase: Phase,
+    pub host: Option<PlayerId>,
+    pub turn_order: Vec<PlayerId>,
+    pub players: Vec<PlayerSnapshot>,
+    pub discard_pile_top: Option<Card>,
+    pub draw_pile_size: usize,
+    pub crabul_player: Option<PlayerId>,
+    pub drawn_card: Option<Card>,
+    pub remaining_time_ms: Option<u64>,
+    pub round: u32,
+    /// Empty unless the room plays a match.
+    pub standings: Vec<Standing>,
+}
@@ -0,0 +1,77 @@
+use crate::consts::{RoomCode, RoomId};
+
+/// One word per byte of the room id, so every id has exactly one code.
+#[rustfmt::skip]
+const WORDS: [&str; 256] = [
+    "CRAB", "TIDE", "REEF", "SAND", "WAVE", "SHELL", "CLAW", "KELP",
+    "SALT", "FOAM", "DUNE", "COVE", "GULL", "PIER", "MAST", "SAIL",
+    "BUOY", "DOCK", "HULL", "KEEL", "OAR", "ROPE", "NET", "HOOK",
+    "BAIT", "FISH", "CARP", "COD", "EEL", "TUNA", "SEAL", "ORCA",
+    "SQUID", "CLAM", "PEARL", "CORAL", "ISLE", "BAY", "CAPE", "GULF",
+    "LAGOON", "DELTA", "FJORD", "MARSH", "PORT", "HARBOR", "BEACH", "SHORE",
+    "STORM", "RAIN", "MIST", "FOG", "GALE", "SQUALL", "BREEZE", "GUST",
+    "CLOUD", "SUN", "MOON", "STAR", "COMET", "ORBIT", "NOVA", "DAWN",
+    "DUSK", "NOON", "NIGHT", "TWILIGHT", "FROST", "SNOW", "ICE", "HAIL",
+    "FLAME", "EMBER", "ASH", "SMOKE", "SPARK", "BLAZE", "TORCH", "LAMP",
+    "CANDLE", "WICK", "ROSE", "LILY", "IRIS", "FERN", "MOSS", "OAK",
+    "PINE", "ELM", "BIRCH", "ASPEN", "CEDAR", "MAPLE", "WILLOW", "HAZEL",
+    "ACORN", "SEED", "ROOT", "LEAF", "BARK", "BRANCH", "TWIG", "BLOOM",
+    "PETAL", "THORN", "VINE", "REED", "RUSH", "SAGE", "MINT", "BASIL",
+    "THYME", "CLOVE", "PEPPER", "HONEY", "SUGAR", "BREAD", "CAKE", "PIE",
+    "JAM", "TEA", "COCOA", "LEMON", "LIME", "PLUM", "PEACH", "PEAR",
+    "APPLE", "MANGO", "KIWI", "MELON", "GRAPE", "BERRY", "CHERRY", "OLIVE",
+    "BEAN", "CORN", "RICE", "WHEAT", "OAT", "RYE", "BARLEY", "MILLET",
+    "FOX", "WOLF", "BEAR", "DEER", "ELK", "MOOSE", "HARE", "RABBIT",
+    "OTTER", "BEAVER", "BADGER", "MOLE", "MOUSE", "RAT", "BAT", "OWL",
+    "HAWK", "EAGLE", "FALCON", "RAVEN", "CROW", "ROBIN", "WREN", "FINCH",
+    "SWAN", "DUCK", "GOOSE", "HERON", "CRANE", "STORK", "PELICAN", "PUFFIN",
+    "FROG", "TOAD", "NEWT", "LIZARD", "SNAKE", "TURTLE", "GECKO", "IGUANA",
+    "LION", "TIGER", "LYNX", "PUMA", "JAGUAR", "ZEBRA", "HIPPO", "RHINO",
+    "BISON", "YAK", "LLAMA", "CAMEL", "GOAT", "SHEEP", "LAMB", "COW",
+    "BULL", "HORSE", "PONY", "MULE", "DONKEY", "PIG", "BOAR", "HEN",
+    "ROOSTER", "CHICK", "BEE", "WASP", "ANT", "MOTH", "FLY", "GNAT",
+    "BEETLE", "SNAIL", "SLUG", "WORM", "SPIDER", "TICK", "FLEA", "LARK",
+    "RUBY", "JADE", "OPAL", "ONYX", "AMBER", "TOPAZ", "GOLD", "SILVER",
+    "COPPER", "IRON", "TIN", "ZINC", "LEAD", "STEEL", "BRASS", "BRONZE",
+    "CHALK", "CLAY", "STONE", "ROCK", "PEBBLE", "BOULDER", "CLIFF", "RIDGE",
+    "PEAK", "HILL", "VALE", "GLEN", "DALE", "MEADOW", "FIELD", "GROVE",
+];
+
+/// Turns a room id into a code that is easy to read out, e.g. `CRAB-TIDE`.
+pub fn to_code(room_id: RoomId) -> RoomCode {
+    let [high, low] = room_id.to_be_bytes();
+    format!("{}-{}", WORDS[high as usize], WORDS[low as usize])
+}
+
+/// Reads a code back, ignoring case. Plain numeric ids are still accepted.
+pub fn from_code(code: &str) -> Option<RoomId> {
+    if let Ok(room_id) = code.parse::<RoomId>() {
+        return Some(room_id);
+    }
+    let (high, low) = code.trim().split_once('-')?;
+    let byte = |word: &str| {
+        WORDS
+            .iter()
+            .position(|candidate| candidate.eq_ignore_ascii_case(word))
+            .map(|idx| idx as u8)
+    };
+    Some(RoomId::from_be_bytes([byte(high)?, byte(low)?]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn codes_map_back_to_their_room() {
+        assert!(to_code(0) == "CRAB-CRAB");
+        assert!(to_code(1) == "CRAB-TIDE");
+        for room_id in [0, 1, 255, 256, 4242, RoomId::MAX] {
+            assert!(from_code(&to_code(room_id)) == Some(room_id));
+        }
+        assert!(from_code("crab-tide") == Some(1));
+        assert!(from_code("4242") == Some(4242));
+        assert!(from_code("CRAB-NOPE").is_none());
+        assert!(from_code("CRAB").is_none());
+    }
+}
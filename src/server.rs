@@ -1,5 +1,7 @@
-use std::{collections::HashMap, io};
+use std::{collections::HashMap, io, time::Duration};
 
+use futures_util::future::join_all;
+use rand::{thread_rng, Rng};
 use serde::Serialize;
 use tokio::{
     spawn,
@@ -7,29 +9,275 @@ use tokio::{
         mpsc::{self, UnboundedReceiver, UnboundedSender},
         oneshot,
     },
+    time::sleep,
 };
 
 use crate::{
-    consts::RoomId,
-    room::{commander::RoomCommander, server::RoomServer},
+    consts::{
+        BotToken, GameId, HostToken, Identity, ParticipantToken, PlayerName, RoomCode, RoomId,
+        TournamentId,
+    },
+    history::{BotRecord, GameRepository, GameSummary, InMemoryRepository, Storage, StorageError},
+    invite::{hash_password, secrets_match, InviteError, InviteSigner, InviteToken},
+    matchmaking::{MatchRequest, MatchmakingQueue, QueueUpdate, SeatedPlayer, Ticket, TicketId},
+    rating::{PlayerProfile, PlayerRating, INITIAL_RATING},
+    room::{
+        commander::RoomCommander,
+        config::RoomConfig,
+        events::RoomNotification,
+        record::GameRecord,
+        server::RoomServer,
+        snapshot::{Phase, RoomSummary},
+    },
+    room_code,
+    stats::{PlayerStats, StatsAggregator},
+    tournament::{
+        Tournament, TournamentConfig, TournamentError, TournamentEvent, TournamentStandings,
+    },
 };
 
-#[derive(Serialize)]
+/// How long the invite handed to the creator of a private room stays valid.
+pub const INVITE_DURATION: Duration = Duration::from_secs(24 * 60 * 60);
+/// How long the players of a tournament table have to get their game going,
+/// after which those missing forfeit it.
+pub const TABLE_SEATING_DEADLINE: Duration = Duration::from_secs(5 * 60);
+
+#[derive(Serialize, Debug)]
 pub enum ServerError {
     RoomNotFound,
+    InvalidConfig,
+    ReplayNotFound,
+    PlayerNotFound,
+    TooManyRooms,
+    WrongPassword,
+    InvalidInvite,
+    InviteExpired,
+    NotRoomHost,
+    TournamentNotFound,
+    NotTournamentHost,
+    InvalidParticipantToken,
+    /// The participant has no table to play right now.
+    NotSeated,
+    /// Bot names are unique and not empty.
+    InvalidBotName,
+    InvalidBotToken,
+    Tournament(TournamentError),
+    Storage(StorageError),
+}
+
+impl From<TournamentError> for ServerError {
+    fn from(err: TournamentError) -> Self {
+        ServerError::Tournament(err)
+    }
+}
+
+impl From<StorageError> for ServerError {
+    fn from(err: StorageError) -> Self {
+        ServerError::Storage(err)
+    }
+}
+
+impl From<InviteError> for ServerError {
+    fn from(err: InviteError) -> Self {
+        match err {
+            InviteError::Invalid => ServerError::InvalidInvite,
+            InviteError::Expired => ServerError::InviteExpired,
+        }
+    }
+}
+
+/// What a client shows to get into a room. Public rooms take anything.
+pub enum RoomKey {
+    None,
+    Password(String),
+    Invite(InviteToken),
+    /// Resuming a seat, the room itself checks the reconnect token.
+    Reconnect,
+}
+
+/// Handed to the creator of a private room, to be shared with the other players.
+#[derive(Serialize)]
+pub struct Invite {
+    pub room_id: RoomId,
+    pub room_code: RoomCode,
+    pub token: InviteToken,
+}
+
+/// How a room can be found and entered, chosen at creation.
+#[derive(Default)]
+pub struct RoomAccess {
+    /// Listed by the room browser. Rooms with a password never are.
+    pub public: bool,
+    pub password: Option<String>,
+}
+
+/// A room just created, with what its creator needs to manage it.
+pub struct CreatedRoom {
+    pub room_id: RoomId,
+    pub commander: RoomCommander,
+    pub host_token: HostToken,
+}
+
+struct Room {
+    commander: RoomCommander,
+    public: bool,
+    password_hash: Option<Vec<u8>>,
+    host_token: HostToken,
+}
+
+/// A tournament just created, with the token needed to start it.
+#[derive(Serialize)]
+pub struct CreatedTournament {
+    pub tournament_id: TournamentId,
+    pub host_token: HostToken,
+}
+
+/// A bot just registered. The token is not shown again.
+#[derive(Serialize)]
+pub struct RegisteredBot {
+    pub name: PlayerName,
+    pub token: BotToken,
+}
+
+/// Who a bot token belongs to. Bots are rated like everybody else, under an
+/// identity of their own.
+pub struct BotAccount {
+    pub name: PlayerName,
+    pub profile: PlayerProfile,
+}
+
+/// A participant just registered. The token is not shown again.
+#[derive(Serialize)]
+pub struct RegisteredParticipant {
+    pub name: PlayerName,
+    pub token: ParticipantToken,
+}
+
+/// The table a participant plays next, with the invite only they can use.
+#[derive(Serialize)]
+pub struct Seat {
+    pub round: u32,
+    pub table: usize,
+    pub room_id: RoomId,
+    pub room_code: RoomCode,
+    pub token: InviteToken,
+}
+
+struct HostedTournament {
+    tournament: Tournament,
+    host_token: HostToken,
 }
 
 pub enum ServerCommand {
     NewRoom {
-        cmd_tx: oneshot::Sender<RoomCommander>,
+        config: RoomConfig,
+        access: RoomAccess,
+        cmd_tx: oneshot::Sender<Result<CreatedRoom, ServerError>>,
     },
+    /// Tournament tables only let in their players, under their own name.
     JoinRoom {
+        room_id: RoomId,
+        key: RoomKey,
+        name: Option<PlayerName>,
+        cmd_tx: oneshot::Sender<Result<RoomCommander, ServerError>>,
+    },
+    CreateInvite {
+        room_id: RoomId,
+        cmd_tx: oneshot::Sender<Result<Invite, ServerError>>,
+    },
+    ListRooms {
+        cmd_tx: oneshot::Sender<Vec<RoomCommander>>,
+    },
+    /// The room whatever its access, for status queries.
+    GetRoom {
         room_id: RoomId,
         cmd_tx: oneshot::Sender<Result<RoomCommander, ServerError>>,
     },
+    DeleteRoom {
+        room_id: RoomId,
+        host_token: HostToken,
+        cmd_tx: oneshot::Sender<Result<(), ServerError>>,
+    },
     DestroyRoom {
         room_id: RoomId,
     },
+    /// The tournament table is still waiting for players once the seating
+    /// deadline passed.
+    TableDeadline {
+        room_id: RoomId,
+        present: Vec<PlayerName>,
+    },
+    StoreReplay(Box<GameRecord>),
+    /// Most recent games first, only those of the given player when set.
+    GetHistory {
+        player_name: Option<PlayerName>,
+        limit: usize,
+        cmd_tx: oneshot::Sender<Result<Vec<GameSummary>, ServerError>>,
+    },
+    GetReplay {
+        game_id: GameId,
+        cmd_tx: oneshot::Sender<Result<GameRecord, ServerError>>,
+    },
+    /// Newcomers get the initial rating.
+    GetProfile {
+        identity: Identity,
+        cmd_tx: oneshot::Sender<Result<PlayerProfile, ServerError>>,
+    },
+    GetLeaderboard {
+        limit: usize,
+        cmd_tx: oneshot::Sender<Result<Vec<PlayerRating>, ServerError>>,
+    },
+    GetStats {
+        identity: Identity,
+        cmd_tx: oneshot::Sender<Result<PlayerStats, ServerError>>,
+    },
+    /// Queue positions and the seat found come through `tx`.
+    /// The profile is looked up by the commander beforehand.
+    Matchmake {
+        request: MatchRequest,
+        profile: PlayerProfile,
+        tx: UnboundedSender<QueueUpdate>,
+        cmd_tx: oneshot::Sender<Result<TicketId, ServerError>>,
+    },
+    LeaveQueue {
+        ticket_id: TicketId,
+    },
+    NewTournament {
+        config: TournamentConfig,
+        cmd_tx: oneshot::Sender<Result<CreatedTournament, ServerError>>,
+    },
+    RegisterParticipant {
+        tournament_id: TournamentId,
+        name: PlayerName,
+        cmd_tx: oneshot::Sender<Result<RegisteredParticipant, ServerError>>,
+    },
+    GetSeat {
+        tournament_id: TournamentId,
+        token: ParticipantToken,
+        cmd_tx: oneshot::Sender<Result<Seat, ServerError>>,
+    },
+    StartTournament {
+        tournament_id: TournamentId,
+        host_token: HostToken,
+        cmd_tx: oneshot::Sender<Result<(), ServerError>>,
+    },
+    GetTournament {
+        tournament_id: TournamentId,
+        cmd_tx: oneshot::Sender<Result<TournamentStandings, ServerError>>,
+    },
+    /// Events of the tournament from now on.
+    WatchTournament {
+        tournament_id: TournamentId,
+        cmd_tx: oneshot::Sender<Result<UnboundedReceiver<TournamentEvent>, ServerError>>,
+    },
+    RegisterBot {
+        name: PlayerName,
+        cmd_tx: oneshot::Sender<Result<RegisteredBot, ServerError>>,
+    },
+    AuthenticateBot {
+        token: BotToken,
+        cmd_tx: oneshot::Sender<Result<BotAccount, ServerError>>,
+    },
 }
 
 #[derive(Clone)]
@@ -38,34 +286,275 @@ pub struct ServerCommander {
 }
 
 impl ServerCommander {
-    pub async fn new_room(&self) -> RoomCommander {
+    pub async fn new_room(
+        &self,
+        config: RoomConfig,
+        access: RoomAccess,
+    ) -> Result<CreatedRoom, ServerError> {
+        let (cmd_tx, cmd_rx) = oneshot::channel();
+        self.tx_channel
+            .send(ServerCommand::NewRoom {
+                config,
+                access,
+                cmd_tx,
+            })
+            .unwrap();
+        cmd_rx.await.unwrap()
+    }
+    /// The name is the one the player is about to take a seat under.
+    pub async fn join_room(
+        &self,
+        room_id: RoomId,
+        key: RoomKey,
+        name: Option<PlayerName>,
+    ) -> Result<RoomCommander, ServerError> {
+        let (cmd_tx, cmd_rx) = oneshot::channel();
+        self.tx_channel
+            .send(ServerCommand::JoinRoom {
+                room_id,
+                key,
+                name,
+                cmd_tx,
+            })
+            .unwrap();
+        cmd_rx.await.unwrap()
+    }
+    pub async fn create_invite(&self, room_id: RoomId) -> Result<Invite, ServerError> {
+        let (cmd_tx, cmd_rx) = oneshot::channel();
+        self.tx_channel
+            .send(ServerCommand::CreateInvite { room_id, cmd_tx })
+            .unwrap();
+        cmd_rx.await.unwrap()
+    }
+    /// Summaries of the public rooms, asked to the rooms themselves so the
+    /// server loop does not wait on them.
+    pub async fn list_rooms(&self) -> Vec<RoomSummary> {
+        let (cmd_tx, cmd_rx) = oneshot::channel();
+        self.tx_channel
+            .send(ServerCommand::ListRooms { cmd_tx })
+            .unwrap();
+        let rooms = cmd_rx.await.unwrap();
+        let mut summaries: Vec<RoomSummary> = join_all(rooms.iter().map(|room| room.get_summary()))
+            .await
+            .into_iter()
+            .flatten()
+            .collect();
+        summaries.sort_by_key(|summary| summary.room_id);
+        summaries
+    }
+    pub async fn room_summary(&self, room_id: RoomId) -> Result<RoomSummary, ServerError> {
+        let (cmd_tx, cmd_rx) = oneshot::channel();
+        self.tx_channel
+            .send(ServerCommand::GetRoom { room_id, cmd_tx })
+            .unwrap();
+        let room = cmd_rx.await.unwrap()?;
+        room.get_summary().await.ok_or(ServerError::RoomNotFound)
+    }
+    pub async fn delete_room(
+        &self,
+        room_id: RoomId,
+        host_token: HostToken,
+    ) -> Result<(), ServerError> {
+        let (cmd_tx, cmd_rx) = oneshot::channel();
+        self.tx_channel
+            .send(ServerCommand::DeleteRoom {
+                room_id,
+                host_token,
+                cmd_tx,
+            })
+            .unwrap();
+        cmd_rx.await.unwrap()
+    }
+    pub async fn get_history(
+        &self,
+        player_name: Option<PlayerName>,
+        limit: usize,
+    ) -> Result<Vec<GameSummary>, ServerError> {
+        let (cmd_tx, cmd_rx) = oneshot::channel();
+        self.tx_channel
+            .send(ServerCommand::GetHistory {
+                player_name,
+                limit,
+                cmd_tx,
+            })
+            .unwrap();
+        cmd_rx.await.unwrap()
+    }
+    pub async fn get_replay(&self, game_id: GameId) -> Result<GameRecord, ServerError> {
+        let (cmd_tx, cmd_rx) = oneshot::channel();
+        self.tx_channel
+            .send(ServerCommand::GetReplay { game_id, cmd_tx })
+            .unwrap();
+        cmd_rx.await.unwrap()
+    }
+    pub async fn get_profile(&self, identity: Identity) -> Result<PlayerProfile, ServerError> {
+        let (cmd_tx, cmd_rx) = oneshot::channel();
+        self.tx_channel
+            .send(ServerCommand::GetProfile { identity, cmd_tx })
+            .unwrap();
+        cmd_rx.await.unwrap()
+    }
+    pub async fn get_leaderboard(&self, limit: usize) -> Result<Vec<PlayerRating>, ServerError> {
+        let (cmd_tx, cmd_rx) = oneshot::channel();
+        self.tx_channel
+            .send(ServerCommand::GetLeaderboard { limit, cmd_tx })
+            .unwrap();
+        cmd_rx.await.unwrap()
+    }
+    pub async fn matchmake(
+        &self,
+        request: MatchRequest,
+        identity: Identity,
+    ) -> Result<(TicketId, UnboundedReceiver<QueueUpdate>), ServerError> {
+        let profile = self.get_profile(identity).await?;
+        let (tx, rx) = mpsc::unbounded_channel();
+        let (cmd_tx, cmd_rx) = oneshot::channel();
+        self.tx_channel
+            .send(ServerCommand::Matchmake {
+                request,
+                profile,
+                tx,
+                cmd_tx,
+            })
+            .unwrap();
+        cmd_rx.await.unwrap().map(|ticket_id| (ticket_id, rx))
+    }
+    pub fn leave_queue(&self, ticket_id: TicketId) {
+        let _ = self
+            .tx_channel
+            .send(ServerCommand::LeaveQueue { ticket_id });
+    }
+    pub async fn new_tournament(
+        &self,
+        config: TournamentConfig,
+    ) -> Result<CreatedTournament, ServerError> {
+        let (cmd_tx, cmd_rx) = oneshot::channel();
+        self.tx_channel
+            .send(ServerCommand::NewTournament { config, cmd_tx })
+            .unwrap();
+        cmd_rx.await.unwrap()
+    }
+    pub async fn register_participant(
+        &self,
+        tournament_id: TournamentId,
+        name: PlayerName,
+    ) -> Result<RegisteredParticipant, ServerError> {
+        let (cmd_tx, cmd_rx) = oneshot::channel();
+        self.tx_channel
+            .send(ServerCommand::RegisterParticipant {
+                tournament_id,
+                name,
+                cmd_tx,
+            })
+            .unwrap();
+        cmd_rx.await.unwrap()
+    }
+    pub async fn get_seat(
+        &self,
+        tournament_id: TournamentId,
+        token: ParticipantToken,
+    ) -> Result<Seat, ServerError> {
+        let (cmd_tx, cmd_rx) = oneshot::channel();
+        self.tx_channel
+            .send(ServerCommand::GetSeat {
+                tournament_id,
+                token,
+                cmd_tx,
+            })
+            .unwrap();
+        cmd_rx.await.unwrap()
+    }
+    pub async fn start_tournament(
+        &self,
+        tournament_id: TournamentId,
+        host_token: HostToken,
+    ) -> Result<(), ServerError> {
+        let (cmd_tx, cmd_rx) = oneshot::channel();
+        self.tx_channel
+            .send(ServerCommand::StartTournament {
+                tournament_id,
+                host_token,
+                cmd_tx,
+            })
+            .unwrap();
+        cmd_rx.await.unwrap()
+    }
+    pub async fn get_tournament(
+        &self,
+        tournament_id: TournamentId,
+    ) -> Result<TournamentStandings, ServerError> {
+        let (cmd_tx, cmd_rx) = oneshot::channel();
+        self.tx_channel
+            .send(ServerCommand::GetTournament {
+                tournament_id,
+                cmd_tx,
+            })
+            .unwrap();
+        cmd_rx.await.unwrap()
+    }
+    pub async fn watch_tournament(
+        &self,
+        tournament_id: TournamentId,
+    ) -> Result<UnboundedReceiver<TournamentEvent>, ServerError> {
+        let (cmd_tx, cmd_rx) = oneshot::channel();
+        self.tx_channel
+            .send(ServerCommand::WatchTournament {
+                tournament_id,
+                cmd_tx,
+            })
+            .unwrap();
+        cmd_rx.await.unwrap()
+    }
+    pub async fn register_bot(&self, name: PlayerName) -> Result<RegisteredBot, ServerError> {
+        let (cmd_tx, cmd_rx) = oneshot::channel();
+        self.tx_channel
+            .send(ServerCommand::RegisterBot { name, cmd_tx })
+            .unwrap();
+        cmd_rx.await.unwrap()
+    }
+    pub async fn authenticate_bot(&self, token: BotToken) -> Result<BotAccount, ServerError> {
         let (cmd_tx, cmd_rx) = oneshot::channel();
         self.tx_channel
-            .send(ServerCommand::NewRoom { cmd_tx })
+            .send(ServerCommand::AuthenticateBot { token, cmd_tx })
             .unwrap();
         cmd_rx.await.unwrap()
     }
-    pub async fn join_room(&self, room_id: RoomId) -> Result<RoomCommander, ServerError> {
+    pub async fn get_stats(&self, identity: Identity) -> Result<PlayerStats, ServerError> {
         let (cmd_tx, cmd_rx) = oneshot::channel();
         self.tx_channel
-            .send(ServerCommand::JoinRoom { room_id, cmd_tx })
+            .send(ServerCommand::GetStats { identity, cmd_tx })
             .unwrap();
         cmd_rx.await.unwrap()
     }
 }
 
 pub struct Server {
-    rooms: HashMap<RoomId, RoomCommander>,
+    rooms: HashMap<RoomId, Room>,
+    history: Storage,
+    invite_signer: InviteSigner,
+    matchmaking: MatchmakingQueue,
+    next_ticket: TicketId,
+    tournaments: HashMap<TournamentId, HostedTournament>,
+    next_tournament: TournamentId,
     tx_channel: UnboundedSender<ServerCommand>,
     rx_channel: UnboundedReceiver<ServerCommand>,
 }
 
 impl Server {
     pub fn new() -> (Self, ServerCommander) {
+        Self::with_history(Box::new(InMemoryRepository::default()))
+    }
+    pub fn with_history(history: Box<dyn GameRepository>) -> (Self, ServerCommander) {
         let (tx_channel, rx_channel) = mpsc::unbounded_channel();
         (
             Self {
                 rooms: HashMap::new(),
+                history: Storage::new(history),
+                invite_signer: InviteSigner::default(),
+                matchmaking: MatchmakingQueue::default(),
+                next_ticket: 0,
+                tournaments: HashMap::new(),
+                next_tournament: 0,
                 tx_channel: tx_channel.clone(),
                 rx_channel,
             },
@@ -75,62 +564,618 @@ impl Server {
     pub async fn run(mut self) -> io::Result<()> {
         while let Some(msg) = self.rx_channel.recv().await {
             match msg {
-                ServerCommand::NewRoom { cmd_tx } => {
-                    let res = self.new_room();
+                ServerCommand::NewRoom {
+                    config,
+                    access,
+                    cmd_tx,
+                } => {
+                    let res = self.new_room(config, access);
+                    let _ = cmd_tx.send(res);
+                }
+                ServerCommand::JoinRoom {
+                    room_id,
+                    key,
+                    name,
+                    cmd_tx,
+                } => {
+                    let res = self.join_room(room_id, key, name.as_deref());
+                    let _ = cmd_tx.send(res);
+                }
+                ServerCommand::CreateInvite { room_id, cmd_tx } => {
+                    let res = self.create_invite(room_id, INVITE_DURATION);
                     let _ = cmd_tx.send(res);
                 }
-                ServerCommand::JoinRoom { room_id, cmd_tx } => {
-                    let res = self.join_room(room_id);
+                ServerCommand::ListRooms { cmd_tx } => {
+                    let _ = cmd_tx.send(self.public_rooms());
+                }
+                ServerCommand::GetRoom { room_id, cmd_tx } => {
+                    let res = self
+                        .rooms
+                        .get(&room_id)
+                        .map(|room| room.commander.clone())
+                        .ok_or(ServerError::RoomNotFound);
+                    let _ = cmd_tx.send(res);
+                }
+                ServerCommand::DeleteRoom {
+                    room_id,
+                    host_token,
+                    cmd_tx,
+                } => {
+                    let res = self.delete_room(room_id, &host_token);
                     let _ = cmd_tx.send(res);
                 }
                 ServerCommand::DestroyRoom { room_id } => {
                     self.destroy_room(room_id);
                 }
+                ServerCommand::TableDeadline { room_id, present } => {
+                    self.abandon_table(room_id, &present);
+                }
+                ServerCommand::StoreReplay(record) => {
+                    if let Some(summary) = GameSummary::from_record(&record) {
+                        self.record_table_result(&summary);
+                    }
+                    self.history
+                        .run(move |history| store_game(history, &record));
+                }
+                ServerCommand::GetHistory {
+                    player_name,
+                    limit,
+                    cmd_tx,
+                } => self.history.run(move |history| {
+                    let res = match player_name {
+                        Some(name) => history.player_games(&name, limit),
+                        None => history.recent_games(limit),
+                    };
+                    let _ = cmd_tx.send(res.map_err(ServerError::from));
+                }),
+                ServerCommand::GetReplay { game_id, cmd_tx } => self.history.run(move |history| {
+                    let res = match history.game_record(game_id) {
+                        Ok(Some(record)) => Ok(record),
+                        Ok(None) => Err(ServerError::ReplayNotFound),
+                        Err(err) => Err(ServerError::from(err)),
+                    };
+                    let _ = cmd_tx.send(res);
+                }),
+                ServerCommand::GetProfile { identity, cmd_tx } => {
+                    self.history.run(move |history| {
+                        let _ = cmd_tx.send(profile(history, identity));
+                    })
+                }
+                ServerCommand::GetLeaderboard { limit, cmd_tx } => {
+                    self.history.run(move |history| {
+                        let res = history.leaderboard(limit);
+                        let _ = cmd_tx.send(res.map_err(ServerError::from));
+                    })
+                }
+                ServerCommand::GetStats { identity, cmd_tx } => self.history.run(move |history| {
+                    let res = match history.player_stats(&identity) {
+                        Ok(Some(stats)) => Ok(stats),
+                        Ok(None) => Err(ServerError::PlayerNotFound),
+                        Err(err) => Err(ServerError::from(err)),
+                    };
+                    let _ = cmd_tx.send(res);
+                }),
+                ServerCommand::Matchmake {
+                    request,
+                    profile,
+                    tx,
+                    cmd_tx,
+                } => {
+                    let res = self.matchmake(request, profile, tx);
+                    let _ = cmd_tx.send(res);
+                }
+                ServerCommand::LeaveQueue { ticket_id } => {
+                    self.matchmaking.leave(ticket_id);
+                }
+                ServerCommand::NewTournament { config, cmd_tx } => {
+                    let res = self.new_tournament(config);
+                    let _ = cmd_tx.send(res);
+                }
+                ServerCommand::RegisterParticipant {
+                    tournament_id,
+                    name,
+                    cmd_tx,
+                } => {
+                    let res = match self.tournaments.get_mut(&tournament_id) {
+                        Some(hosted) => hosted
+                            .tournament
+                            .register(name.clone())
+                            .map(|token| RegisteredParticipant { name, token })
+                            .map_err(ServerError::from),
+                        None => Err(ServerError::TournamentNotFound),
+                    };
+                    let _ = cmd_tx.send(res);
+                }
+                ServerCommand::GetSeat {
+                    tournament_id,
+                    token,
+                    cmd_tx,
+                } => {
+                    let res = self.get_seat(tournament_id, &token);
+                    let _ = cmd_tx.send(res);
+                }
+                ServerCommand::StartTournament {
+                    tournament_id,
+                    host_token,
+                    cmd_tx,
+                } => {
+                    let res = self.start_tournament(tournament_id, &host_token);
+                    let _ = cmd_tx.send(res);
+                }
+                ServerCommand::GetTournament {
+                    tournament_id,
+                    cmd_tx,
+                } => {
+                    let res = self
+                        .tournaments
+                        .get(&tournament_id)
+                        .map(|hosted| hosted.tournament.summary())
+                        .ok_or(ServerError::TournamentNotFound);
+                    let _ = cmd_tx.send(res);
+                }
+                ServerCommand::RegisterBot { name, cmd_tx } => self.history.run(move |history| {
+                    let _ = cmd_tx.send(register_bot(history, name));
+                }),
+                ServerCommand::AuthenticateBot { token, cmd_tx } => {
+                    self.history.run(move |history| {
+                        let _ = cmd_tx.send(authenticate_bot(history, &token));
+                    })
+                }
+                ServerCommand::WatchTournament {
+                    tournament_id,
+                    cmd_tx,
+                } => {
+                    let res = self
+                        .tournaments
+                        .get_mut(&tournament_id)
+                        .map(|hosted| hosted.tournament.subscribe())
+                        .ok_or(ServerError::TournamentNotFound);
+                    let _ = cmd_tx.send(res);
+                }
             }
         }
         Ok(())
     }
-    fn new_room(&mut self) -> RoomCommander {
-        let (room_server, room_commander) = RoomServer::new();
-        let room_id = room_server.get_id();
-        spawn(room_server.run());
-        self.rooms.insert(room_id, room_commander.clone());
-        spawn(Self::remove_room(
+    fn new_room(
+        &mut self,
+        config: RoomConfig,
+        access: RoomAccess,
+    ) -> Result<CreatedRoom, ServerError> {
+        if !config.is_valid() {
+            return Err(ServerError::InvalidConfig);
+        }
+        let room_id = self.free_room_id().ok_or(ServerError::TooManyRooms)?;
+        let (mut room_server, room_commander) = RoomServer::with_id(room_id, config);
+        spawn(Self::forward_notifications(
             self.tx_channel.clone(),
             room_id,
-            room_commander.clone(),
+            room_server.notifications(),
         ));
+        spawn(room_server.run());
+        let host_token = format!("{:032x}", thread_rng().gen::<u128>());
+        self.rooms.insert(
+            room_id,
+            Room {
+                commander: room_commander.clone(),
+                public: access.public && access.password.is_none(),
+                password_hash: access.password.as_deref().map(hash_password),
+                host_token: host_token.clone(),
+            },
+        );
 
-        room_commander
+        Ok(CreatedRoom {
+            room_id,
+            commander: room_commander,
+            host_token,
+        })
+    }
+
+    fn free_room_id(&self) -> Option<RoomId> {
+        if self.rooms.len() > RoomId::MAX as usize {
+            return None;
+        }
+        let mut room_id = thread_rng().gen::<RoomId>();
+        while self.rooms.contains_key(&room_id) {
+            room_id = thread_rng().gen::<RoomId>();
+        }
+        Some(room_id)
+    }
+
+    fn public_rooms(&self) -> Vec<RoomCommander> {
+        self.rooms
+            .values()
+            .filter(|room| room.public)
+            .map(|room| room.commander.clone())
+            .collect()
+    }
+
+    /// Closes the room, which removes itself once its players are gone.
+    fn delete_room(&mut self, room_id: RoomId, host_token: &str) -> Result<(), ServerError> {
+        let room = self.rooms.get(&room_id).ok_or(ServerError::RoomNotFound)?;
+        if !secrets_match(&room.host_token, host_token) {
+            return Err(ServerError::NotRoomHost);
+        }
+        room.commander.close();
+        Ok(())
     }
 
+    /// A tournament table whose game never finished is abandoned rather
+    /// than left to hold the round up.
     fn destroy_room(&mut self, room_id: RoomId) {
         self.rooms.remove(&room_id);
+        self.abandon_table(room_id, &[]);
+    }
+
+    fn matchmake(
+        &mut self,
+        request: MatchRequest,
+        profile: PlayerProfile,
+        tx: UnboundedSender<QueueUpdate>,
+    ) -> Result<TicketId, ServerError> {
+        if !request.room_config().is_valid() {
+            return Err(ServerError::InvalidConfig);
+        }
+        let ticket = Ticket {
+            id: self.next_ticket,
+            request,
+            profile,
+            tx,
+        };
+        self.next_ticket += 1;
+        let ticket_id = ticket.id;
+        if let Some(group) = self.matchmaking.join(ticket) {
+            // Without a room the tickets are dropped, which tells the
+            // players there is no match to wait for.
+            let config = group[0].request.room_config();
+            if let Ok(room) = self.new_room(config, RoomAccess::default()) {
+                spawn(Self::seat_match(room.room_id, room.commander, group));
+            }
+        }
+        Ok(ticket_id)
+    }
+
+    /// Seats everyone before marking them ready, so that the game starts
+    /// only once the table is full.
+    async fn seat_match(room_id: RoomId, commander: RoomCommander, group: Vec<Ticket>) {
+        let mut seated = vec![];
+        for ticket in group {
+            let name = ticket.request.name.clone();
+            if let Ok((player_id, player_channel)) = commander
+                .new_identified_player(name, Some(ticket.profile.clone()))
+                .await
+            {
+                seated.push((ticket, player_id, player_channel));
+            }
+        }
+        for (ticket, player_id, player_channel) in seated {
+            let found = ticket.tx.send(QueueUpdate::Found(Box::new(SeatedPlayer {
+                room_id,
+                player_id,
+                commander: commander.clone(),
+                player_channel,
+            })));
+            // The player is gone, the seat must not hold the table up.
+            match found {
+                Ok(()) => {
+                    let _ = commander.set_lobby_ready(player_id, true).await;
+                }
+                Err(_) => commander.remove_player(player_id).await,
+            }
+        }
+    }
+
+    fn new_tournament(
+        &mut self,
+        config: TournamentConfig,
+    ) -> Result<CreatedTournament, ServerError> {
+        if !config.is_valid() {
+            return Err(ServerError::InvalidConfig);
+        }
+        let tournament_id = self.next_tournament;
+        self.next_tournament += 1;
+        let host_token = format!("{:032x}", thread_rng().gen::<u128>());
+        self.tournaments.insert(
+            tournament_id,
+            HostedTournament {
+                tournament: Tournament::new(tournament_id, config),
+                host_token: host_token.clone(),
+            },
+        );
+        Ok(CreatedTournament {
+            tournament_id,
+            host_token,
+        })
+    }
+
+    fn start_tournament(
+        &mut self,
+        tournament_id: TournamentId,
+        host_token: &str,
+    ) -> Result<(), ServerError> {
+        let hosted = self
+            .tournaments
+            .get_mut(&tournament_id)
+            .ok_or(ServerError::TournamentNotFound)?;
+        if !secrets_match(&hosted.host_token, host_token) {
+            return Err(ServerError::NotTournamentHost);
+        }
+        let tables = hosted.tournament.start()?;
+        self.seat_tournament_round(tournament_id, tables);
+        Ok(())
+    }
+
+    /// Every table gets a private room, entered with the invites its players
+    /// fetch with their participant token.
+    fn seat_tournament_round(&mut self, tournament_id: TournamentId, tables: Vec<Vec<PlayerName>>) {
+        for players in tables {
+            let Some(hosted) = self.tournaments.get(&tournament_id) else {
+                return;
+            };
+            let config = hosted.tournament.config().table_config(players.len());
+            let access = RoomAccess {
+                public: false,
+                password: Some(format!("{:032x}", thread_rng().gen::<u128>())),
+            };
+            let Ok(room) = self.new_room(config, access) else {
+                continue;
+            };
+            spawn(Self::table_deadline(
+                self.tx_channel.clone(),
+                room.room_id,
+                room.commander,
+            ));
+            if let Some(hosted) = self.tournaments.get_mut(&tournament_id) {
+                hosted.tournament.add_table(room.room_id, players);
+            }
+        }
+    }
+
+    fn get_seat(&self, tournament_id: TournamentId, token: &str) -> Result<Seat, ServerError> {
+        let tournament = &self
+            .tournaments
+            .get(&tournament_id)
+            .ok_or(ServerError::TournamentNotFound)?
+            .tournament;
+        let name = tournament
+            .participant(token)
+            .ok_or(ServerError::InvalidParticipantToken)?;
+        let table = tournament.table_of(name).ok_or(ServerError::NotSeated)?;
+        Ok(Seat {
+            round: table.round,
+            table: table.table,
+            room_id: table.room_id,
+            room_code: room_code::to_code(table.room_id),
+            token: self
+                .invite_signer
+                .sign_seat(table.room_id, name, INVITE_DURATION),
+        })
+    }
+
+    fn abandon_table(&mut self, room_id: RoomId, present: &[PlayerName]) {
+        let Some((&tournament_id, hosted)) = self
+            .tournaments
+            .iter_mut()
+            .find(|(_, hosted)| hosted.tournament.plays_in(room_id))
+        else {
+            return;
+        };
+        let next_round = hosted.tournament.abandon_table(room_id, present);
+        if let Some(room) = self.rooms.get(&room_id) {
+            room.commander.close();
+        }
+        if let Some(tables) = next_round {
+            self.seat_tournament_round(tournament_id, tables);
+        }
+    }
+
+    fn record_table_result(&mut self, summary: &GameSummary) {
+        let Some((&tournament_id, hosted)) = self
+            .tournaments
+            .iter_mut()
+            .find(|(_, hosted)| hosted.tournament.plays_in(summary.room_id))
+        else {
+            return;
+        };
+        if let Some(tables) = hosted.tournament.record_result(summary) {
+            self.seat_tournament_round(tournament_id, tables);
+        }
+    }
+
+    fn join_room(
+        &mut self,
+        room_id: RoomId,
+        key: RoomKey,
+        name: Option<&str>,
+    ) -> Result<RoomCommander, ServerError> {
+        let room = self.rooms.get(&room_id).ok_or(ServerError::RoomNotFound)?;
+        let table_players = self
+            .tournaments
+            .values()
+            .find_map(|hosted| hosted.tournament.table_players(room_id));
+        if let Some(password_hash) = &room.password_hash {
+            match key {
+                RoomKey::Password(password) if hash_password(&password) == *password_hash => {}
+                // Seat invites are only good for the name they were made for.
+                RoomKey::Invite(token) => match (table_players, name) {
+                    (None, _) => self.invite_signer.verify(room_id, &token)?,
+                    (Some(players), Some(name)) if players.iter().any(|player| player == name) => {
+                        self.invite_signer.verify_seat(room_id, name, &token)?
+                    }
+                    (Some(_), _) => return Err(ServerError::InvalidInvite),
+                },
+                RoomKey::Reconnect => {}
+                RoomKey::None | RoomKey::Password(_) => return Err(ServerError::WrongPassword),
+            }
+        }
+        Ok(room.commander.clone())
     }
 
-    fn join_room(&mut self, room_id: RoomId) -> Result<RoomCommander, ServerError> {
-        let room = self
-            .rooms
-            .get_mut(&room_id)
-            .ok_or(ServerError::RoomNotFound)?;
-        Ok(room.clone())
+    fn create_invite(&self, room_id: RoomId, valid_for: Duration) -> Result<Invite, ServerError> {
+        if !self.rooms.contains_key(&room_id) {
+            return Err(ServerError::RoomNotFound);
+        }
+        Ok(Invite {
+            room_id,
+            room_code: room_code::to_code(room_id),
+            token: self.invite_signer.sign(room_id, valid_for),
+        })
     }
 
-    async fn remove_room(
+    /// The notifications end with the room itself, so its last game is
+    /// always stored before the room is removed.
+    async fn forward_notifications(
         tx_channel: UnboundedSender<ServerCommand>,
         room_id: RoomId,
-        room_channel: RoomCommander,
+        mut notifications: UnboundedReceiver<RoomNotification>,
     ) {
-        room_channel.tx_channel.closed().await;
+        while let Some(notification) = notifications.recv().await {
+            let cmd = match notification {
+                RoomNotification::GameFinished(record) => ServerCommand::StoreReplay(record),
+            };
+            let _ = tx_channel.send(cmd);
+        }
         let _ = tx_channel.send(ServerCommand::DestroyRoom { room_id });
     }
+
+    async fn table_deadline(
+        tx_channel: UnboundedSender<ServerCommand>,
+        room_id: RoomId,
+        commander: RoomCommander,
+    ) {
+        sleep(TABLE_SEATING_DEADLINE).await;
+        let Some(summary) = commander.get_summary().await else {
+            return;
+        };
+        if matches!(summary.phase, Phase::NotStarted) {
+            let _ = tx_channel.send(ServerCommand::TableDeadline {
+                room_id,
+                present: summary.player_names,
+            });
+        }
+    }
+}
+
+/// Each part is stored on its own: a game missing from the history must not
+/// cost the players their ratings, nor take the server down. Failures are
+/// logged, as nobody waits on the result.
+fn store_game(history: &mut dyn GameRepository, record: &GameRecord) {
+    let mut results = vec![];
+    if let Some(summary) = GameSummary::from_record(record) {
+        results.push(update_ratings(history, &summary));
+        results.push(update_stats(history, &summary, record));
+        results.push(history.save_game(summary));
+    }
+    results.push(history.save_record(record));
+    for err in results.into_iter().filter_map(Result::err) {
+        log::error!("Could not store game {}: {err:?}", record.game_id);
+    }
+}
+
+/// Deltas are applied to the stored ratings rather than overwriting them,
+/// as the same person may have finished another game in the meantime.
+fn update_ratings(
+    history: &mut dyn GameRepository,
+    summary: &GameSummary,
+) -> Result<(), StorageError> {
+    for player in summary.players.iter() {
+        let (Some(identity), Some(delta)) = (
+            player.identity.as_ref(),
+            summary.rating_changes.get(&player.player_id),
+        ) else {
+            continue;
+        };
+        let mut rating = history
+            .player_rating(identity)?
+            .unwrap_or_else(|| PlayerRating::new(identity.clone(), player.name.clone()));
+        rating.name = player.name.clone();
+        rating.rating += delta;
+        rating.games += 1;
+        history.save_rating(rating)?;
+    }
+    Ok(())
+}
+
+/// Stats follow identities like ratings do, seats without one are not kept.
+fn update_stats(
+    history: &mut dyn GameRepository,
+    summary: &GameSummary,
+    record: &GameRecord,
+) -> Result<(), StorageError> {
+    let mut game_stats = StatsAggregator::from_record(record).finish();
+    for player in summary.players.iter() {
+        let (Some(identity), Some(game_stats)) = (
+            player.identity.as_ref(),
+            game_stats.remove(&player.player_id),
+        ) else {
+            continue;
+        };
+        let mut stats = history
+            .player_stats(identity)?
+            .unwrap_or_else(|| PlayerStats::new(identity.clone(), player.name.clone()));
+        stats.name = player.name.clone();
+        stats.merge(&game_stats);
+        history.save_stats(stats)?;
+    }
+    Ok(())
+}
+
+fn profile(
+    history: &mut dyn GameRepository,
+    identity: Identity,
+) -> Result<PlayerProfile, ServerError> {
+    let stored = history.player_rating(&identity)?;
+    Ok(PlayerProfile {
+        rating: stored.map_or(INITIAL_RATING, |stored| stored.rating),
+        identity,
+    })
+}
+
+/// Runs on the storage thread, so that no other registration comes between
+/// the name check and the save.
+fn register_bot(
+    history: &mut dyn GameRepository,
+    name: PlayerName,
+) -> Result<RegisteredBot, ServerError> {
+    if name.is_empty() || history.bot_by_name(&name)?.is_some() {
+        return Err(ServerError::InvalidBotName);
+    }
+    let token = format!("{:032x}", thread_rng().gen::<u128>());
+    history.save_bot(BotRecord {
+        name: name.clone(),
+        token_hash: hash_password(&token),
+    })?;
+    Ok(RegisteredBot { name, token })
+}
+
+fn authenticate_bot(
+    history: &mut dyn GameRepository,
+    token: &str,
+) -> Result<BotAccount, ServerError> {
+    let bot = history
+        .bot_by_token(&hash_password(token))?
+        .ok_or(ServerError::InvalidBotToken)?;
+    let profile = profile(history, format!("bot:{}", bot.name))?;
+    Ok(BotAccount {
+        name: bot.name,
+        profile,
+    })
 }
 
 #[cfg(test)]
 mod tests {
-    use tokio::spawn;
+    use std::collections::BTreeMap;
 
-    use crate::room::events::RoomEvent;
+    use tokio::{spawn, time::pause};
+
+    use crate::{
+        history::testing_summary,
+        room::{
+            events::RoomEvent,
+            record::{RecordEntry, RecordKind, RecordedCommand},
+            server::{FinalScore, Score},
+        },
+        tournament::{TournamentFormat, TournamentState},
+    };
 
     use super::*;
 
@@ -138,25 +1183,474 @@ mod tests {
     async fn create_room() {
         let (mut server, _) = Server::new();
         assert!(server.rooms.is_empty());
-        let _ = server.new_room();
+        let _ = server.new_room(RoomConfig::default(), RoomAccess::default());
         assert!(server.rooms.len() == 1);
     }
 
+    #[tokio::test]
+    async fn create_room_with_invalid_config() {
+        let (mut server, _) = Server::new();
+        let config = RoomConfig {
+            min_players: 1,
+            ..Default::default()
+        };
+        assert!(matches!(
+            server.new_room(config, RoomAccess::default()),
+            Err(ServerError::InvalidConfig)
+        ));
+        assert!(server.rooms.is_empty());
+    }
+
+    #[tokio::test]
+    async fn unknown_replay() {
+        let (server, server_commander) = Server::new();
+        spawn(server.run());
+        assert!(matches!(
+            server_commander.get_replay(42).await,
+            Err(ServerError::ReplayNotFound)
+        ));
+    }
+
+    #[tokio::test]
+    async fn private_room_needs_password_or_invite() {
+        let (mut server, _) = Server::new();
+        let room_id = server
+            .new_room(
+                RoomConfig::default(),
+                RoomAccess {
+                    password: Some("crab".into()),
+                    ..Default::default()
+                },
+            )
+            .unwrap()
+            .room_id;
+
+        assert!(matches!(
+            server.join_room(room_id, RoomKey::None, None),
+            Err(ServerError::WrongPassword)
+        ));
+        assert!(matches!(
+            server.join_room(room_id, RoomKey::Password("crabs".into()), None),
+            Err(ServerError::WrongPassword)
+        ));
+        assert!(server
+            .join_room(room_id, RoomKey::Password("crab".into()), None)
+            .is_ok());
+
+        let invite = server.create_invite(room_id, INVITE_DURATION).unwrap();
+        assert!(server
+            .join_room(room_id, RoomKey::Invite(invite.token), None)
+            .is_ok());
+        let invite = server.create_invite(room_id, Duration::ZERO).unwrap();
+        assert!(matches!(
+            server.join_room(room_id, RoomKey::Invite(invite.token), None),
+            Err(ServerError::InviteExpired)
+        ));
+        assert!(matches!(
+            server.join_room(room_id, RoomKey::Invite("1.2.3".into()), None),
+            Err(ServerError::InvalidInvite)
+        ));
+    }
+
+    #[tokio::test]
+    async fn rooms_get_distinct_ids() {
+        let (mut server, _) = Server::new();
+        for _ in 0..100 {
+            server
+                .new_room(RoomConfig::default(), RoomAccess::default())
+                .unwrap();
+        }
+        assert!(server.rooms.len() == 100);
+    }
+
+    #[tokio::test]
+    async fn only_public_rooms_are_listed() {
+        let (server, server_commander) = Server::new();
+        spawn(server.run());
+        let public = RoomAccess {
+            public: true,
+            ..Default::default()
+        };
+        let room_commander = server_commander
+            .new_room(RoomConfig::default(), public)
+            .await
+            .unwrap()
+            .commander;
+        room_commander.new_player("host".into()).await.unwrap();
+        server_commander
+            .new_room(RoomConfig::default(), RoomAccess::default())
+            .await
+            .unwrap();
+
+        let rooms = server_commander.list_rooms().await;
+        assert!(rooms.len() == 1);
+        assert!(rooms[0].players == 1);
+        assert!(rooms[0].host_name.as_deref() == Some("host"));
+    }
+
+    #[tokio::test]
+    async fn only_the_creator_deletes_a_room() {
+        let (server, server_commander) = Server::new();
+        spawn(server.run());
+        let room = server_commander
+            .new_room(RoomConfig::default(), RoomAccess::default())
+            .await
+            .unwrap();
+        let (_, mut player) = room.commander.new_player("test1".into()).await.unwrap();
+
+        assert!(matches!(
+            server_commander
+                .delete_room(room.room_id, "guess".into())
+                .await,
+            Err(ServerError::NotRoomHost)
+        ));
+        let summary = server_commander.room_summary(room.room_id).await.unwrap();
+        assert!(summary.players == 1);
+
+        server_commander
+            .delete_room(room.room_id, room.host_token)
+            .await
+            .unwrap();
+        room.commander.tx_channel.closed().await;
+        assert!(matches!(
+            player.recv().await,
+            Some(RoomEvent::PlayerJoined { .. })
+        ));
+        assert!(matches!(player.recv().await, Some(RoomEvent::RoomClosed)));
+        assert!(player.recv().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn bots_connect_with_their_token() {
+        let (server, server_commander) = Server::new();
+        spawn(server.run());
+        let registered = server_commander
+            .register_bot("clawbert".into())
+            .await
+            .unwrap();
+        assert!(matches!(
+            server_commander.register_bot("clawbert".into()).await,
+            Err(ServerError::InvalidBotName)
+        ));
+        assert!(matches!(
+            server_commander.authenticate_bot("nope".into()).await,
+            Err(ServerError::InvalidBotToken)
+        ));
+
+        let account = server_commander
+            .authenticate_bot(registered.token)
+            .await
+            .unwrap();
+        assert!(account.name == "clawbert");
+        assert!(account.profile.identity == "bot:clawbert");
+        assert!(account.profile.rating == INITIAL_RATING);
+    }
+
+    #[test]
+    fn finished_games_move_stored_ratings() {
+        let mut summary = testing_summary(1, &["ann", "bob", "cid"]);
+        summary.players[0].identity = Some("guest-a".into());
+        summary.players[1].identity = Some("guest-b".into());
+        summary.rating_changes = BTreeMap::from([(0, 16.0), (1, -16.0), (2, 3.0)]);
+        let mut history = InMemoryRepository::default();
+        update_ratings(&mut history, &summary).unwrap();
+        update_ratings(&mut history, &summary).unwrap();
+
+        let leaderboard = history.leaderboard(10).unwrap();
+        assert!(leaderboard.len() == 2);
+        assert!(leaderboard[0].name == "ann" && leaderboard[0].games == 2);
+        assert!(leaderboard[0].rating == INITIAL_RATING + 32.0);
+        assert!(leaderboard[1].rating == INITIAL_RATING - 32.0);
+    }
+
+    #[test]
+    fn stats_are_kept_by_identity() {
+        let mut entries = vec![];
+        for (player_id, name, identity) in [(0, "ann", Some("guest-a")), (1, "bob", None)] {
+            let profile = identity.map(|identity| PlayerProfile {
+                identity: identity.into(),
+                rating: INITIAL_RATING,
+            });
+            let command = RecordedCommand::AddPlayer(name.into(), profile, false);
+            let event = RoomEvent::PlayerJoined {
+                room_id: 0,
+                room_code: String::new(),
+                player_id,
+                player_name: name.into(),
+                player_list: BTreeMap::new(),
+                host: 0,
+                bot: false,
+                reconnect_token: None,
+            };
+            entries.push(RecordKind::Command(command));
+            entries.push(RecordKind::Event { to: None, event });
+        }
+        let score = |player_id, total_score| Score {
+            player_id,
+            cards: vec![],
+            total_score,
+            forfeited: false,
+        };
+        let event = RoomEvent::GameTerminated(FinalScore {
+            game_id: 1,
+            winner: 0,
+            scores: vec![score(0, 3), score(1, 9)],
+            seed: 0,
+            rating_changes: vec![],
+        });
+        entries.push(RecordKind::Event { to: None, event });
+        let record = GameRecord {
+            game_id: 1,
+            room_id: 0,
+            config: RoomConfig::default(),
+            seed: 0,
+            started_at: 0,
+            entries: entries
+                .into_iter()
+                .map(|kind| RecordEntry {
+                    elapsed_ms: 0,
+                    kind,
+                })
+                .collect(),
+        };
+
+        let mut history = InMemoryRepository::default();
+        store_game(&mut history, &record);
+        store_game(&mut history, &record);
+        let ann = history.player_stats("guest-a").unwrap().unwrap();
+        assert!(ann.name == "ann" && ann.games == 2 && ann.hands_won == 2);
+        assert!(history.player_stats("ann").unwrap().is_none());
+        assert!(history.player_stats("bob").unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn matchmade_players_share_a_room() {
+        let (server, server_commander) = Server::new();
+        spawn(server.run());
+        let request = |name: &str| MatchRequest {
+            name: name.into(),
+            players: 2,
+            rating_band: None,
+        };
+        for players in [0, 1, 50] {
+            let invalid = MatchRequest {
+                players,
+                ..request("cid")
+            };
+            assert!(matches!(
+                server_commander.matchmake(invalid, "guest-c".into()).await,
+                Err(ServerError::InvalidConfig)
+            ));
+        }
+
+        let (_, mut first) = server_commander
+            .matchmake(request("ann"), "guest-a".into())
+            .await
+            .unwrap();
+        assert!(matches!(first.recv().await, Some(QueueUpdate::Position(1))));
+        let (_, mut second) = server_commander
+            .matchmake(request("bob"), "guest-b".into())
+            .await
+            .unwrap();
+
+        let (Some(QueueUpdate::Found(first)), Some(QueueUpdate::Found(second))) =
+            (first.recv().await, second.recv().await)
+        else {
+            panic!("Players were not matched");
+        };
+        assert!(first.room_id == second.room_id);
+        assert!(first.player_id != second.player_id);
+        let summary = first.commander.get_summary().await.unwrap();
+        assert!(summary.players == 2);
+    }
+
+    #[tokio::test]
+    async fn players_gone_before_their_seat_is_found_are_removed() {
+        let request = |id| MatchRequest {
+            name: format!("player {id}"),
+            players: 2,
+            rating_band: None,
+        };
+        let (room_server, commander) = RoomServer::new(request(0).room_config());
+        spawn(room_server.run());
+        let mut receivers = vec![];
+        let group = (0..2)
+            .map(|id| {
+                let (tx, rx) = mpsc::unbounded_channel();
+                receivers.push(rx);
+                Ticket {
+                    id,
+                    request: request(id),
+                    profile: PlayerProfile {
+                        identity: format!("guest-{id}"),
+                        rating: INITIAL_RATING,
+                    },
+                    tx,
+                }
+            })
+            .collect();
+        receivers.truncate(1);
+
+        Server::seat_match(0, commander.clone(), group).await;
+        assert!(matches!(
+            receivers[0].recv().await,
+            Some(QueueUpdate::Found(_))
+        ));
+        let summary = commander.get_summary().await.unwrap();
+        assert!(summary.players == 1);
+    }
+
+    #[tokio::test]
+    async fn tournament_tables_get_private_rooms() {
+        let (mut server, _) = Server::new();
+        let config = TournamentConfig {
+            name: "office".into(),
+            format: TournamentFormat::Swiss { rounds: 1 },
+            table_size: 2,
+            room: RoomConfig::default(),
+        };
+        let created = server.new_tournament(config).unwrap();
+        let hosted = server.tournaments.get_mut(&created.tournament_id).unwrap();
+        let mut feed = hosted.tournament.subscribe();
+        let mut tokens = BTreeMap::new();
+        for name in ["ann", "bob", "cid", "dan"] {
+            let token = hosted.tournament.register(name.into()).unwrap();
+            tokens.insert(name.to_string(), token);
+        }
+        assert!(matches!(
+            server.get_seat(created.tournament_id, &tokens["ann"]),
+            Err(ServerError::NotSeated)
+        ));
+
+        assert!(matches!(
+            server.start_tournament(created.tournament_id, "guess"),
+            Err(ServerError::NotTournamentHost)
+        ));
+        server
+            .start_tournament(created.tournament_id, &created.host_token)
+            .unwrap();
+        assert!(server.rooms.len() == 2);
+
+        assert!(matches!(
+            server.get_seat(created.tournament_id, "guess"),
+            Err(ServerError::InvalidParticipantToken)
+        ));
+
+        let mut rooms = vec![];
+        while let Ok(event) = feed.try_recv() {
+            if let TournamentEvent::TableStarted {
+                room_id, players, ..
+            } = event
+            {
+                assert!(server.public_rooms().is_empty());
+                assert!(matches!(
+                    server.join_room(room_id, RoomKey::None, None),
+                    Err(ServerError::WrongPassword)
+                ));
+                let (name, other) = (players[0].clone(), players[1].clone());
+                let seat = server
+                    .get_seat(created.tournament_id, &tokens[&name])
+                    .unwrap();
+                assert!(seat.room_id == room_id);
+                let invite = || RoomKey::Invite(seat.token.clone());
+                assert!(server.join_room(room_id, invite(), Some(&name)).is_ok());
+                for name in [Some(other.as_str()), Some("eve"), None] {
+                    assert!(matches!(
+                        server.join_room(room_id, invite(), name),
+                        Err(ServerError::InvalidInvite)
+                    ));
+                }
+                rooms.push(room_id);
+            }
+        }
+        assert!(rooms.len() == 2);
+
+        // Rooms gone before their game ended do not hold the round up.
+        for room_id in rooms {
+            server.destroy_room(room_id);
+        }
+        let hosted = &server.tournaments[&created.tournament_id];
+        assert!(matches!(
+            hosted.tournament.state(),
+            TournamentState::Finished { .. }
+        ));
+    }
+
+    #[tokio::test]
+    async fn tables_not_started_in_time_are_forfeited() {
+        pause();
+        let (server, server_commander) = Server::new();
+        spawn(server.run());
+        let config = TournamentConfig {
+            name: "office".into(),
+            format: TournamentFormat::Swiss { rounds: 1 },
+            table_size: 2,
+            room: RoomConfig::default(),
+        };
+        let created = server_commander.new_tournament(config).await.unwrap();
+        let tournament_id = created.tournament_id;
+        let ann = server_commander
+            .register_participant(tournament_id, "ann".into())
+            .await
+            .unwrap();
+        server_commander
+            .register_participant(tournament_id, "bob".into())
+            .await
+            .unwrap();
+        server_commander
+            .start_tournament(tournament_id, created.host_token)
+            .await
+            .unwrap();
+
+        let seat = server_commander
+            .get_seat(tournament_id, ann.token)
+            .await
+            .unwrap();
+        let commander = server_commander
+            .join_room(
+                seat.room_id,
+                RoomKey::Invite(seat.token),
+                Some("ann".into()),
+            )
+            .await
+            .unwrap();
+        commander.new_player("ann".into()).await.unwrap();
+
+        sleep(TABLE_SEATING_DEADLINE + Duration::from_secs(1)).await;
+        let standings = server_commander
+            .get_tournament(tournament_id)
+            .await
+            .unwrap();
+        assert!(
+            standings.state
+                == TournamentState::Finished {
+                    winner: "ann".into()
+                }
+        );
+        assert!(matches!(
+            server_commander.room_summary(seat.room_id).await,
+            Err(ServerError::RoomNotFound)
+        ));
+    }
+
     #[tokio::test]
     async fn join_and_auto_delete_room() {
         let (server, server_commander) = Server::new();
         spawn(server.run());
-        let room_commander = server_commander.new_room().await;
+        let room_commander = server_commander
+            .new_room(RoomConfig::default(), RoomAccess::default())
+            .await
+            .unwrap()
+            .commander;
         let (_, mut player) = room_commander.new_player("test1".into()).await.unwrap();
         if let Ok(RoomEvent::PlayerJoined {
-            room_id,
-            player_id,
-            player_name: _,
-            player_list: _,
+            room_id, player_id, ..
         }) = player.try_recv()
         {
             room_commander.remove_player(player_id).await;
-            let res = server_commander.join_room(room_id).await;
+            let res = server_commander
+                .join_room(room_id, RoomKey::None, None)
+                .await;
             assert!(matches!(res, Err(ServerError::RoomNotFound)))
         } else {
             panic!("Did not receive player joined event");
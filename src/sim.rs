@@ -0,0 +1,256 @@
+use std::{collections::BTreeMap, fmt::Write};
+
+use serde::Serialize;
+use tokio::spawn;
+
+use crate::{
+    bot::{Bot, BotLevel},
+    consts::PlayerName,
+    history::GameSummary,
+    room::{
+        config::RoomConfig,
+        events::{RoomEvent, RoomNotification},
+        server::RoomServer,
+    },
+    stats::{PlayerStats, StatsAggregator},
+};
+
+/// Games running longer are abandoned, in case no bot ever calls crabul.
+pub const MAX_TURNS: u32 = 1000;
+/// Names of the powers in reports, as counted by `StatsAggregator`.
+const POWERS: [&str; 4] = ["PeekOwnCard", "PeekOtherCard", "BlindSwap", "CheckAndSwap"];
+
+/// What to simulate. Game `n` is played with seed `seed + n`, for both the
+/// room and its bots, so that a run can be repeated.
+pub struct Simulation {
+    pub games: u32,
+    pub seed: u64,
+    /// Strategy of each seat, the table has as many players.
+    pub seats: Vec<BotLevel>,
+    /// Rule options, player counts are taken from `seats`.
+    pub config: RoomConfig,
+}
+
+#[derive(Serialize)]
+pub struct SeatReport {
+    pub seat: usize,
+    pub strategy: BotLevel,
+    pub wins: u32,
+    pub win_rate: f64,
+    pub average_score: f64,
+    /// Times each power was used per game.
+    pub powers_per_game: BTreeMap<String, f64>,
+}
+
+#[derive(Serialize)]
+pub struct SimReport {
+    pub games: u32,
+    /// Games stopped after `MAX_TURNS` turns, left out of every average.
+    pub abandoned: u32,
+    pub average_turns: f64,
+    /// Simulated time, bot thinking time and countdowns included.
+    pub average_duration_ms: f64,
+    /// Times each power was used per game, all seats together.
+    pub powers_per_game: BTreeMap<String, f64>,
+    pub seats: Vec<SeatReport>,
+}
+
+#[derive(Default)]
+struct SeatTotals {
+    wins: u32,
+    score: i64,
+    powers: BTreeMap<String, u32>,
+}
+
+impl Simulation {
+    pub fn room_config(&self, game: u32) -> RoomConfig {
+        RoomConfig {
+            min_players: self.seats.len(),
+            max_players: self.seats.len(),
+            seed: Some(self.seed.wrapping_add(game as u64)),
+            ..self.config.clone()
+        }
+    }
+
+    /// Best run on a runtime with paused time, where countdowns and bot
+    /// thinking time elapse at once.
+    pub async fn run(&self) -> SimReport {
+        let mut seats: Vec<SeatTotals> = self.seats.iter().map(|_| SeatTotals::default()).collect();
+        let mut finished = 0;
+        let mut turns = 0;
+        let mut duration_ms = 0;
+        for game in 0..self.games {
+            let Some(result) = self.play(game).await else {
+                continue;
+            };
+            finished += 1;
+            duration_ms += result.duration_ms;
+            let stats = result.stats;
+            for (seat, totals) in seats.iter_mut().enumerate() {
+                let Some(player) = stats.get(&seat_name(seat)) else {
+                    continue;
+                };
+                turns += player.turns;
+                totals.wins += (result.winner == seat_name(seat)) as u32;
+                totals.score += player.total_final_score;
+                for (power, count) in player.powers_used.iter() {
+                    *totals.powers.entry(power.clone()).or_insert(0) += count;
+                }
+            }
+        }
+
+        let per_game = |total: f64| match finished {
+            0 => 0.0,
+            _ => total / finished as f64,
+        };
+        let powers_per_game = POWERS
+            .iter()
+            .map(|power| {
+                let total: u32 = seats
+                    .iter()
+                    .filter_map(|seat| seat.powers.get(*power))
+                    .sum();
+                (power.to_string(), per_game(total as f64))
+            })
+            .collect();
+        SimReport {
+            games: finished,
+            abandoned: self.games - finished,
+            average_turns: per_game(turns as f64),
+            average_duration_ms: per_game(duration_ms as f64),
+            powers_per_game,
+            seats: seats
+                .into_iter()
+                .enumerate()
+                .map(|(seat, totals)| SeatReport {
+                    seat,
+                    strategy: self.seats[seat],
+                    wins: totals.wins,
+                    win_rate: per_game(totals.wins as f64),
+                    average_score: per_game(totals.score as f64),
+                    powers_per_game: POWERS
+                        .iter()
+                        .map(|power| {
+                            let count = totals.powers.get(*power).copied().unwrap_or(0);
+                            (power.to_string(), per_game(count as f64))
+                        })
+                        .collect(),
+                })
+                .collect(),
+        }
+    }
+
+    /// `None` when the game was abandoned.
+    async fn play(&self, game: u32) -> Option<GameResult> {
+        let config = self.room_config(game);
+        let seed = config.seed.unwrap_or_default();
+        let (mut room_server, room_commander) = RoomServer::with_id(0, config);
+        let mut notifications = room_server.notifications();
+        spawn(room_server.run());
+
+        let (_, mut spectator_channel) = room_commander.new_spectator().await;
+        for (seat, level) in self.seats.iter().enumerate() {
+            let (player_id, player_channel) =
+                room_commander.new_bot(seat_name(seat), None).await.ok()?;
+            let bot = Bot::new(player_id, *level, room_commander.clone(), player_channel)
+                .with_seed(seed.wrapping_add(seat as u64));
+            spawn(bot.run());
+        }
+
+        let mut turns = 0;
+        while let Some(event) = spectator_channel.recv().await {
+            if let RoomEvent::PlayerTurn(_) = event {
+                turns += 1;
+                if turns > MAX_TURNS {
+                    room_commander.close();
+                    return None;
+                }
+            }
+        }
+        let RoomNotification::GameFinished(record) = notifications.recv().await?;
+        let summary = GameSummary::from_record(&record)?;
+        let winner = summary
+            .players
+            .iter()
+            .find(|player| player.player_id == summary.winner)?
+            .name
+            .clone();
+        Some(GameResult {
+            winner,
+            duration_ms: summary.duration_ms,
+            stats: StatsAggregator::from_record(&record)
+                .finish()
+                .into_values()
+                .map(|stats| (stats.name.clone(), stats))
+                .collect(),
+        })
+    }
+}
+
+struct GameResult {
+    winner: PlayerName,
+    duration_ms: u64,
+    stats: BTreeMap<PlayerName, PlayerStats>,
+}
+
+fn seat_name(seat: usize) -> PlayerName {
+    format!("Seat {seat}")
+}
+
+impl SimReport {
+    /// One row per seat, the game wide figures repeated on each.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from(
+            "seat,strategy,games,abandoned,wins,win_rate,average_score,average_turns,average_duration_ms",
+        );
+        for power in POWERS {
+            let _ = write!(csv, ",{power}");
+        }
+        csv.push('\n');
+        for seat in self.seats.iter() {
+            let _ = write!(
+                csv,
+                "{},{:?},{},{},{},{:.4},{:.2},{:.2},{:.0}",
+                seat.seat,
+                seat.strategy,
+                self.games,
+                self.abandoned,
+                seat.wins,
+                seat.win_rate,
+                seat.average_score,
+                self.average_turns,
+                self.average_duration_ms,
+            );
+            for power in POWERS {
+                let _ = write!(csv, ",{:.3}", seat.powers_per_game[power]);
+            }
+            csv.push('\n');
+        }
+        csv
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn bots_play_whole_games() {
+        let simulation = Simulation {
+            games: 3,
+            seed: 7,
+            seats: vec![BotLevel::Memory, BotLevel::Random],
+            config: RoomConfig::default(),
+        };
+        let report = simulation.run().await;
+        assert!(report.games + report.abandoned == 3);
+        assert!(report.games > 0);
+        let wins: u32 = report.seats.iter().map(|seat| seat.wins).sum();
+        assert!(wins == report.games);
+        assert!(report.average_turns > 0.0);
+
+        let csv = report.to_csv();
+        assert!(csv.lines().count() == 3);
+        assert!(csv.lines().nth(1).unwrap().starts_with("0,Memory,"));
+    }
+}
@@ -0,0 +1,71 @@
+use std::{env, process::ExitCode};
+
+use crabul::{bot::BotLevel, room::config::RoomConfig, sim::Simulation};
+
+const USAGE: &str = "usage: crabul-sim [--games N] [--seed N] [--bots memory,random,...] \
+[--config RULES_JSON] [--format json|csv]";
+
+enum Format {
+    Json,
+    Csv,
+}
+
+fn parse_args() -> Result<(Simulation, Format), String> {
+    let mut simulation = Simulation {
+        games: 1000,
+        seed: 0,
+        seats: vec![BotLevel::Memory, BotLevel::Random],
+        config: RoomConfig::default(),
+    };
+    let mut format = Format::Json;
+    let mut args = env::args().skip(1);
+    while let Some(flag) = args.next() {
+        let value = args.next().ok_or(format!("missing value for {flag}"))?;
+        match flag.as_str() {
+            "--games" => simulation.games = value.parse().map_err(|_| "invalid --games")?,
+            "--seed" => simulation.seed = value.parse().map_err(|_| "invalid --seed")?,
+            "--bots" => {
+                simulation.seats = value
+                    .split(',')
+                    .map(|level| level.trim().parse::<BotLevel>())
+                    .collect::<Result<_, _>>()
+                    .map_err(|_| "invalid --bots")?
+            }
+            "--config" => {
+                simulation.config = serde_json::from_str(&value)
+                    .map_err(|err| format!("invalid --config: {err}"))?
+            }
+            "--format" => {
+                format = match value.as_str() {
+                    "json" => Format::Json,
+                    "csv" => Format::Csv,
+                    _ => return Err("invalid --format".into()),
+                }
+            }
+            _ => return Err(format!("unknown flag {flag}")),
+        }
+    }
+    if !simulation.room_config(0).is_valid() {
+        return Err("the rules do not allow this number of bots".into());
+    }
+    Ok((simulation, format))
+}
+
+/// Plays bot against bot on rooms driven directly, without the web server.
+/// Time is paused, so countdowns and bot thinking time cost nothing.
+#[tokio::main(flavor = "current_thread", start_paused = true)]
+async fn main() -> ExitCode {
+    let (simulation, format) = match parse_args() {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("{err}\n{USAGE}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let report = simulation.run().await;
+    match format {
+        Format::Json => println!("{}", serde_json::to_string_pretty(&report).unwrap()),
+        Format::Csv => print!("{}", report.to_csv()),
+    }
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,318 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    consts::{Identity, PlayerId, PlayerName},
+    room::{
+        events::RoomEvent,
+        record::{GameRecord, RecordKind},
+        server::{DuplicateCardResult, Power},
+    },
+};
+
+/// Counters kept for a player across games. Averages and rates are derived
+/// from them in `StatsReport`.
+#[derive(Deserialize, Serialize, Clone, Default, PartialEq, Debug)]
+pub struct PlayerStats {
+    pub identity: Identity,
+    /// Name last played under.
+    pub name: PlayerName,
+    pub games: u32,
+    /// Hands played to the end, a match having one per round.
+    pub hands: u32,
+    pub hands_won: u32,
+    pub total_final_score: i64,
+    pub crabul_calls: u32,
+    /// Calls after which the caller won the hand.
+    pub crabul_successes: u32,
+    pub duplicate_successes: u32,
+    pub duplicate_not_the_same: u32,
+    pub duplicate_too_late: u32,
+    /// Times each power was used, by power name.
+    pub powers_used: BTreeMap<String, u32>,
+    pub timeouts: u32,
+    pub turns: u32,
+    pub total_turn_ms: u64,
+}
+
+impl PlayerStats {
+    pub fn new(identity: Identity, name: PlayerName) -> Self {
+        Self {
+            identity,
+            name,
+            ..Default::default()
+        }
+    }
+
+    pub fn merge(&mut self, other: &PlayerStats) {
+        self.games += other.games;
+        self.hands += other.hands;
+        self.hands_won += other.hands_won;
+        self.total_final_score += other.total_final_score;
+        self.crabul_calls += other.crabul_calls;
+        self.crabul_successes += other.crabul_successes;
+        self.duplicate_successes += other.duplicate_successes;
+        self.duplicate_not_the_same += other.duplicate_not_the_same;
+        self.duplicate_too_late += other.duplicate_too_late;
+        for (power, count) in other.powers_used.iter() {
+            *self.powers_used.entry(power.clone()).or_insert(0) += count;
+        }
+        self.timeouts += other.timeouts;
+        self.turns += other.turns;
+        self.total_turn_ms += other.total_turn_ms;
+    }
+}
+
+/// What `GET /players/{identity}/stats` returns.
+#[derive(Serialize)]
+pub struct StatsReport {
+    #[serde(flatten)]
+    pub stats: PlayerStats,
+    pub average_final_score: Option<f64>,
+    pub crabul_success_rate: Option<f64>,
+    /// Share of duplicate throws that matched the card thrown on.
+    pub duplicate_accuracy: Option<f64>,
+    pub average_turn_ms: Option<f64>,
+}
+
+impl From<PlayerStats> for StatsReport {
+    fn from(stats: PlayerStats) -> Self {
+        let ratio = |part: f64, total: u32| (total > 0).then(|| part / total as f64);
+        let duplicates =
+            stats.duplicate_successes + stats.duplicate_not_the_same + stats.duplicate_too_late;
+        Self {
+            average_final_score: ratio(stats.total_final_score as f64, stats.hands),
+            crabul_success_rate: ratio(stats.crabul_successes as f64, stats.crabul_calls),
+            duplicate_accuracy: ratio(stats.duplicate_successes as f64, duplicates),
+            average_turn_ms: ratio(stats.total_turn_ms as f64, stats.turns),
+            stats,
+        }
+    }
+}
+
+/// Follows the public events of a room and tallies what each player did.
+#[derive(Default)]
+pub struct StatsAggregator {
+    players: BTreeMap<PlayerId, PlayerStats>,
+    /// Player whose turn is running and when it started.
+    turn: Option<(PlayerId, u64)>,
+    crabul_caller: Option<PlayerId>,
+}
+
+impl StatsAggregator {
+    /// Replays the public events of a finished game.
+    pub fn from_record(record: &GameRecord) -> Self {
+        let mut aggregator = Self::default();
+        for entry in record.entries.iter() {
+            if let RecordKind::Event { to: None, event } = &entry.kind {
+                aggregator.observe(entry.elapsed_ms, event);
+            }
+        }
+        aggregator
+    }
+
+    /// `elapsed_ms` is the time at which the event happened.
+    pub fn observe(&mut self, elapsed_ms: u64, event: &RoomEvent) {
+        match event {
+            RoomEvent::PlayerJoined {
+                player_id,
+                player_name,
+                ..
+            } => {
+                let stats = PlayerStats {
+                    name: player_name.clone(),
+                    ..Default::default()
+                };
+                self.players.insert(*player_id, stats);
+            }
+            RoomEvent::PlayerTurn(player_id) => {
+                self.end_turn(elapsed_ms);
+                self.turn = Some((*player_id, elapsed_ms));
+            }
+            RoomEvent::PowerUsed(power, player_id, ..) => {
+                if let (Some(name), Some(stats)) =
+                    (power_name(power), self.players.get_mut(player_id))
+                {
+                    *stats.powers_used.entry(name.into()).or_insert(0) += 1;
+                }
+            }
+            RoomEvent::DuplicateCardAttempt(player_id, _, _, _, result) => {
+                if let Some(stats) = self.players.get_mut(player_id) {
+                    match result {
+                        DuplicateCardResult::Success => stats.duplicate_successes += 1,
+                        DuplicateCardResult::NotTheSame => stats.duplicate_not_the_same += 1,
+                        DuplicateCardResult::TooLate => stats.duplicate_too_late += 1,
+                    }
+                }
+            }
+            RoomEvent::PlayerWentCrabul(player_id) => {
+                self.crabul_caller = Some(*player_id);
+                if let Some(stats) = self.players.get_mut(player_id) {
+                    stats.crabul_calls += 1;
+                }
+            }
+            RoomEvent::TurnEndedByTimeout(player_id) => {
+                if let Some(stats) = self.players.get_mut(player_id) {
+                    stats.timeouts += 1;
+                }
+            }
+            RoomEvent::GameTerminated(final_score) => {
+                self.end_turn(elapsed_ms);
+                if let Some(caller) = self.crabul_caller.take() {
+                    if caller == final_score.winner {
+                        if let Some(stats) = self.players.get_mut(&caller) {
+                            stats.crabul_successes += 1;
+                        }
+                    }
+                }
+                for score in final_score.scores.iter().filter(|score| !score.forfeited) {
+                    if let Some(stats) = self.players.get_mut(&score.player_id) {
+                        stats.hands += 1;
+                        stats.hands_won += (score.player_id == final_score.winner) as u32;
+                        stats.total_final_score += score.total_score as i64;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Stats of everyone who played a hand to the end, by seat. Events carry
+    /// no identity, it is left for the caller to fill in.
+    pub fn finish(self) -> BTreeMap<PlayerId, PlayerStats> {
+        self.players
+            .into_iter()
+            .filter(|(_, stats)| stats.hands > 0)
+            .map(|(player_id, mut stats)| {
+                stats.games = 1;
+                (player_id, stats)
+            })
+            .collect()
+    }
+
+    fn end_turn(&mut self, elapsed_ms: u64) {
+        if let Some((player_id, started_at)) = self.turn.take() {
+            if let Some(stats) = self.players.get_mut(&player_id) {
+                stats.turns += 1;
+                stats.total_turn_ms += elapsed_ms.saturating_sub(started_at);
+            }
+        }
+    }
+}
+
+/// Check and swap shows up twice, it is counted on its first stage.
+fn power_name(power: &Power) -> Option<&'static str> {
+    match power {
+        Power::PeekOwnCard => Some("PeekOwnCard"),
+        Power::PeekOtherCard => Some("PeekOtherCard"),
+        Power::BlindSwap => Some("BlindSwap"),
+        Power::CheckAndSwapStage1 => Some("CheckAndSwap"),
+        Power::CheckAndSwapStage2(..) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::room::server::{FinalScore, Score};
+
+    use super::*;
+
+    fn joined(player_id: PlayerId, name: &str) -> RoomEvent {
+        RoomEvent::PlayerJoined {
+            room_id: 0,
+            room_code: String::new(),
+            player_id,
+            player_name: name.into(),
+            player_list: BTreeMap::new(),
+            host: 0,
+            bot: false,
+            reconnect_token: None,
+        }
+    }
+
+    fn score(player_id: PlayerId, total_score: i32) -> Score {
+        Score {
+            player_id,
+            cards: vec![],
+            total_score,
+            forfeited: false,
+        }
+    }
+
+    #[test]
+    fn events_are_tallied_per_player() {
+        let mut aggregator = StatsAggregator::default();
+        let events = [
+            (0, joined(0, "ann")),
+            (0, joined(1, "bob")),
+            (100, RoomEvent::PlayerTurn(0)),
+            (
+                200,
+                RoomEvent::PowerUsed(Power::CheckAndSwapStage1, 0, None, Some(1), Some(0)),
+            ),
+            (
+                300,
+                RoomEvent::PowerUsed(Power::CheckAndSwapStage2(1, 0), 0, None, Some(1), Some(0)),
+            ),
+            (1100, RoomEvent::PlayerTurn(1)),
+            (
+                1200,
+                RoomEvent::DuplicateCardAttempt(0, 0, 1, None, DuplicateCardResult::TooLate),
+            ),
+            (
+                1300,
+                RoomEvent::DuplicateCardAttempt(0, 1, 1, None, DuplicateCardResult::Success),
+            ),
+            (4100, RoomEvent::TurnEndedByTimeout(1)),
+            (4100, RoomEvent::PlayerTurn(0)),
+            (4500, RoomEvent::PlayerWentCrabul(0)),
+            (4600, RoomEvent::PlayerTurn(1)),
+            (
+                5600,
+                RoomEvent::GameTerminated(FinalScore {
+                    game_id: 0,
+                    winner: 0,
+                    scores: vec![score(0, 3), score(1, 12)],
+                    seed: 0,
+                    rating_changes: vec![],
+                }),
+            ),
+        ];
+        for (elapsed_ms, event) in events.iter() {
+            aggregator.observe(*elapsed_ms, event);
+        }
+        let stats = aggregator.finish();
+
+        let ann = &stats[&0];
+        assert!(ann.games == 1 && ann.hands == 1 && ann.hands_won == 1);
+        assert!(ann.crabul_calls == 1 && ann.crabul_successes == 1);
+        assert!(ann.powers_used == BTreeMap::from([("CheckAndSwap".to_string(), 1)]));
+        assert!(ann.turns == 2 && ann.total_turn_ms == 1500);
+
+        let bob = StatsReport::from(stats[&1].clone());
+        assert!(bob.stats.timeouts == 1);
+        assert!(bob.average_final_score == Some(12.0));
+        assert!(bob.average_turn_ms == Some(2000.0));
+        assert!(bob.crabul_success_rate.is_none());
+
+        let ann = StatsReport::from(ann.clone());
+        assert!(ann.duplicate_accuracy == Some(0.5));
+    }
+
+    #[test]
+    fn stats_add_up_across_games() {
+        let mut total = PlayerStats::new("guest-a".into(), "ann".into());
+        let game = PlayerStats {
+            games: 1,
+            hands: 2,
+            total_final_score: 9,
+            powers_used: BTreeMap::from([("BlindSwap".to_string(), 2)]),
+            ..PlayerStats::new("guest-a".into(), "ann".into())
+        };
+        total.merge(&game);
+        total.merge(&game);
+        assert!(total.games == 2 && total.hands == 4 && total.total_final_score == 18);
+        assert!(total.powers_used["BlindSwap"] == 4);
+    }
+}
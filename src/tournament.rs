@@ -0,0 +1,591 @@
+use rand::{thread_rng, Rng};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+
+use crate::{
+    consts::{ParticipantToken, PlayerName, RoomCode, RoomId, TournamentId},
+    history::GameSummary,
+    invite::secrets_match,
+    room::{config::RoomConfig, consts::MAX_PLAYERS},
+    room_code,
+};
+
+#[derive(Serialize, PartialEq, Debug)]
+pub enum TournamentError {
+    EmptyName,
+    NameAlreadyExists,
+    AlreadyStarted,
+    NotEnoughParticipants,
+}
+
+#[derive(Deserialize, Serialize, Clone, PartialEq, Debug)]
+pub enum TournamentFormat {
+    /// The best finishers of each table go on until a single table is left.
+    SingleElimination { advancing_per_table: usize },
+    /// Everyone plays every round, at tables of players with similar points.
+    Swiss { rounds: u32 },
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+pub struct TournamentConfig {
+    pub name: String,
+    pub format: TournamentFormat,
+    /// Tables are filled up to this size, and never left with a single player.
+    pub table_size: usize,
+    /// Settings of every table, player counts aside.
+    #[serde(default)]
+    pub room: RoomConfig,
+}
+
+impl TournamentConfig {
+    /// Single elimination needs every table to send at most half its players
+    /// on, or the rounds would never shrink.
+    pub fn is_valid(&self) -> bool {
+        let format_is_valid = match self.format {
+            TournamentFormat::SingleElimination {
+                advancing_per_table,
+            } => advancing_per_table > 0 && advancing_per_table * 2 <= self.table_size,
+            TournamentFormat::Swiss { rounds } => rounds > 0,
+        };
+        (2..=MAX_PLAYERS).contains(&self.table_size)
+            && format_is_valid
+            && self.table_config(self.table_size).is_valid()
+    }
+
+    pub fn table_config(&self, players: usize) -> RoomConfig {
+        RoomConfig {
+            min_players: players,
+            max_players: players,
+            ..self.room.clone()
+        }
+    }
+}
+
+#[derive(Serialize, Clone)]
+pub struct Participant {
+    pub name: PlayerName,
+    /// One point for every player finished ahead of at each table.
+    pub points: u32,
+    pub tables_played: u32,
+    pub eliminated: bool,
+}
+
+#[derive(Serialize, Clone)]
+pub struct Table {
+    pub round: u32,
+    pub table: usize,
+    pub room_id: RoomId,
+    pub players: Vec<PlayerName>,
+    /// Best first, set once the game of the table is over.
+    pub placings: Option<Vec<PlayerName>>,
+}
+
+#[derive(Serialize, Clone, PartialEq, Debug)]
+pub enum TournamentState {
+    Registration,
+    Running { round: u32 },
+    Finished { winner: PlayerName },
+}
+
+/// Sent on the feed of a tournament, which anybody can watch. Players of a
+/// table fetch their invite with their participant token.
+#[derive(Deserialize, Serialize, Clone)]
+pub enum TournamentEvent {
+    ParticipantRegistered(PlayerName),
+    RoundStarted(u32),
+    TableStarted {
+        round: u32,
+        table: usize,
+        room_id: RoomId,
+        room_code: RoomCode,
+        players: Vec<PlayerName>,
+    },
+    TableFinished {
+        round: u32,
+        table: usize,
+        placings: Vec<PlayerName>,
+    },
+    TournamentFinished {
+        winner: PlayerName,
+    },
+}
+
+/// What `GET /tournaments/{id}` returns, participants ranked best first.
+#[derive(Serialize)]
+pub struct TournamentStandings {
+    pub tournament_id: TournamentId,
+    pub config: TournamentConfig,
+    pub state: TournamentState,
+    pub standings: Vec<Participant>,
+    pub tables: Vec<Table>,
+}
+
+pub struct Tournament {
+    id: TournamentId,
+    config: TournamentConfig,
+    state: TournamentState,
+    /// In registration order, which is also the seeding.
+    participants: Vec<Participant>,
+    tokens: Vec<(ParticipantToken, PlayerName)>,
+    tables: Vec<Table>,
+    subscribers: Vec<UnboundedSender<TournamentEvent>>,
+}
+
+impl Tournament {
+    pub fn new(id: TournamentId, config: TournamentConfig) -> Self {
+        Self {
+            id,
+            config,
+            state: TournamentState::Registration,
+            participants: vec![],
+            tokens: vec![],
+            tables: vec![],
+            subscribers: vec![],
+        }
+    }
+
+    pub fn config(&self) -> &TournamentConfig {
+        &self.config
+    }
+
+    pub fn state(&self) -> &TournamentState {
+        &self.state
+    }
+
+    /// The token returned is the only way to get the invites of the tables.
+    pub fn register(&mut self, name: PlayerName) -> Result<ParticipantToken, TournamentError> {
+        if self.state != TournamentState::Registration {
+            return Err(TournamentError::AlreadyStarted);
+        }
+        if name.is_empty() {
+            return Err(TournamentError::EmptyName);
+        }
+        if self.participants.iter().any(|other| other.name == name) {
+            return Err(TournamentError::NameAlreadyExists);
+        }
+        self.participants.push(Participant {
+            name: name.clone(),
+            points: 0,
+            tables_played: 0,
+            eliminated: false,
+        });
+        let token = format!("{:032x}", thread_rng().gen::<u128>());
+        self.tokens.push((token.clone(), name.clone()));
+        self.broadcast(TournamentEvent::ParticipantRegistered(name));
+        Ok(token)
+    }
+
+    pub fn participant(&self, token: &str) -> Option<&PlayerName> {
+        self.tokens
+            .iter()
+            .find(|(expected, _)| secrets_match(expected, token))
+            .map(|(_, name)| name)
+    }
+
+    /// The table the participant still has to play, if seated.
+    pub fn table_of(&self, name: &PlayerName) -> Option<&Table> {
+        self.tables
+            .iter()
+            .find(|table| table.placings.is_none() && table.players.contains(name))
+    }
+
+    /// Players of the unfinished table played in the room.
+    pub fn table_players(&self, room_id: RoomId) -> Option<&[PlayerName]> {
+        self.tables
+            .iter()
+            .find(|table| table.room_id == room_id && table.placings.is_none())
+            .map(|table| table.players.as_slice())
+    }
+
+    /// Closes the registration and returns the tables of the first round.
+    pub fn start(&mut self) -> Result<Vec<Vec<PlayerName>>, TournamentError> {
+        if self.state != TournamentState::Registration {
+            return Err(TournamentError::AlreadyStarted);
+        }
+        if self.participants.len() < 2 {
+            return Err(TournamentError::NotEnoughParticipants);
+        }
+        Ok(self.next_round())
+    }
+
+    /// Records the room made for a table of the current round.
+    pub fn add_table(&mut self, room_id: RoomId, players: Vec<PlayerName>) {
+        let TournamentState::Running { round } = self.state else {
+            return;
+        };
+        let table = self
+            .tables
+            .iter()
+            .filter(|table| table.round == round)
+            .count();
+        self.broadcast(TournamentEvent::TableStarted {
+            round,
+            table,
+            room_id,
+            room_code: room_code::to_code(room_id),
+            players: players.clone(),
+        });
+        self.tables.push(Table {
+            round,
+            table,
+            room_id,
+            players,
+            placings: None,
+        });
+    }
+
+    pub fn plays_in(&self, room_id: RoomId) -> bool {
+        self.table_players(room_id).is_some()
+    }
+
+    /// Places the players of the table whose game just ended, players who
+    /// did not show up coming last. Returns the tables of the next round once
+    /// the current one is over.
+    pub fn record_result(&mut self, summary: &GameSummary) -> Option<Vec<Vec<PlayerName>>> {
+        let name_of = |player_id| {
+            summary
+                .players
+                .iter()
+                .find(|player| player.player_id == player_id)
+                .map(|player| player.name.clone())
+        };
+        let finish_order = std::iter::once(summary.winner).chain(
+            summary
+                .final_score
+                .scores
+                .iter()
+                .map(|score| score.player_id),
+        );
+        let finish_order: Vec<PlayerName> = finish_order.filter_map(name_of).collect();
+        self.place_table(summary.room_id, &finish_order)
+    }
+
+    /// Ends a table whose game never got played: the players who showed up
+    /// place ahead of the others, in seating order otherwise.
+    pub fn abandon_table(
+        &mut self,
+        room_id: RoomId,
+        present: &[PlayerName],
+    ) -> Option<Vec<Vec<PlayerName>>> {
+        let players = self.table_players(room_id)?;
+        let (mut finish_order, absent): (Vec<PlayerName>, Vec<PlayerName>) = players
+            .iter()
+            .cloned()
+            .partition(|name| present.contains(name));
+        finish_order.extend(absent);
+        self.place_table(room_id, &finish_order)
+    }
+
+    fn place_table(
+        &mut self,
+        room_id: RoomId,
+        finish_order: &[PlayerName],
+    ) -> Option<Vec<Vec<PlayerName>>> {
+        let table = self
+            .tables
+            .iter_mut()
+            .find(|table| table.room_id == room_id && table.placings.is_none())?;
+
+        let mut placings: Vec<PlayerName> = vec![];
+        for name in finish_order {
+            if table.players.contains(name) && !placings.contains(name) {
+                placings.push(name.clone());
+            }
+        }
+        for name in table.players.iter() {
+            if !placings.contains(name) {
+                placings.push(name.clone());
+            }
+        }
+
+        let table_size = placings.len();
+        for (place, name) in placings.iter().enumerate() {
+            if let Some(participant) = self
+                .participants
+                .iter_mut()
+                .find(|participant| participant.name == *name)
+            {
+                participant.points += (table_size - 1 - place) as u32;
+                participant.tables_played += 1;
+                if let TournamentFormat::SingleElimination {
+                    advancing_per_table,
+                } = self.config.format
+                {
+                    participant.eliminated |= place >= advancing_per_table;
+                }
+            }
+        }
+        table.placings = Some(placings.clone());
+        let event = TournamentEvent::TableFinished {
+            round: table.round,
+            table: table.table,
+            placings: placings.clone(),
+        };
+        self.broadcast(event);
+
+        let TournamentState::Running { round } = self.state else {
+            return None;
+        };
+        let round_tables = self.tables.iter().filter(|table| table.round == round);
+        if round_tables.clone().any(|table| table.placings.is_none()) {
+            return None;
+        }
+        let last_round = match self.config.format {
+            TournamentFormat::SingleElimination { .. } => round_tables.count() == 1,
+            TournamentFormat::Swiss { rounds } => round >= rounds,
+        };
+        if last_round {
+            // A single elimination final is won at the table, not on points.
+            let winner = match self.config.format {
+                TournamentFormat::SingleElimination { .. } => placings[0].clone(),
+                TournamentFormat::Swiss { .. } => self.standings()[0].name.clone(),
+            };
+            self.state = TournamentState::Finished {
+                winner: winner.clone(),
+            };
+            self.broadcast(TournamentEvent::TournamentFinished { winner });
+            return None;
+        }
+        Some(self.next_round())
+    }
+
+    pub fn subscribe(&mut self) -> UnboundedReceiver<TournamentEvent> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.subscribers.push(tx);
+        rx
+    }
+
+    /// Best first: points, then seeding.
+    pub fn standings(&self) -> Vec<Participant> {
+        let mut standings = self.participants.clone();
+        standings
+            .sort_by_key(|participant| (participant.eliminated, u32::MAX - participant.points));
+        standings
+    }
+
+    pub fn summary(&self) -> TournamentStandings {
+        TournamentStandings {
+            tournament_id: self.id,
+            config: self.config.clone(),
+            state: self.state.clone(),
+            standings: self.standings(),
+            tables: self.tables.clone(),
+        }
+    }
+
+    fn next_round(&mut self) -> Vec<Vec<PlayerName>> {
+        let round = match self.state {
+            TournamentState::Running { round } => round + 1,
+            _ => 1,
+        };
+        self.state = TournamentState::Running { round };
+        self.broadcast(TournamentEvent::RoundStarted(round));
+
+        let players: Vec<PlayerName> = self
+            .standings()
+            .into_iter()
+            .filter(|participant| !participant.eliminated)
+            .map(|participant| participant.name)
+            .collect();
+        let table_count = players.len().div_ceil(self.config.table_size);
+        match self.config.format {
+            // Deals players like cards so the best seeds meet as late as possible.
+            TournamentFormat::SingleElimination { .. } => {
+                let mut tables = vec![vec![]; table_count];
+                for (idx, name) in players.into_iter().enumerate() {
+                    tables[idx % table_count].push(name);
+                }
+                tables
+            }
+            // Players with close points share a table.
+            TournamentFormat::Swiss { .. } => {
+                let mut players = players.into_iter();
+                (0..table_count)
+                    .map(|table| {
+                        let remaining = players.len();
+                        let size = remaining.div_ceil(table_count - table);
+                        players.by_ref().take(size).collect()
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    fn broadcast(&mut self, event: TournamentEvent) {
+        self.subscribers
+            .retain(|subscriber| subscriber.send(event.clone()).is_ok());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::history::testing_summary;
+
+    use super::*;
+
+    fn tournament(format: TournamentFormat, table_size: usize, players: usize) -> Tournament {
+        let config = TournamentConfig {
+            name: "office".into(),
+            format,
+            table_size,
+            room: RoomConfig::default(),
+        };
+        assert!(config.is_valid());
+        let mut tournament = Tournament::new(0, config);
+        for idx in 0..players {
+            tournament.register(format!("p{idx}")).unwrap();
+        }
+        tournament
+    }
+
+    /// The game played at `room_id`, finished in the order of `placings`.
+    fn game(room_id: RoomId, placings: &[PlayerName]) -> GameSummary {
+        let names: Vec<&str> = placings.iter().map(String::as_str).collect();
+        GameSummary {
+            room_id,
+            ..testing_summary(0, &names)
+        }
+    }
+
+    /// Seats every table of the round and ends them in seat order.
+    fn play_round(
+        tournament: &mut Tournament,
+        tables: Vec<Vec<PlayerName>>,
+        next_room: &mut RoomId,
+    ) -> Option<Vec<Vec<PlayerName>>> {
+        let mut rooms = vec![];
+        for players in tables {
+            tournament.add_table(*next_room, players.clone());
+            rooms.push((*next_room, players));
+            *next_room += 1;
+        }
+        let mut next = None;
+        for (room_id, players) in rooms {
+            assert!(tournament.plays_in(room_id));
+            next = tournament.record_result(&game(room_id, &players));
+        }
+        next
+    }
+
+    #[test]
+    fn registration_closes_at_start() {
+        let mut tournament = tournament(TournamentFormat::Swiss { rounds: 1 }, 4, 1);
+        assert!(tournament.register("p0".into()) == Err(TournamentError::NameAlreadyExists));
+        assert!(tournament.start() == Err(TournamentError::NotEnoughParticipants));
+        tournament.register("p1".into()).unwrap();
+        assert!(tournament.start().is_ok());
+        assert!(tournament.register("p2".into()) == Err(TournamentError::AlreadyStarted));
+    }
+
+    #[test]
+    fn single_elimination_advances_table_winners() {
+        let format = TournamentFormat::SingleElimination {
+            advancing_per_table: 1,
+        };
+        let mut tournament = tournament(format, 3, 7);
+        let mut feed = tournament.subscribe();
+        let mut next_room = 0;
+
+        let tables = tournament.start().unwrap();
+        let sizes: Vec<usize> = tables.iter().map(Vec::len).collect();
+        assert!(sizes == vec![3, 2, 2]);
+        assert!(tables[0] == vec!["p0", "p3", "p6"]);
+
+        let tables = play_round(&mut tournament, tables, &mut next_room).unwrap();
+        assert!(tables == vec![vec!["p0", "p1", "p2"]]);
+        assert!(play_round(&mut tournament, tables, &mut next_room).is_none());
+        assert!(
+            *tournament.state()
+                == TournamentState::Finished {
+                    winner: "p0".into()
+                }
+        );
+        let standings = tournament.standings();
+        assert!(standings[0].name == "p0" && !standings[0].eliminated);
+        assert!(standings.iter().filter(|p| p.eliminated).count() == 6);
+
+        let mut last = None;
+        while let Ok(event) = feed.try_recv() {
+            last = Some(event);
+        }
+        assert!(matches!(
+            last,
+            Some(TournamentEvent::TournamentFinished { winner }) if winner == "p0"
+        ));
+    }
+
+    #[test]
+    fn swiss_plays_every_round_on_points() {
+        let mut tournament = tournament(TournamentFormat::Swiss { rounds: 2 }, 2, 4);
+        let mut next_room = 0;
+
+        let tables = tournament.start().unwrap();
+        assert!(tables == vec![vec!["p0", "p1"], vec!["p2", "p3"]]);
+        let tables = play_round(&mut tournament, tables, &mut next_room).unwrap();
+        // Winners meet winners.
+        assert!(tables == vec![vec!["p0", "p2"], vec!["p1", "p3"]]);
+        assert!(play_round(&mut tournament, tables, &mut next_room).is_none());
+
+        let standings = tournament.standings();
+        let points: Vec<u32> = standings.iter().map(|p| p.points).collect();
+        assert!(points == vec![2, 1, 1, 0]);
+        assert!(standings
+            .iter()
+            .all(|p| p.tables_played == 2 && !p.eliminated));
+        assert!(
+            *tournament.state()
+                == TournamentState::Finished {
+                    winner: "p0".into()
+                }
+        );
+    }
+
+    #[test]
+    fn missing_players_finish_last() {
+        let mut tournament = tournament(TournamentFormat::Swiss { rounds: 1 }, 3, 3);
+        let tables = tournament.start().unwrap();
+        tournament.add_table(9, tables[0].clone());
+        let game = game(9, &["p2".into(), "intruder".into()]);
+        assert!(tournament.record_result(&game).is_none());
+        assert!(!tournament.plays_in(9));
+
+        let placings = tournament.summary().tables[0].placings.clone().unwrap();
+        assert!(placings == vec!["p2", "p0", "p1"]);
+    }
+
+    #[test]
+    fn participants_find_their_table_by_token() {
+        let mut tournament = tournament(TournamentFormat::Swiss { rounds: 1 }, 2, 3);
+        let token = tournament.register("p3".into()).unwrap();
+        assert!(tournament
+            .participant(&token)
+            .is_some_and(|name| name == "p3"));
+        assert!(tournament.participant("guess").is_none());
+
+        let tables = tournament.start().unwrap();
+        assert!(tournament.table_of(&"p3".into()).is_none());
+        tournament.add_table(9, tables[1].clone());
+        assert!(tournament
+            .table_of(&"p3".into())
+            .is_some_and(|table| table.room_id == 9));
+        assert!(tournament.table_players(9) == Some(&tables[1][..]));
+    }
+
+    #[test]
+    fn abandoned_tables_place_present_players_first() {
+        let mut tournament = tournament(TournamentFormat::Swiss { rounds: 1 }, 3, 3);
+        let tables = tournament.start().unwrap();
+        tournament.add_table(9, tables[0].clone());
+        assert!(tournament.abandon_table(9, &["p1".into()]).is_none());
+        assert!(tournament.abandon_table(9, &[]).is_none());
+
+        let placings = tournament.summary().tables[0].placings.clone().unwrap();
+        assert!(placings == vec!["p1", "p0", "p2"]);
+        assert!(
+            *tournament.state()
+                == TournamentState::Finished {
+                    winner: "p1".into()
+                }
+        );
+    }
+}
@@ -1,12 +1,19 @@
-use std::{num::ParseIntError, pin::pin};
+use std::pin::pin;
 
 use actix_ws::{AggregatedMessage, AggregatedMessageStream, Session};
 use futures_util::future::{select, Either};
 use tokio::sync::mpsc::UnboundedReceiver;
 
-use crate::room::errors::GameError;
+use crate::protocol::{ClientMessage, ClientRequest, CommandResponse};
 use crate::room::events::RoomEvent;
-use crate::{consts::PlayerId, room::commander::RoomCommander};
+use crate::{
+    consts::{PlayerId, SpectatorId},
+    matchmaking::{MatchmakingEvent, QueueUpdate, TicketId},
+    room::commander::RoomCommander,
+    room_code,
+    server::ServerCommander,
+    tournament::TournamentEvent,
+};
 
 pub struct WsClient {
     player_id: PlayerId,
@@ -39,239 +46,300 @@ impl WsClient {
             let player_message = pin!(self.stream.recv());
             match select(room_message, player_message).await {
                 Either::Left((Some(room_event), _)) => {
-                    self.session
+                    if self
+                        .session
                         .text(serde_json::to_string(&room_event).unwrap())
                         .await
-                        .unwrap();
+                        .is_err()
+                    {
+                        break;
+                    }
+                    // The seat is gone, the socket goes with it.
+                    if matches!(room_event, RoomEvent::PlayerKicked(id) if id == self.player_id) {
+                        let _ = self.session.close(None).await;
+                        return;
+                    }
+                }
+                // The room is gone, there is nothing to come back to.
+                Either::Left((None, _)) => {
+                    let _ = self.session.close(None).await;
+                    return;
                 }
                 Either::Right((Some(Ok(msg)), _)) => match msg {
                     AggregatedMessage::Text(msg) => {
-                        let msg: &str = &msg;
-                        let to_send = match msg {
-                            "/start" => self.room_commander.start_game().await,
-                            "/ready" => self.room_commander.set_player_ready(self.player_id).await,
-                            "/draw" => self.room_commander.draw_card(self.player_id).await,
-                            "/discard" => self.room_commander.discard_card(self.player_id).await,
-                            "/crabul" => self.room_commander.go_crabul(self.player_id).await,
-                            swap_command if swap_command.starts_with("/swap ") => {
-                                Self::swap(
-                                    self.player_id,
-                                    self.room_commander.clone(),
-                                    swap_command,
-                                )
-                                .await
-                            }
-                            pow1_command if pow1_command.starts_with("/pow1 ") => {
-                                Self::pow1(
-                                    self.player_id,
-                                    self.room_commander.clone(),
-                                    pow1_command,
-                                )
-                                .await
-                            }
-                            pow2_command if pow2_command.starts_with("/pow2 ") => {
-                                Self::pow2(
-                                    self.player_id,
-                                    self.room_commander.clone(),
-                                    pow2_command,
-                                )
-                                .await
-                            }
-                            pow3_command if pow3_command.starts_with("/pow3 ") => {
-                                Self::pow3(
-                                    self.player_id,
-                                    self.room_commander.clone(),
-                                    pow3_command,
-                                )
-                                .await
-                            }
-                            pow4_1_command if pow4_1_command.starts_with("/pow4_1 ") => {
-                                Self::pow4_1(
-                                    self.player_id,
-                                    self.room_commander.clone(),
-                                    pow4_1_command,
-                                )
-                                .await
-                            }
-                            pow4_2_command if pow4_2_command.starts_with("/pow4_2 ") => {
-                                Self::pow4_2(
-                                    self.player_id,
-                                    self.room_commander.clone(),
-                                    pow4_2_command,
-                                )
-                                .await
-                            }
-                            throw_command if throw_command.starts_with("/throw ") => {
-                                Self::throw(
-                                    self.player_id,
-                                    self.room_commander.clone(),
-                                    throw_command,
-                                )
-                                .await
-                            }
-                            throw_2_command if throw_2_command.starts_with("/throw_2 ") => {
-                                Self::throw_2(
-                                    self.player_id,
-                                    self.room_commander.clone(),
-                                    throw_2_command,
-                                )
-                                .await
-                            }
-                            _ => {
-                                self.session.text("Command not recognized").await.unwrap();
-                                Ok(())
-                            }
-                        };
-                        if let Err(err) = to_send {
-                            self.session
-                                .text(serde_json::to_string(&err).unwrap())
-                                .await
-                                .unwrap();
+                        match Self::handle_message(
+                            self.player_id,
+                            &self.room_commander,
+                            &mut self.session,
+                            &msg,
+                        )
+                        .await
+                        {
+                            AfterMessage::Listen => {}
+                            AfterMessage::Disconnect => break,
+                            AfterMessage::Stop => return,
                         }
                     }
-                    AggregatedMessage::Close(_) => {
-                        let _ = self.room_commander.remove_player(self.player_id).await;
-                        break;
-                    }
+                    AggregatedMessage::Close(_) => break,
                     _ => {}
                 },
-                _ => {
-                    // todo: Should log what happened
-                    break;
-                }
+                _ => break,
             }
         }
+        // Keep the seat: the player can come back through the resume endpoint.
+        self.room_commander
+            .disconnect_player(self.player_id, self.player_channel)
+            .await;
     }
 
-    async fn swap(
+    /// Runs one client message against the room. JSON requests always get a
+    /// response echoing their request id, slash commands only report errors.
+    /// Query replies are sent before the response.
+    async fn handle_message(
         player_id: PlayerId,
-        room_commander: RoomCommander,
-        command: &str,
-    ) -> Result<(), GameError> {
-        if let Ok(params) = Self::parse_command(command) {
-            if let Some(card_idx) = params.first() {
-                return room_commander.swap_card(player_id, *card_idx).await;
+        room_commander: &RoomCommander,
+        session: &mut Session,
+        text: &str,
+    ) -> AfterMessage {
+        let request = ClientRequest::parse(text);
+        let leaving = matches!(
+            request,
+            Ok(ClientRequest {
+                message: ClientMessage::Leave,
+                ..
+            })
+        );
+        let (request_id, result) = match request {
+            Ok(request) => (
+                request.request_id,
+                request.message.execute(player_id, room_commander).await,
+            ),
+            Err(err) => (ClientRequest::request_id_of(text), Err(err)),
+        };
+
+        let result = match result {
+            Ok(Some(reply)) => {
+                if session
+                    .text(serde_json::to_string(&reply).unwrap())
+                    .await
+                    .is_err()
+                {
+                    return AfterMessage::Disconnect;
+                }
+                Ok(())
             }
-        }
-        Err(GameError::UnableToParseCommand)
-    }
-    async fn pow1(
-        player_id: PlayerId,
-        room_commander: RoomCommander,
-        command: &str,
-    ) -> Result<(), GameError> {
-        if let Ok(params) = Self::parse_command(command) {
-            if let Some(card_idx) = params.first() {
-                return room_commander.peek_own_card(player_id, *card_idx).await;
+            Ok(None) => Ok(()),
+            Err(err) => Err(err),
+        };
+        let response = if text.starts_with('/') {
+            result.err().map(|err| serde_json::to_string(&err).unwrap())
+        } else {
+            Some(serde_json::to_string(&CommandResponse { request_id, result }).unwrap())
+        };
+        if let Some(response) = response {
+            if session.text(response).await.is_err() {
+                return AfterMessage::Disconnect;
             }
         }
-        Err(GameError::UnableToParseCommand)
+        if leaving {
+            let _ = session.clone().close(None).await;
+            return AfterMessage::Stop;
+        }
+        AfterMessage::Listen
     }
+}
 
-    async fn pow2(
-        player_id: PlayerId,
+/// What the client loop does once a message is handled.
+enum AfterMessage {
+    Listen,
+    /// The socket is dead, the seat is kept for a resume.
+    Disconnect,
+    /// The player left, there is no seat to keep.
+    Stop,
+}
+
+pub struct WsSpectator {
+    spectator_id: SpectatorId,
+    room_commander: RoomCommander,
+    spectator_channel: UnboundedReceiver<RoomEvent>,
+    stream: AggregatedMessageStream,
+    session: Session,
+}
+
+impl WsSpectator {
+    pub fn new(
+        spectator_id: SpectatorId,
         room_commander: RoomCommander,
-        command: &str,
-    ) -> Result<(), GameError> {
-        if let Ok(params) = Self::parse_command(command) {
-            if let (Some(other_player_id), Some(other_card_idx)) = (params.first(), params.get(1)) {
-                return room_commander
-                    .peek_other_card(player_id, *other_player_id as PlayerId, *other_card_idx)
-                    .await;
-            }
+        spectator_channel: UnboundedReceiver<RoomEvent>,
+        stream: AggregatedMessageStream,
+        session: Session,
+    ) -> Self {
+        Self {
+            spectator_id,
+            room_commander,
+            spectator_channel,
+            stream,
+            session,
         }
-        Err(GameError::UnableToParseCommand)
     }
 
-    async fn pow3(
-        player_id: PlayerId,
-        room_commander: RoomCommander,
-        command: &str,
-    ) -> Result<(), GameError> {
-        if let Ok(params) = Self::parse_command(command) {
-            if let (Some(card_idx), Some(other_player_id), Some(other_card_idx)) =
-                (params.first(), params.get(1), params.get(2))
-            {
-                return room_commander
-                    .blind_swap(
-                        player_id,
-                        *card_idx,
-                        *other_player_id as PlayerId,
-                        *other_card_idx,
-                    )
-                    .await;
+    /// Spectators only listen: anything but a close frame is ignored.
+    pub async fn run(mut self) {
+        loop {
+            let room_message = pin!(self.spectator_channel.recv());
+            let spectator_message = pin!(self.stream.recv());
+            match select(room_message, spectator_message).await {
+                Either::Left((Some(room_event), _)) => {
+                    if self
+                        .session
+                        .text(serde_json::to_string(&room_event).unwrap())
+                        .await
+                        .is_err()
+                    {
+                        break;
+                    }
+                }
+                Either::Left((None, _)) => {
+                    let _ = self.session.close(None).await;
+                    return;
+                }
+                Either::Right((Some(Ok(AggregatedMessage::Close(_))), _)) => break,
+                Either::Right((Some(Ok(_)), _)) => {}
+                _ => break,
             }
         }
-        Err(GameError::UnableToParseCommand)
+        self.room_commander.remove_spectator(self.spectator_id);
     }
+}
 
-    async fn pow4_1(
-        player_id: PlayerId,
-        room_commander: RoomCommander,
-        command: &str,
-    ) -> Result<(), GameError> {
-        if let Ok(params) = Self::parse_command(command) {
-            if let (Some(other_player_id), Some(other_card_idx)) = (params.first(), params.get(1)) {
-                return room_commander
-                    .check_and_swap_stage1(player_id, *other_player_id as PlayerId, *other_card_idx)
-                    .await;
-            }
+/// A player waiting in the matchmaking queue. Once seated it carries on as a
+/// `WsClient` over the same connection.
+pub struct WsQueuedPlayer {
+    ticket_id: TicketId,
+    players: usize,
+    server_commander: ServerCommander,
+    queue_channel: UnboundedReceiver<QueueUpdate>,
+    stream: AggregatedMessageStream,
+    session: Session,
+}
+
+impl WsQueuedPlayer {
+    pub fn new(
+        ticket_id: TicketId,
+        players: usize,
+        server_commander: ServerCommander,
+        queue_channel: UnboundedReceiver<QueueUpdate>,
+        stream: AggregatedMessageStream,
+        session: Session,
+    ) -> Self {
+        Self {
+            ticket_id,
+            players,
+            server_commander,
+            queue_channel,
+            stream,
+            session,
         }
-        Err(GameError::UnableToParseCommand)
     }
 
-    async fn pow4_2(
-        player_id: PlayerId,
-        room_commander: RoomCommander,
-        command: &str,
-    ) -> Result<(), GameError> {
-        if let Ok(params) = Self::parse_command(command) {
-            if let Some(card_idx) = params.first() {
-                return room_commander
-                    .check_and_swap_stage2(player_id, Some(*card_idx))
-                    .await;
-            } else {
-                return room_commander.check_and_swap_stage2(player_id, None).await;
+    pub async fn run(mut self) {
+        let seat = loop {
+            let queue_message = pin!(self.queue_channel.recv());
+            let player_message = pin!(self.stream.recv());
+            match select(queue_message, player_message).await {
+                Either::Left((Some(QueueUpdate::Position(position)), _)) => {
+                    let event = MatchmakingEvent::QueuePosition {
+                        position,
+                        players: self.players,
+                    };
+                    if self
+                        .session
+                        .text(serde_json::to_string(&event).unwrap())
+                        .await
+                        .is_err()
+                    {
+                        break None;
+                    }
+                }
+                Either::Left((Some(QueueUpdate::Found(seat)), _)) => break Some(seat),
+                // No room could be made for the match.
+                Either::Left((None, _)) => {
+                    let _ = self.session.close(None).await;
+                    return;
+                }
+                Either::Right((Some(Ok(AggregatedMessage::Close(_))), _)) => break None,
+                Either::Right((Some(Ok(_)), _)) => {}
+                _ => break None,
             }
-        }
-        Err(GameError::UnableToParseCommand)
+        };
+        let Some(seat) = seat else {
+            self.server_commander.leave_queue(self.ticket_id);
+            return;
+        };
+
+        let event = MatchmakingEvent::MatchFound {
+            room_id: seat.room_id,
+            room_code: room_code::to_code(seat.room_id),
+            player_id: seat.player_id,
+        };
+        let _ = self
+            .session
+            .text(serde_json::to_string(&event).unwrap())
+            .await;
+        let client = WsClient::new(
+            seat.player_id,
+            seat.commander,
+            seat.player_channel,
+            self.stream,
+            self.session,
+        );
+        client.run().await;
     }
-    async fn throw(
-        player_id: PlayerId,
-        room_commander: RoomCommander,
-        command: &str,
-    ) -> Result<(), GameError> {
-        if let Ok(params) = Self::parse_command(command) {
-            if let (Some(other_player_id), Some(other_card_idx)) = (params.first(), params.get(1)) {
-                return room_commander
-                    .throw_same_card(player_id, *other_player_id as PlayerId, *other_card_idx)
-                    .await;
-            }
+}
+
+/// Follows the feed of a tournament until either side goes away.
+pub struct WsTournamentWatcher {
+    feed: UnboundedReceiver<TournamentEvent>,
+    stream: AggregatedMessageStream,
+    session: Session,
+}
+
+impl WsTournamentWatcher {
+    pub fn new(
+        feed: UnboundedReceiver<TournamentEvent>,
+        stream: AggregatedMessageStream,
+        session: Session,
+    ) -> Self {
+        Self {
+            feed,
+            stream,
+            session,
         }
-        Err(GameError::UnableToParseCommand)
     }
 
-    async fn throw_2(
-        player_id: PlayerId,
-        room_commander: RoomCommander,
-        command: &str,
-    ) -> Result<(), GameError> {
-        if let Ok(params) = Self::parse_command(command) {
-            if let Some(card_idx) = params.first() {
-                return room_commander
-                    .select_card_to_give_away(player_id, *card_idx)
-                    .await;
+    pub async fn run(mut self) {
+        loop {
+            let tournament_event = pin!(self.feed.recv());
+            let watcher_message = pin!(self.stream.recv());
+            match select(tournament_event, watcher_message).await {
+                Either::Left((Some(event), _)) => {
+                    if self
+                        .session
+                        .text(serde_json::to_string(&event).unwrap())
+                        .await
+                        .is_err()
+                    {
+                        return;
+                    }
+                }
+                Either::Left((None, _)) => {
+                    let _ = self.session.close(None).await;
+                    return;
+                }
+                Either::Right((Some(Ok(AggregatedMessage::Close(_))), _)) => return,
+                Either::Right((Some(Ok(_)), _)) => {}
+                _ => return,
             }
         }
-        Err(GameError::UnableToParseCommand)
-    }
-
-    fn parse_command(command: &str) -> Result<Vec<usize>, ParseIntError> {
-        command
-            .split(" ")
-            .skip(1)
-            .filter(|split| !split.is_empty())
-            .map(|val| val.parse::<usize>())
-            .collect()
     }
 }
@@ -1,7 +1,12 @@
 use std::{net::TcpListener, time::Duration};
 
-use crabul::{api::run, room::events::RoomEvent};
-use futures_util::StreamExt;
+use crabul::{
+    api::run,
+    matchmaking::MatchmakingEvent,
+    protocol::CommandResponse,
+    room::{errors::GameError, events::RoomEvent},
+};
+use futures_util::{SinkExt, StreamExt};
 use tokio::time::timeout;
 use tokio_tungstenite::{connect_async, tungstenite::Message};
 
@@ -44,12 +49,12 @@ async fn join_game_room() {
         Message::Text(payload) => serde_json::from_str::<RoomEvent>(&payload).unwrap(),
         _ => panic!("Error when reading ws msg"),
     };
-    let room_id = match event {
-        RoomEvent::PlayerJoined { room_id, .. } => room_id,
+    let room_code = match event {
+        RoomEvent::PlayerJoined { room_code, .. } => room_code,
         _ => panic!("Wrong event received"),
     };
 
-    let (mut ws_stream2, _) = connect_async(&format!("ws://{address}/connect/{room_id}?name=gioggi"))
+    let (mut ws_stream2, _) = connect_async(&format!("ws://{address}/connect/{room_code}?name=gioggi"))
         .await
         .unwrap();
 
@@ -64,3 +69,99 @@ async fn join_game_room() {
         _ => panic!("Wrong event"),
     };
 }
+
+#[tokio::test]
+async fn spectate_game_room() {
+    let address = spawn_app();
+
+    let (mut ws_stream, _) = connect_async(&format!("ws://{address}/connect?name=gio"))
+        .await
+        .unwrap();
+
+    let received = timeout(Duration::from_millis(1), ws_stream.next()).await.unwrap().unwrap().unwrap();
+    let room_id = match received {
+        Message::Text(payload) => match serde_json::from_str::<RoomEvent>(&payload).unwrap() {
+            RoomEvent::PlayerJoined { room_id, .. } => room_id,
+            _ => panic!("Wrong event received"),
+        },
+        _ => panic!("Error when reading ws msg"),
+    };
+
+    let (mut spectator_stream, _) = connect_async(&format!("ws://{address}/spectate/{room_id}"))
+        .await
+        .unwrap();
+
+    let received = timeout(Duration::from_millis(100), spectator_stream.next()).await.unwrap().unwrap().unwrap();
+    match received {
+        Message::Text(payload) => assert!(matches!(
+            serde_json::from_str::<RoomEvent>(&payload).unwrap(),
+            RoomEvent::StateSnapshot(_)
+        )),
+        _ => panic!("Wrong event"),
+    };
+}
+
+#[tokio::test]
+async fn json_command_echoes_request_id() {
+    let address = spawn_app();
+
+    let (mut ws_stream, _) = connect_async(&format!("ws://{address}/connect?name=gio"))
+        .await
+        .unwrap();
+    let _ = timeout(Duration::from_millis(100), ws_stream.next()).await.unwrap();
+
+    ws_stream
+        .send(Message::Text(r#"{"type":"DrawCard","request_id":5}"#.into()))
+        .await
+        .unwrap();
+
+    let received = timeout(Duration::from_millis(100), ws_stream.next()).await.unwrap().unwrap().unwrap();
+    match received {
+        Message::Text(payload) => {
+            let response = serde_json::from_str::<CommandResponse>(&payload).unwrap();
+            assert!(response.request_id == Some(5));
+            assert!(matches!(
+                response.result,
+                Err(GameError::OperationNotAllowedAtCurrentState)
+            ));
+        }
+        _ => panic!("Wrong message"),
+    };
+}
+
+#[tokio::test]
+async fn matchmaking_seats_queued_players_together() {
+    let address = spawn_app();
+    let next_event = |payload: Option<Result<Message, _>>| match payload {
+        Some(Ok(Message::Text(payload))) => serde_json::from_str::<MatchmakingEvent>(&payload).unwrap(),
+        _ => panic!("Error when reading ws msg"),
+    };
+
+    let (mut ws_stream1, _) = connect_async(&format!("ws://{address}/matchmake?name=gio&players=2"))
+        .await
+        .unwrap();
+    let received = timeout(Duration::from_secs(1), ws_stream1.next()).await.unwrap();
+    assert!(matches!(
+        next_event(received),
+        MatchmakingEvent::QueuePosition { position: 1, players: 2 }
+    ));
+
+    let (mut ws_stream2, _) = connect_async(&format!("ws://{address}/matchmake?name=gioggi&players=2"))
+        .await
+        .unwrap();
+    let received = timeout(Duration::from_secs(1), ws_stream1.next()).await.unwrap();
+    let MatchmakingEvent::MatchFound { room_id: first_room, .. } = next_event(received) else {
+        panic!("Wrong event received");
+    };
+    let received = timeout(Duration::from_secs(1), ws_stream2.next()).await.unwrap();
+    let MatchmakingEvent::MatchFound { room_id: second_room, .. } = next_event(received) else {
+        panic!("Wrong event received");
+    };
+    assert!(first_room == second_room);
+
+    let received = timeout(Duration::from_secs(1), ws_stream2.next()).await.unwrap().unwrap().unwrap();
+    match received {
+        Message::Text(payload) => serde_json::from_str::<RoomEvent>(&payload).unwrap(),
+        _ => panic!("Wrong event"),
+    };
+}